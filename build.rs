@@ -1,15 +1,24 @@
-use std::process::Command;
+use std::process;
+
+#[path = "src/shader_compiler.rs"]
+#[allow(dead_code)]
+mod shader_compiler;
+#[path = "src/shader_list.rs"]
+mod shader_list;
 
 fn main() {
-    println!("cargo:rerun-if-changed=shaders");
-    assert!(Command::new("glslc")
-        .args(&["shaders/shader.vert", "-o", "shaders/vert.spv"])
-        .status()
-        .unwrap()
-        .success());
-    assert!(Command::new("glslc")
-        .args(&["shaders/shader.frag", "-o", "shaders/frag.spv"])
-        .status()
-        .unwrap()
-        .success());
+    // A directory is scanned for any change, new sources included.
+    println!("cargo:rerun-if-changed={}", shader_list::SHADER_DIR);
+    println!("cargo:rerun-if-changed=src/shader_compiler.rs");
+    println!("cargo:rerun-if-changed=src/shader_list.rs");
+    let mut failed = false;
+    for (source, output) in shader_list::shaders() {
+        if let Err(err) = shader_compiler::compile_to_file(&source, &output) {
+            eprintln!("{}", err);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
 }
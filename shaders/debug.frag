@@ -0,0 +1,68 @@
+#version 450
+
+#define MAX_LIGHTS 8
+
+// See RenderMode::id.
+#define DEBUG_WIREFRAME 1
+#define DEBUG_UV_CHECKER 2
+#define DEBUG_NORMALS 3
+#define DEBUG_DEPTH 4
+#define DEBUG_OVERDRAW 5
+
+struct Light {
+    vec4 position;
+    vec4 color;
+};
+
+layout(set = 0, binding = 0) uniform GlobalUniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    vec4 ambient;
+    uint lightCount;
+    Light lights[MAX_LIGHTS];
+} global;
+
+layout(push_constant) uniform DebugPushConstants {
+    uint mode;
+    float near;
+    float far;
+} pushConstants;
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+layout(location = 2) in vec3 fragPosition;
+layout(location = 3) in vec3 fragNormal;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    switch (pushConstants.mode) {
+    case DEBUG_WIREFRAME:
+        outColor = vec4(0.1, 1.0, 0.3, 1.0);
+        break;
+    case DEBUG_UV_CHECKER: {
+        // 8 squares across the texture, with red and green following u and v to show the orientation.
+        vec2 cell = floor(fragTexCoord * 8.0);
+        float checker = mod(cell.x + cell.y, 2.0) * 0.8 + 0.2;
+        outColor = vec4(fract(fragTexCoord) * checker, checker, 1.0);
+        break;
+    }
+    case DEBUG_NORMALS:
+        outColor = vec4(normalize(fragNormal) * 0.5 + 0.5, 1.0);
+        break;
+    case DEBUG_DEPTH: {
+        float depth = -(global.view * vec4(fragPosition, 1.0)).z;
+        float near = pushConstants.near;
+        float far = pushConstants.far;
+        float t = clamp(log(max(depth, near) / near) / log(far / near), 0.0, 1.0);
+        outColor = vec4(vec3(1.0 - t), 1.0);
+        break;
+    }
+    case DEBUG_OVERDRAW:
+        // Added up by the blending, the tonemapping keeps the heavy areas from saturating too early.
+        outColor = vec4(0.1, 0.04, 0.01, 1.0);
+        break;
+    default:
+        outColor = vec4(1.0, 0.0, 1.0, 1.0);
+    }
+}
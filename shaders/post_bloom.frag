@@ -0,0 +1,26 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform texture2D inputTexture;
+layout(set = 0, binding = 1) uniform sampler inputSampler;
+
+// See PostPushConstants.
+layout(push_constant) uniform PostPushConstants {
+    vec2 texelSize;
+    vec2 direction;
+    vec4 params[3];
+} pushConstants;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+// The blurred bright parts, at half resolution.
+layout(set = 1, binding = 0) uniform texture2D bloomTexture;
+layout(set = 1, binding = 1) uniform sampler bloomSampler;
+
+// params[0].x is the intensity.
+void main() {
+    vec3 color = texture(sampler2D(inputTexture, inputSampler), fragTexCoord).rgb;
+    vec3 bloom = texture(sampler2D(bloomTexture, bloomSampler), fragTexCoord).rgb;
+    outColor = vec4(color + bloom * pushConstants.params[0].x, 1.0);
+}
@@ -0,0 +1,29 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform texture2D inputTexture;
+layout(set = 0, binding = 1) uniform sampler inputSampler;
+
+// See PostPushConstants.
+layout(push_constant) uniform PostPushConstants {
+    vec2 texelSize;
+    vec2 direction;
+    vec4 params[3];
+} pushConstants;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+// 9 tap gaussian along the direction, in 5 taps thanks to bilinear filtering.
+const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+    vec2 offset = pushConstants.direction * pushConstants.texelSize;
+    vec3 color = texture(sampler2D(inputTexture, inputSampler), fragTexCoord).rgb * weights[0];
+    for (int i = 1; i < 3; i++) {
+        color += texture(sampler2D(inputTexture, inputSampler), fragTexCoord + offset * offsets[i]).rgb * weights[i];
+        color += texture(sampler2D(inputTexture, inputSampler), fragTexCoord - offset * offsets[i]).rgb * weights[i];
+    }
+    outColor = vec4(color, 1.0);
+}
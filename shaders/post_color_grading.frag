@@ -0,0 +1,32 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform texture2D inputTexture;
+layout(set = 0, binding = 1) uniform sampler inputSampler;
+
+// See PostPushConstants.
+layout(push_constant) uniform PostPushConstants {
+    vec2 texelSize;
+    vec2 direction;
+    vec4 params[3];
+} pushConstants;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 1, binding = 0) uniform texture3D lutTexture;
+layout(set = 1, binding = 1) uniform sampler lutSampler;
+
+// params[0].x is the strength and params[0].y the size of the table, params[1].xyz and params[2].xyz
+// the range of colors it covers.
+void main() {
+    vec3 color = texture(sampler2D(inputTexture, inputSampler), fragTexCoord).rgb;
+    vec3 domainMin = pushConstants.params[1].xyz;
+    vec3 domainMax = pushConstants.params[2].xyz;
+    vec3 coord = clamp((color - domainMin) / (domainMax - domainMin), 0.0, 1.0);
+    // The first and last entries sit on the centers of the edge texels.
+    float size = pushConstants.params[0].y;
+    coord = (coord * (size - 1.0) + 0.5) / size;
+    vec3 graded = texture(sampler3D(lutTexture, lutSampler), coord).rgb;
+    outColor = vec4(mix(color, graded, pushConstants.params[0].x), 1.0);
+}
@@ -0,0 +1,69 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform texture2D inputTexture;
+layout(set = 0, binding = 1) uniform sampler inputSampler;
+
+// See PostPushConstants.
+layout(push_constant) uniform PostPushConstants {
+    vec2 texelSize;
+    vec2 direction;
+    vec4 params[3];
+} pushConstants;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+#define FXAA_SPAN_MAX 8.0
+#define FXAA_REDUCE_MUL (1.0 / 8.0)
+#define FXAA_REDUCE_MIN (1.0 / 128.0)
+
+// See Tonemap.
+#define TONEMAP_ACES 0
+#define TONEMAP_REINHARD 1
+#define TONEMAP_EXPOSURE 2
+
+// The curves of tonemap.frag, params[0] holding the tonemap and the exposure.
+vec3 tonemap(vec3 color) {
+    color *= pushConstants.params[0].y;
+    uint curve = uint(pushConstants.params[0].x);
+    if (curve == TONEMAP_ACES) {
+        return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+    } else if (curve == TONEMAP_REINHARD) {
+        return color / (1.0 + color);
+    }
+    return 1.0 - exp(-color);
+}
+
+// Perceptual luma of the color once tonemapped, which the edge thresholds are made for.
+float luma(vec3 color) {
+    return sqrt(dot(tonemap(color), vec3(0.299, 0.587, 0.114)));
+}
+
+vec3 sampleColor(vec2 offset) {
+    return texture(sampler2D(inputTexture, inputSampler), fragTexCoord + offset).rgb;
+}
+
+void main() {
+    vec2 t = pushConstants.texelSize;
+    float lumaNW = luma(sampleColor(vec2(-t.x, -t.y)));
+    float lumaNE = luma(sampleColor(vec2(t.x, -t.y)));
+    float lumaSW = luma(sampleColor(vec2(-t.x, t.y)));
+    float lumaSE = luma(sampleColor(vec2(t.x, t.y)));
+    vec3 colorM = sampleColor(vec2(0.0));
+    float lumaM = luma(colorM);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // Blur along the edge, which is perpendicular to the luma gradient.
+    vec2 dir = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * t;
+
+    vec3 colorA = 0.5 * (sampleColor(dir * (1.0 / 3.0 - 0.5)) + sampleColor(dir * (2.0 / 3.0 - 0.5)));
+    vec3 colorB = colorA * 0.5 + 0.25 * (sampleColor(dir * -0.5) + sampleColor(dir * 0.5));
+    float lumaB = luma(colorB);
+    // The wider blur went past the edge.
+    outColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? colorA : colorB, 1.0);
+}
@@ -0,0 +1,33 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform texture2D inputTexture;
+layout(set = 0, binding = 1) uniform sampler inputSampler;
+
+// See PostPushConstants.
+layout(push_constant) uniform PostPushConstants {
+    vec2 texelSize;
+    vec2 direction;
+    vec4 params[3];
+} pushConstants;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+// params[0].x is the threshold, softened by a knee so that the bloom doesn't pop in.
+void main() {
+    // Drawn at half resolution, the four bilinear taps average the sixteen texels underneath.
+    vec2 t = pushConstants.texelSize;
+    vec3 color = 0.25 * (texture(sampler2D(inputTexture, inputSampler), fragTexCoord + vec2(-t.x, -t.y)).rgb
+        + texture(sampler2D(inputTexture, inputSampler), fragTexCoord + vec2(t.x, -t.y)).rgb
+        + texture(sampler2D(inputTexture, inputSampler), fragTexCoord + vec2(-t.x, t.y)).rgb
+        + texture(sampler2D(inputTexture, inputSampler), fragTexCoord + vec2(t.x, t.y)).rgb);
+
+    float threshold = pushConstants.params[0].x;
+    float knee = threshold * 0.5;
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.0001);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 0.0001);
+    outColor = vec4(color * contribution, 1.0);
+}
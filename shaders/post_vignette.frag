@@ -0,0 +1,25 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform texture2D inputTexture;
+layout(set = 0, binding = 1) uniform sampler inputSampler;
+
+// See PostPushConstants.
+layout(push_constant) uniform PostPushConstants {
+    vec2 texelSize;
+    vec2 direction;
+    vec4 params[3];
+} pushConstants;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+// params[0].x is the intensity, params[0].y the distance from the center where the darkening starts,
+// 1 being the corners.
+void main() {
+    vec3 color = texture(sampler2D(inputTexture, inputSampler), fragTexCoord).rgb;
+    float distance = length(fragTexCoord - 0.5) * sqrt(2.0);
+    float radius = pushConstants.params[0].y;
+    float falloff = clamp((distance - radius) / max(1.0 - radius, 0.0001), 0.0, 1.0);
+    outColor = vec4(color * (1.0 - pushConstants.params[0].x * smoothstep(0.0, 1.0, falloff)), 1.0);
+}
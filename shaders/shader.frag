@@ -1,12 +1,59 @@
 #version 450
 
+#define MAX_LIGHTS 8
+
+struct Light {
+    vec4 position;
+    vec4 color;
+};
+
+layout(set = 0, binding = 0) uniform GlobalUniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    vec4 ambient;
+    uint lightCount;
+    Light lights[MAX_LIGHTS];
+} global;
+
+layout(set = 1, binding = 0) uniform ModelUniformBufferObject {
+    mat4 model;
+    vec4 baseColor;
+    float alphaCutoff;
+} object;
+
 layout(location = 0) in vec3 fragColor;
 layout(location = 1) in vec2 fragTexCoord;
+layout(location = 2) in vec3 fragPosition;
+layout(location = 3) in vec3 fragNormal;
 
-layout(set = 1, binding = 1) uniform sampler2D texSampler;
+layout(set = 1, binding = 1) uniform texture2D modelTexture;
+layout(set = 1, binding = 2) uniform sampler texSampler;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = texture(texSampler, fragTexCoord);
+    vec3 normal = normalize(fragNormal);
+    vec3 light = global.ambient.rgb;
+    for (uint i = 0; i < global.lightCount; i++) {
+        Light l = global.lights[i];
+        vec3 direction;
+        float attenuation = 1.0;
+        if (l.position.w == 0.0) {
+            direction = -normalize(l.position.xyz);
+        } else {
+            vec3 toLight = l.position.xyz - fragPosition;
+            float distance = length(toLight);
+            direction = toLight / distance;
+            // Smooth falloff reaching zero at the range of the light.
+            attenuation = clamp(1.0 - pow(distance / l.color.w, 4.0), 0.0, 1.0);
+            attenuation *= attenuation / (1.0 + distance * distance);
+        }
+        light += l.color.rgb * max(dot(normal, direction), 0.0) * attenuation;
+    }
+
+    vec4 color = texture(sampler2D(modelTexture, texSampler), fragTexCoord) * object.baseColor;
+    if (color.a < object.alphaCutoff) {
+        discard;
+    }
+    outColor = vec4(color.rgb * light, color.a);
 }
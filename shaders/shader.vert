@@ -1,26 +1,42 @@
 #version 450
 
+#define MAX_LIGHTS 8
+
+struct Light {
+    vec4 position;
+    vec4 color;
+};
+
 layout(set = 0, binding = 0) uniform GlobalUniformBufferObject {
-    mat4 model;
     mat4 view;
     mat4 proj;
+    vec4 ambient;
+    uint lightCount;
+    Light lights[MAX_LIGHTS];
 } global;
 
 layout(set = 1, binding = 0) uniform ModelUniformBufferObject {
     mat4 model;
-    mat4 view;
-    mat4 proj;
-} model;
+    vec4 baseColor;
+    float alphaCutoff;
+} object;
 
 layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec3 inColor;
 layout(location = 2) in vec2 inTexCoord;
+layout(location = 3) in vec3 inNormal;
 
 layout(location = 0) out vec3 fragColor;
 layout(location = 1) out vec2 fragTexCoord;
+layout(location = 2) out vec3 fragPosition;
+layout(location = 3) out vec3 fragNormal;
 
 void main() {
-    gl_Position = global.proj * global.view * model.model * vec4(inPosition, 1.0);
+    vec4 position = object.model * vec4(inPosition, 1.0);
+    gl_Position = global.proj * global.view * position;
     fragColor = inColor;
     fragTexCoord = inTexCoord;
+    fragPosition = position.xyz;
+    // The model matrix has a uniform scale, so it transforms normals correctly.
+    fragNormal = mat3(object.model) * inNormal;
 }
@@ -0,0 +1,74 @@
+#version 450
+
+// See Tonemap.
+#define TONEMAP_ACES 0
+#define TONEMAP_REINHARD 1
+#define TONEMAP_EXPOSURE 2
+
+// See OutputTransform.
+#define OUTPUT_NONE 0
+#define OUTPUT_SRGB 1
+#define OUTPUT_SCRGB 2
+#define OUTPUT_PQ 3
+
+layout(set = 0, binding = 0) uniform texture2D hdrTexture;
+layout(set = 0, binding = 1) uniform sampler hdrSampler;
+
+layout(push_constant) uniform TonemapPushConstants {
+    uint tonemap;
+    float exposure;
+    uint transform;
+    // Brightness of SDR white in nits.
+    float paperWhite;
+    // Brightness in nits the HDR outputs are mapped to.
+    float peakBrightness;
+} pushConstants;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 curve(vec3 color) {
+    if (pushConstants.tonemap == TONEMAP_ACES) {
+        // Narkowicz's fit of the ACES filmic curve.
+        return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+    } else if (pushConstants.tonemap == TONEMAP_REINHARD) {
+        return color / (1.0 + color);
+    }
+    return 1.0 - exp(-color);
+}
+
+// To [0, 1] in SDR. In HDR, to [0, peak brightness], in paper white units like the scene.
+vec3 tonemap(vec3 color) {
+    color *= pushConstants.exposure;
+    if (pushConstants.transform == OUTPUT_SCRGB || pushConstants.transform == OUTPUT_PQ) {
+        float range = pushConstants.peakBrightness / pushConstants.paperWhite;
+        return curve(color / range) * range;
+    }
+    return curve(color);
+}
+
+vec3 encodeOutput(vec3 color) {
+    if (pushConstants.transform == OUTPUT_SRGB) {
+        return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, greaterThan(color, vec3(0.0031308)));
+    } else if (pushConstants.transform == OUTPUT_SCRGB) {
+        // 1.0 is 80 nits.
+        return color * pushConstants.paperWhite / 80.0;
+    } else if (pushConstants.transform == OUTPUT_PQ) {
+        // Rec.709 to Rec.2020, column major.
+        const mat3 toRec2020 = mat3(
+            0.6274, 0.0691, 0.0164,
+            0.3293, 0.9195, 0.0880,
+            0.0433, 0.0114, 0.8956
+        );
+        // SMPTE ST 2084, 1.0 being 10000 nits.
+        vec3 l = pow(max(toRec2020 * color * pushConstants.paperWhite / 10000.0, 0.0), vec3(0.1593017578125));
+        return pow((0.8359375 + 18.8515625 * l) / (1.0 + 18.6875 * l), vec3(78.84375));
+    }
+    return color;
+}
+
+void main() {
+    vec3 color = texture(sampler2D(hdrTexture, hdrSampler), fragTexCoord).rgb;
+    outColor = vec4(encodeOutput(tonemap(color)), 1.0);
+}
@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+use crate::renderer::{
+    default_pipeline_cache_path, PresentMode, RendererConfig, SurfaceFormat, ValidationFeature,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PresentModeArg {
+    Fifo,
+    FifoRelaxed,
+    Mailbox,
+    Immediate,
+}
+
+impl From<PresentModeArg> for PresentMode {
+    fn from(present_mode: PresentModeArg) -> Self {
+        match present_mode {
+            PresentModeArg::Fifo => PresentMode::Fifo,
+            PresentModeArg::FifoRelaxed => PresentMode::FifoRelaxed,
+            PresentModeArg::Mailbox => PresentMode::Mailbox,
+            PresentModeArg::Immediate => PresentMode::Immediate,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SurfaceFormatArg {
+    Srgb,
+    Srgb10,
+    Scrgb,
+    Hdr10,
+}
+
+impl From<SurfaceFormatArg> for SurfaceFormat {
+    fn from(surface_format: SurfaceFormatArg) -> Self {
+        match surface_format {
+            SurfaceFormatArg::Srgb => SurfaceFormat::Srgb,
+            SurfaceFormatArg::Srgb10 => SurfaceFormat::Srgb10,
+            SurfaceFormatArg::Scrgb => SurfaceFormat::ScRgb,
+            SurfaceFormatArg::Hdr10 => SurfaceFormat::Hdr10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ValidationFeatureArg {
+    GpuAssisted,
+    Synchronization,
+    BestPractices,
+}
+
+impl From<ValidationFeatureArg> for ValidationFeature {
+    fn from(feature: ValidationFeatureArg) -> Self {
+        match feature {
+            ValidationFeatureArg::GpuAssisted => ValidationFeature::GpuAssisted,
+            ValidationFeatureArg::Synchronization => ValidationFeature::Synchronization,
+            ValidationFeatureArg::BestPractices => ValidationFeature::BestPractices,
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "vk-rs", version, about = "Vulkan renderer")]
+pub struct Cli {
+    /// Scene file (.ron or .json), or a single .obj model shown with --texture
+    #[arg(default_value = "scenes/default.ron")]
+    pub path: PathBuf,
+
+    /// Texture used for every model instead of the scene materials' ones
+    #[arg(long)]
+    pub texture: Option<PathBuf>,
+
+    /// Window width in logical pixels
+    #[arg(long, default_value_t = 800)]
+    pub width: u32,
+
+    /// Window height in logical pixels
+    #[arg(long, default_value_t = 600)]
+    pub height: u32,
+
+    /// Borderless fullscreen on the current monitor
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Falls back to fifo when the surface doesn't support it
+    #[arg(long, value_enum, default_value_t = PresentModeArg::Mailbox)]
+    pub present_mode: PresentModeArg,
+
+    /// Same as --present-mode immediate
+    #[arg(long, conflicts_with = "present_mode")]
+    pub no_vsync: bool,
+
+    /// Surface formats to try in order, comma separated, falls back to srgb
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [SurfaceFormatArg::Srgb])]
+    pub surface_format: Vec<SurfaceFormatArg>,
+
+    /// Brightness of SDR white in nits, for the scrgb and hdr10 formats
+    #[arg(long, default_value_t = 200.0)]
+    pub paper_white: f32,
+
+    /// Peak brightness of the display in nits, what the tonemapper maps to on scrgb and hdr10
+    #[arg(long, default_value_t = 1000.0)]
+    pub peak_brightness: f32,
+
+    /// Samples per pixel, lowered to what the device supports, 1 turns multisampling off
+    #[arg(long, default_value_t = 4, value_parser = parse_msaa)]
+    pub msaa: u32,
+
+    /// Shades each sample instead of each pixel when multisampling, smoothing textures too
+    #[arg(long)]
+    pub sample_shading: bool,
+
+    /// File the compiled pipelines are kept in between runs [default: vk-rs/pipeline_cache.bin in the
+    /// user cache directory]
+    #[arg(long, value_name = "FILE")]
+    pub pipeline_cache: Option<PathBuf>,
+
+    /// Compiles every pipeline from scratch and doesn't save them
+    #[arg(long)]
+    pub no_pipeline_cache: bool,
+
+    /// Caps the frame rate by waiting on the CPU, whatever the present mode
+    #[arg(long, value_parser = parse_max_fps)]
+    pub max_fps: Option<f32>,
+
+    /// Index of the device to use, as printed by --list-devices
+    #[arg(long)]
+    pub device: Option<usize>,
+
+    /// Vulkan validation layers, on by default in debug builds
+    #[arg(long, default_value_t = cfg!(debug_assertions), action = clap::ArgAction::Set)]
+    pub validation: bool,
+
+    /// Extra validation checks, comma separated
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub validation_features: Vec<ValidationFeatureArg>,
+
+    /// Validation message not to log, by id name (VUID-...) or number, can be repeated
+    #[arg(long, value_name = "ID")]
+    pub suppress_message: Vec<String>,
+
+    /// Panics after a frame in which validation reported an error
+    #[arg(long)]
+    pub panic_on_validation_error: bool,
+
+    /// Renders this many frames in a hidden window, saves the last one to --output and exits
+    #[arg(long, requires = "output")]
+    pub frames: Option<u32>,
+
+    /// Screenshot file, the format follows the extension
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// CSV file the CPU and GPU times of each pass over the last frames are saved to on exit
+    #[arg(long, value_name = "FILE")]
+    pub profile: Option<PathBuf>,
+
+    /// Prints every device with the checks it has to pass to be used, then exits
+    #[arg(long)]
+    pub list_devices: bool,
+}
+
+fn parse_msaa(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(samples) if samples.is_power_of_two() && samples <= 64 => Ok(samples),
+        _ => Err("expected 1, 2, 4, 8, 16, 32 or 64 samples".to_owned()),
+    }
+}
+
+fn parse_max_fps(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(fps) if fps >= 1.0 => Ok(fps),
+        _ => Err("expected at least 1 frame per second".to_owned()),
+    }
+}
+
+impl Cli {
+    pub fn renderer_config(&self) -> RendererConfig {
+        RendererConfig {
+            device: self.device,
+            validation: self.validation,
+            validation_features: self.validation_features.iter().map(|&f| f.into()).collect(),
+            suppressed_messages: self.suppress_message.clone(),
+            panic_on_validation_error: self.panic_on_validation_error,
+            present_mode: if self.no_vsync {
+                PresentMode::Immediate
+            } else {
+                self.present_mode.into()
+            },
+            surface_formats: self.surface_format.iter().map(|&f| f.into()).collect(),
+            paper_white: self.paper_white,
+            peak_brightness: self.peak_brightness,
+            msaa_samples: self.msaa,
+            sample_shading: self.sample_shading,
+            pipeline_cache: if self.no_pipeline_cache {
+                None
+            } else {
+                Some(
+                    self.pipeline_cache
+                        .clone()
+                        .unwrap_or_else(default_pipeline_cache_path),
+                )
+            },
+        }
+    }
+
+    // Number of frames to draw before saving the screenshot, when running headless.
+    pub fn headless_frames(&self) -> Option<u32> {
+        self.output
+            .as_ref()
+            .map(|_| self.frames.unwrap_or(1).max(1))
+    }
+}
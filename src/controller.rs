@@ -0,0 +1,439 @@
+use cgmath::{Deg, InnerSpace, Point3, Vector3, Zero};
+
+use crate::renderer::Camera;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ControllerMode {
+    Orbit,
+    Fly,
+    FirstPerson,
+}
+
+impl ControllerMode {
+    pub fn next(self) -> Self {
+        match self {
+            ControllerMode::Orbit => ControllerMode::Fly,
+            ControllerMode::Fly => ControllerMode::FirstPerson,
+            ControllerMode::FirstPerson => ControllerMode::Orbit,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct OrbitSettings {
+    // Degrees per mouse count.
+    pub rotate_sensitivity: f32,
+    // Degrees per second when rotating with the keyboard.
+    pub rotate_speed: f32,
+    // Fraction of the distance per scroll step or per second of keyboard zoom.
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    // Time constant of the exponential smoothing in seconds, 0 disables it.
+    pub smoothing: f32,
+}
+
+impl Default for OrbitSettings {
+    fn default() -> Self {
+        OrbitSettings {
+            rotate_sensitivity: 0.2,
+            rotate_speed: 90.0,
+            zoom_speed: 0.1,
+            min_distance: 0.5,
+            smoothing: 0.05,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct FlySettings {
+    // Units per second.
+    pub move_speed: f32,
+    pub look_sensitivity: f32,
+    pub turn_speed: f32,
+    pub smoothing: f32,
+}
+
+impl Default for FlySettings {
+    fn default() -> Self {
+        FlySettings {
+            move_speed: 6.0,
+            look_sensitivity: 0.1,
+            turn_speed: 20.0,
+            smoothing: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct FirstPersonSettings {
+    pub move_speed: f32,
+    pub look_sensitivity: f32,
+    pub turn_speed: f32,
+    pub smoothing: f32,
+    // Units per second squared.
+    pub gravity: f32,
+    pub jump_speed: f32,
+    pub eye_height: f32,
+    pub ground_height: f32,
+}
+
+impl Default for FirstPersonSettings {
+    fn default() -> Self {
+        FirstPersonSettings {
+            move_speed: 4.0,
+            look_sensitivity: 0.1,
+            turn_speed: 20.0,
+            smoothing: 0.1,
+            gravity: 9.81,
+            jump_speed: 4.0,
+            eye_height: 1.7,
+            ground_height: -2.0,
+        }
+    }
+}
+
+// What the user asked for during one frame, independent of how it was entered.
+#[derive(Clone, Copy, Default)]
+pub struct ControllerInput {
+    // Movement in -1..1 along the camera axes.
+    pub forward: f32,
+    pub right: f32,
+    pub up: f32,
+    // Keyboard turning in -1..1, positive turns right.
+    pub turn: f32,
+    // Mouse movement in counts, positive is right and down.
+    pub look_x: f32,
+    pub look_y: f32,
+    // Scroll steps, positive zooms in.
+    pub zoom: f32,
+    pub jump: bool,
+}
+
+pub struct CameraController {
+    mode: ControllerMode,
+    pub orbit: OrbitSettings,
+    pub fly: FlySettings,
+    pub first_person: FirstPersonSettings,
+    // Point the orbit mode turns around.
+    pub target: Point3<f32>,
+    distance: f32,
+    velocity: Vector3<f32>,
+    // Look movement not applied yet, drained over a few frames when smoothing is on.
+    pending_look: (f32, f32),
+    pending_zoom: f32,
+    vertical_velocity: f32,
+    grounded: bool,
+}
+
+fn smoothing_factor(smoothing: f32, time: f32) -> f32 {
+    if smoothing > 0.0 {
+        1.0 - (-time / smoothing).exp()
+    } else {
+        1.0
+    }
+}
+
+impl CameraController {
+    pub fn new(mode: ControllerMode) -> Self {
+        CameraController {
+            mode,
+            orbit: OrbitSettings::default(),
+            fly: FlySettings::default(),
+            first_person: FirstPersonSettings::default(),
+            target: Point3::new(0.0, 0.0, 0.0),
+            distance: 5.0,
+            velocity: Vector3::zero(),
+            pending_look: (0.0, 0.0),
+            pending_zoom: 0.0,
+            vertical_velocity: 0.0,
+            grounded: false,
+        }
+    }
+
+    pub fn mode(&self) -> ControllerMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ControllerMode, camera: &mut Camera) {
+        self.mode = mode;
+        self.velocity = Vector3::zero();
+        self.pending_look = (0.0, 0.0);
+        self.pending_zoom = 0.0;
+        self.vertical_velocity = 0.0;
+        self.grounded = false;
+        if mode == ControllerMode::Orbit {
+            self.distance = (self.target - camera.position)
+                .magnitude()
+                .max(self.orbit.min_distance);
+            camera.look_at(self.target);
+        }
+    }
+
+    pub fn cycle_mode(&mut self, camera: &mut Camera) {
+        self.set_mode(self.mode.next(), camera);
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, input: &ControllerInput, time: f32) {
+        match self.mode {
+            ControllerMode::Orbit => self.update_orbit(camera, input, time),
+            ControllerMode::Fly => self.update_fly(camera, input, time),
+            ControllerMode::FirstPerson => self.update_first_person(camera, input, time),
+        }
+    }
+
+    fn look(&mut self, input: &ControllerInput, smoothing: f32, time: f32) -> (f32, f32) {
+        self.pending_look.0 += input.look_x;
+        self.pending_look.1 += input.look_y;
+        let factor = smoothing_factor(smoothing, time);
+        let applied = (self.pending_look.0 * factor, self.pending_look.1 * factor);
+        self.pending_look.0 -= applied.0;
+        self.pending_look.1 -= applied.1;
+        applied
+    }
+
+    fn rotate(camera: &mut Camera, yaw: f32, pitch: f32) {
+        camera.yaw -= Deg(yaw);
+        camera.pitch = Deg((camera.pitch.0 - pitch).clamp(-89.0, 89.0));
+    }
+
+    fn update_orbit(&mut self, camera: &mut Camera, input: &ControllerInput, time: f32) {
+        let settings = self.orbit;
+        let (look_x, look_y) = self.look(input, settings.smoothing, time);
+        Self::rotate(
+            camera,
+            look_x * settings.rotate_sensitivity + input.turn * settings.rotate_speed * time,
+            look_y * settings.rotate_sensitivity - input.up * settings.rotate_speed * time,
+        );
+
+        self.pending_zoom += input.zoom + input.forward * time;
+        let factor = smoothing_factor(settings.smoothing, time);
+        let zoom = self.pending_zoom * factor;
+        self.pending_zoom -= zoom;
+        self.distance =
+            (self.distance * (1.0 - zoom * settings.zoom_speed)).max(settings.min_distance);
+
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+
+    fn update_fly(&mut self, camera: &mut Camera, input: &ControllerInput, time: f32) {
+        let settings = self.fly;
+        let (look_x, look_y) = self.look(input, settings.smoothing, time);
+        Self::rotate(
+            camera,
+            look_x * settings.look_sensitivity + input.turn * settings.turn_speed * time,
+            look_y * settings.look_sensitivity,
+        );
+
+        let desired = (camera.forward() * input.forward
+            + camera.right() * input.right
+            + Vector3::unit_y() * input.up)
+            * settings.move_speed;
+        self.velocity += (desired - self.velocity) * smoothing_factor(settings.smoothing, time);
+        camera.position += self.velocity * time;
+    }
+
+    fn update_first_person(&mut self, camera: &mut Camera, input: &ControllerInput, time: f32) {
+        let settings = self.first_person;
+        let (look_x, look_y) = self.look(input, settings.smoothing, time);
+        Self::rotate(
+            camera,
+            look_x * settings.look_sensitivity + input.turn * settings.turn_speed * time,
+            look_y * settings.look_sensitivity,
+        );
+
+        // Walking stays on the ground plane whatever the pitch.
+        let mut forward = camera.forward();
+        forward.y = 0.0;
+        if forward.magnitude2() > 0.0 {
+            forward = forward.normalize();
+        }
+        let desired =
+            (forward * input.forward + camera.right() * input.right) * settings.move_speed;
+        self.velocity += (desired - self.velocity) * smoothing_factor(settings.smoothing, time);
+        camera.position += self.velocity * time;
+
+        if input.jump && self.grounded {
+            self.vertical_velocity = settings.jump_speed;
+            self.grounded = false;
+        }
+        self.vertical_velocity -= settings.gravity * time;
+        camera.position.y += self.vertical_velocity * time;
+
+        let floor = settings.ground_height + settings.eye_height;
+        if camera.position.y <= floor {
+            camera.position.y = floor;
+            self.vertical_velocity = 0.0;
+            self.grounded = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    fn run(
+        controller: &mut CameraController,
+        camera: &mut Camera,
+        input: &ControllerInput,
+        time: f32,
+        frames: usize,
+    ) {
+        for _ in 0..frames {
+            controller.update(camera, input, time);
+        }
+    }
+
+    #[test]
+    fn orbit_zooms_and_stops_at_min_distance() {
+        let mut camera = Camera {
+            position: Point3::new(0.0, 0.0, 5.0),
+            ..Default::default()
+        };
+        let mut controller = CameraController::new(ControllerMode::Fly);
+        controller.orbit.smoothing = 0.0;
+        controller.set_mode(ControllerMode::Orbit, &mut camera);
+
+        let zoom_in = ControllerInput {
+            zoom: 1.0,
+            ..Default::default()
+        };
+        controller.update(&mut camera, &zoom_in, 0.016);
+        assert_close((camera.position - controller.target).magnitude(), 4.5);
+
+        let zoom_in = ControllerInput {
+            zoom: 100.0,
+            ..Default::default()
+        };
+        controller.update(&mut camera, &zoom_in, 0.016);
+        assert_close(
+            (camera.position - controller.target).magnitude(),
+            controller.orbit.min_distance,
+        );
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut camera = Camera::default();
+        let mut controller = CameraController::new(ControllerMode::Fly);
+        let look_down = ControllerInput {
+            look_y: 10000.0,
+            ..Default::default()
+        };
+        controller.update(&mut camera, &look_down, 0.016);
+        assert_close(camera.pitch.0, -89.0);
+
+        let look_up = ControllerInput {
+            look_y: -10000.0,
+            ..Default::default()
+        };
+        run(&mut controller, &mut camera, &look_up, 0.016, 2);
+        assert_close(camera.pitch.0, 89.0);
+    }
+
+    #[test]
+    fn first_person_falls_lands_and_jumps() {
+        let mut camera = Camera {
+            position: Point3::new(0.0, 10.0, 0.0),
+            ..Default::default()
+        };
+        let mut controller = CameraController::new(ControllerMode::FirstPerson);
+        let settings = controller.first_person;
+        let floor = settings.ground_height + settings.eye_height;
+        let idle = ControllerInput::default();
+
+        controller.update(&mut camera, &idle, 0.01);
+        assert!(camera.position.y < 10.0);
+        run(&mut controller, &mut camera, &idle, 0.01, 500);
+        assert_close(camera.position.y, floor);
+
+        // Holding jump doesn't jump again before landing.
+        let jump = ControllerInput {
+            jump: true,
+            ..Default::default()
+        };
+        let mut peak = floor;
+        for _ in 0..50 {
+            controller.update(&mut camera, &jump, 0.01);
+            peak = peak.max(camera.position.y);
+        }
+        let expected_peak = settings.jump_speed.powi(2) / (2.0 * settings.gravity);
+        assert!(peak > floor + expected_peak * 0.9);
+        assert!(peak < floor + expected_peak * 1.1);
+
+        run(&mut controller, &mut camera, &idle, 0.01, 200);
+        assert_close(camera.position.y, floor);
+    }
+
+    #[test]
+    fn switching_mode_keeps_position() {
+        let position = Point3::new(1.0, 2.0, 3.0);
+        let mut camera = Camera {
+            position,
+            ..Default::default()
+        };
+        let mut controller = CameraController::new(ControllerMode::Fly);
+        let idle = ControllerInput::default();
+
+        for mode in [
+            ControllerMode::FirstPerson,
+            ControllerMode::Orbit,
+            ControllerMode::Fly,
+        ] {
+            controller.set_mode(mode, &mut camera);
+            assert_close((camera.position - position).magnitude(), 0.0);
+        }
+
+        controller.set_mode(ControllerMode::Orbit, &mut camera);
+        controller.update(&mut camera, &idle, 0.016);
+        assert_close((camera.position - position).magnitude(), 0.0);
+    }
+
+    #[test]
+    fn smoothing_does_not_depend_on_frame_time() {
+        let look = ControllerInput {
+            look_x: 100.0,
+            ..Default::default()
+        };
+        let forward = ControllerInput {
+            forward: 1.0,
+            ..Default::default()
+        };
+        let idle = ControllerInput::default();
+        let smoothing = 0.1;
+        // Looks once then moves forward for 0.2s, in the given number of frames.
+        let simulate = |frames: usize| {
+            let time = 0.2 / frames as f32;
+            let mut camera = Camera::default();
+            let mut controller = CameraController::new(ControllerMode::Fly);
+            controller.fly.smoothing = smoothing;
+            controller.update(&mut camera, &look, time);
+            run(&mut controller, &mut camera, &idle, time, frames - 1);
+            let yaw = camera.yaw.0;
+
+            let mut camera = Camera::default();
+            let mut controller = CameraController::new(ControllerMode::Fly);
+            controller.fly.smoothing = smoothing;
+            run(&mut controller, &mut camera, &forward, time, frames);
+            (yaw, controller.velocity.magnitude())
+        };
+
+        let (coarse_yaw, coarse_speed) = simulate(6);
+        let (fine_yaw, fine_speed) = simulate(29);
+        assert_close(coarse_yaw, fine_yaw);
+        assert_close(coarse_speed, fine_speed);
+
+        // Both only part of the way there.
+        let settings = CameraController::new(ControllerMode::Fly).fly;
+        let expected = 1.0 - (-0.2f32 / smoothing).exp();
+        assert_close(coarse_speed, settings.move_speed * expected);
+        assert_close(
+            coarse_yaw.abs(),
+            look.look_x * settings.look_sensitivity * expected,
+        );
+    }
+}
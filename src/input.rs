@@ -0,0 +1,449 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fmt, fs,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use winit::event::{
+    DeviceEvent, ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+
+const MODIFIERS: [VirtualKeyCode; 6] = [
+    VirtualKeyCode::LShift,
+    VirtualKeyCode::RShift,
+    VirtualKeyCode::LControl,
+    VirtualKeyCode::RControl,
+    VirtualKeyCode::LAlt,
+    VirtualKeyCode::RAlt,
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    // Scroll steps behave like buttons that are pressed and released within the frame.
+    ScrollUp,
+    ScrollDown,
+}
+
+// All the inputs have to be held together, the last one pressed triggers the binding.
+pub type Binding = Vec<Input>;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum AxisSource {
+    Buttons {
+        positive: Binding,
+        negative: Binding,
+    },
+    MouseX(f32),
+    MouseY(f32),
+    Scroll(f32),
+}
+
+// Either every binding, or only the ones replacing those of another map, see overlay.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InputMap {
+    #[serde(default)]
+    pub actions: BTreeMap<String, Vec<Binding>>,
+    #[serde(default)]
+    pub axes: BTreeMap<String, Vec<AxisSource>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let key = |k: VirtualKeyCode| vec![Input::Key(k)];
+        let buttons = |positive: VirtualKeyCode, negative: VirtualKeyCode| AxisSource::Buttons {
+            positive: key(positive),
+            negative: key(negative),
+        };
+
+        let mut actions = BTreeMap::new();
+        actions.insert("quit".to_owned(), vec![key(VirtualKeyCode::Escape)]);
+        actions.insert("reset_camera".to_owned(), vec![key(VirtualKeyCode::R)]);
+        actions.insert(
+            "switch_camera_mode".to_owned(),
+            vec![key(VirtualKeyCode::Tab)],
+        );
+        actions.insert("toggle_projection".to_owned(), vec![key(VirtualKeyCode::P)]);
+        actions.insert(
+            "cycle_present_mode".to_owned(),
+            vec![key(VirtualKeyCode::V)],
+        );
+        actions.insert("cycle_msaa".to_owned(), vec![key(VirtualKeyCode::M)]);
+        actions.insert(
+            "cycle_render_mode".to_owned(),
+            vec![key(VirtualKeyCode::F2)],
+        );
+        actions.insert("cycle_tonemap".to_owned(), vec![key(VirtualKeyCode::T)]);
+        actions.insert(
+            "toggle_post_processing".to_owned(),
+            vec![key(VirtualKeyCode::B)],
+        );
+        actions.insert("toggle_reversed_z".to_owned(), vec![key(VirtualKeyCode::Z)]);
+        actions.insert("jump".to_owned(), vec![key(VirtualKeyCode::Space)]);
+        actions.insert("rebind".to_owned(), vec![key(VirtualKeyCode::F1)]);
+        actions.insert("save_scene".to_owned(), vec![key(VirtualKeyCode::F5)]);
+
+        let mut axes = BTreeMap::new();
+        axes.insert(
+            "move_forward".to_owned(),
+            vec![buttons(VirtualKeyCode::W, VirtualKeyCode::S)],
+        );
+        axes.insert(
+            "move_right".to_owned(),
+            vec![buttons(VirtualKeyCode::D, VirtualKeyCode::A)],
+        );
+        axes.insert(
+            "move_up".to_owned(),
+            vec![buttons(VirtualKeyCode::Space, VirtualKeyCode::C)],
+        );
+        axes.insert(
+            "turn".to_owned(),
+            vec![buttons(VirtualKeyCode::E, VirtualKeyCode::Q)],
+        );
+        axes.insert("look_x".to_owned(), vec![AxisSource::MouseX(1.0)]);
+        axes.insert("look_y".to_owned(), vec![AxisSource::MouseY(1.0)]);
+        axes.insert("zoom".to_owned(), vec![AxisSource::Scroll(1.0)]);
+        axes.insert(
+            "model_x".to_owned(),
+            vec![buttons(VirtualKeyCode::Right, VirtualKeyCode::Left)],
+        );
+        axes.insert(
+            "model_z".to_owned(),
+            vec![buttons(VirtualKeyCode::Down, VirtualKeyCode::Up)],
+        );
+
+        InputMap { actions, axes }
+    }
+}
+
+impl InputMap {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let pretty = ron::ser::PrettyConfig::new();
+        fs::write(path, ron::ser::to_string_pretty(self, pretty)?)?;
+
+        Ok(())
+    }
+
+    // Replaces the bindings of the actions and axes found in `overrides`.
+    pub fn overlay(&mut self, overrides: InputMap) {
+        self.actions.extend(overrides.actions);
+        self.axes.extend(overrides.axes);
+    }
+
+    // The actions and axes bound differently than in `base`, which overlaid on it give this map back.
+    pub fn changes_from(&self, base: &InputMap) -> InputMap {
+        InputMap {
+            actions: self
+                .actions
+                .iter()
+                .filter(|(name, bindings)| base.actions.get(*name) != Some(bindings))
+                .map(|(name, bindings)| (name.clone(), bindings.clone()))
+                .collect(),
+            axes: self
+                .axes
+                .iter()
+                .filter(|(name, sources)| base.axes.get(*name) != Some(sources))
+                .map(|(name, sources)| (name.clone(), sources.clone()))
+                .collect(),
+        }
+    }
+}
+
+// What a binding captured at runtime replaces.
+#[derive(Clone, PartialEq, Debug)]
+pub enum BindingTarget {
+    Action(String),
+    // One side of the button source at `index` in the sources of the axis.
+    AxisButtons {
+        axis: String,
+        index: usize,
+        positive: bool,
+    },
+}
+
+impl fmt::Display for BindingTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BindingTarget::Action(action) => write!(f, "{}", action),
+            BindingTarget::AxisButtons { axis, positive, .. } => {
+                write!(f, "{} {}", axis, if *positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+// Runtime rebinding is done in two steps: the binding of an existing action or axis button is pressed
+// to pick it, then the new chord is pressed.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Capture {
+    SelectBinding,
+    NewBinding(BindingTarget),
+}
+
+pub struct InputState {
+    pub map: InputMap,
+    held: HashSet<Input>,
+    pressed_actions: HashSet<String>,
+    mouse_delta: (f32, f32),
+    scroll: f32,
+    scroll_inputs: Vec<Input>,
+    capture: Option<Capture>,
+}
+
+impl InputState {
+    pub fn new(map: InputMap) -> Self {
+        InputState {
+            map,
+            held: HashSet::new(),
+            pressed_actions: HashSet::new(),
+            mouse_delta: (0.0, 0.0),
+            scroll: 0.0,
+            scroll_inputs: vec![],
+            capture: None,
+        }
+    }
+
+    fn is_held(&self, binding: &Binding) -> bool {
+        !binding.is_empty()
+            && binding
+                .iter()
+                .all(|input| self.held.contains(input) || self.scroll_inputs.contains(input))
+    }
+
+    fn press(&mut self, input: Input) {
+        if let Some(Capture::NewBinding(target)) = &self.capture {
+            if let Input::Key(key) = input {
+                if MODIFIERS.contains(&key) {
+                    return;
+                }
+            }
+            // Modifiers held at that time become part of the chord.
+            let mut binding = self
+                .held
+                .iter()
+                .filter(|i| matches!(i, Input::Key(k) if MODIFIERS.contains(k)))
+                .copied()
+                .collect::<Binding>();
+            binding.push(input);
+            match target.clone() {
+                BindingTarget::Action(action) => self.rebind(&action, binding),
+                BindingTarget::AxisButtons {
+                    axis,
+                    index,
+                    positive,
+                } => self.rebind_axis_buttons(&axis, index, positive, binding),
+            }
+            self.capture = None;
+            return;
+        }
+        if self.capture == Some(Capture::SelectBinding) {
+            if let Some(target) = self.binding_target(input) {
+                self.capture = Some(Capture::NewBinding(target));
+            }
+            return;
+        }
+
+        // When several chords match, like R and Ctrl+R, only the longest ones fire.
+        let matching = self
+            .map
+            .actions
+            .iter()
+            .flat_map(|(action, bindings)| bindings.iter().map(move |b| (action, b)))
+            .filter(|(_, binding)| binding.contains(&input) && self.is_held(binding))
+            .map(|(action, binding)| (action.clone(), binding.len()))
+            .collect::<Vec<(String, usize)>>();
+        let longest = matching.iter().map(|(_, len)| *len).max().unwrap_or(0);
+        for (action, len) in matching {
+            if len == longest {
+                self.pressed_actions.insert(action);
+            }
+        }
+    }
+
+    // The action or axis button the input completes the longest chord of, actions first when a chord is
+    // bound to both, like Space for jump and move_up.
+    fn binding_target(&self, input: Input) -> Option<BindingTarget> {
+        let mut candidates = vec![];
+        for (action, bindings) in self.map.actions.iter() {
+            for binding in bindings.iter() {
+                candidates.push((BindingTarget::Action(action.clone()), binding));
+            }
+        }
+        for (axis, sources) in self.map.axes.iter() {
+            for (index, source) in sources.iter().enumerate() {
+                if let AxisSource::Buttons { positive, negative } = source {
+                    for (side, binding) in [(true, positive), (false, negative)] {
+                        let target = BindingTarget::AxisButtons {
+                            axis: axis.clone(),
+                            index,
+                            positive: side,
+                        };
+                        candidates.push((target, binding));
+                    }
+                }
+            }
+        }
+        let mut best: Option<(BindingTarget, usize)> = None;
+        for (target, binding) in candidates {
+            if binding.contains(&input)
+                && self.is_held(binding)
+                && best.as_ref().is_none_or(|(_, len)| binding.len() > *len)
+            {
+                best = Some((target, binding.len()));
+            }
+        }
+
+        best.map(|(target, _)| target)
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(keycode),
+                        ..
+                    },
+                ..
+            } => {
+                let input = Input::Key(*keycode);
+                match state {
+                    // Key repeat sends presses for a key that is already held.
+                    ElementState::Pressed => {
+                        if self.held.insert(input) {
+                            self.press(input);
+                        }
+                    }
+                    ElementState::Released => {
+                        self.held.remove(&input);
+                    }
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let input = Input::Mouse(*button);
+                match state {
+                    ElementState::Pressed => {
+                        self.held.insert(input);
+                        self.press(input);
+                    }
+                    ElementState::Released => {
+                        self.held.remove(&input);
+                    }
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0,
+                };
+                // Touchpads also send events without any vertical scrolling, horizontal ones for instance.
+                if steps == 0.0 {
+                    return;
+                }
+                self.scroll += steps;
+                let input = if steps > 0.0 {
+                    Input::ScrollUp
+                } else {
+                    Input::ScrollDown
+                };
+                self.scroll_inputs.push(input);
+                self.press(input);
+            }
+            // Keys released while the window is unfocused would otherwise stay held.
+            WindowEvent::Focused(false) => self.held.clear(),
+            _ => (),
+        }
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event {
+            self.mouse_delta.0 += *dx as f32;
+            self.mouse_delta.1 += *dy as f32;
+        }
+    }
+
+    // Whether the action was triggered since the last call to end_frame.
+    pub fn pressed(&self, action: &str) -> bool {
+        self.pressed_actions.contains(action)
+    }
+
+    pub fn held(&self, action: &str) -> bool {
+        self.map
+            .actions
+            .get(action)
+            .is_some_and(|bindings| bindings.iter().any(|b| self.is_held(b)))
+    }
+
+    // Button sources are clamped to -1..1 together, mouse and scroll sources are added as they are.
+    pub fn axis(&self, axis: &str) -> f32 {
+        let mut buttons = 0.0;
+        let mut value = 0.0;
+        for source in self.map.axes.get(axis).into_iter().flatten() {
+            match source {
+                AxisSource::Buttons { positive, negative } => {
+                    buttons += self.is_held(positive) as i32 as f32;
+                    buttons -= self.is_held(negative) as i32 as f32;
+                }
+                AxisSource::MouseX(scale) => value += self.mouse_delta.0 * scale,
+                AxisSource::MouseY(scale) => value += self.mouse_delta.1 * scale,
+                AxisSource::Scroll(scale) => value += self.scroll * scale,
+            }
+        }
+        value + buttons.clamp(-1.0, 1.0)
+    }
+
+    pub fn end_frame(&mut self) {
+        self.pressed_actions.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll = 0.0;
+        self.scroll_inputs.clear();
+    }
+
+    // Replaces every binding of the action.
+    pub fn rebind(&mut self, action: &str, binding: Binding) {
+        self.map.actions.insert(action.to_owned(), vec![binding]);
+    }
+
+    // Replaces one side of a button source of the axis, the other sources being kept.
+    pub fn rebind_axis_buttons(
+        &mut self,
+        axis: &str,
+        index: usize,
+        positive: bool,
+        binding: Binding,
+    ) {
+        let source = self
+            .map
+            .axes
+            .get_mut(axis)
+            .and_then(|sources| sources.get_mut(index));
+        if let Some(AxisSource::Buttons {
+            positive: positive_binding,
+            negative: negative_binding,
+        }) = source
+        {
+            if positive {
+                *positive_binding = binding;
+            } else {
+                *negative_binding = binding;
+            }
+        }
+    }
+
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Capture::SelectBinding);
+    }
+
+    pub fn capture(&self) -> Option<&Capture> {
+        self.capture.as_ref()
+    }
+}
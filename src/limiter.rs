@@ -0,0 +1,41 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+// thread::sleep often wakes up a millisecond or more late, the end of the wait is spun instead.
+const SPIN_TIME: Duration = Duration::from_millis(2);
+
+// Keeps frames from starting more often than the target rate, independently of the present mode.
+pub struct FrameLimiter {
+    frame_time: Duration,
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: f32) -> Self {
+        FrameLimiter {
+            // Only a rate too low to fit the frame time in a Duration, or not a number, fails, which the
+            // command line doesn't let through. Nothing is limited then.
+            frame_time: Duration::try_from_secs_f32(1.0 / max_fps).unwrap_or(Duration::ZERO),
+            next_frame: Instant::now(),
+        }
+    }
+
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if now < self.next_frame {
+            let remaining = self.next_frame - now;
+            if remaining > SPIN_TIME {
+                thread::sleep(remaining - SPIN_TIME);
+            }
+            while Instant::now() < self.next_frame {
+                std::hint::spin_loop();
+            }
+            self.next_frame += self.frame_time;
+        } else {
+            // A late frame doesn't make the next ones start early to catch up.
+            self.next_frame = now + self.frame_time;
+        }
+    }
+}
@@ -1,185 +1,463 @@
-#![windows_subsystem = "windows"]
-
+mod cli;
+mod controller;
+mod input;
+mod limiter;
 mod renderer;
+mod scene;
+mod shader_compiler;
+mod shader_list;
+mod watcher;
 
-use std::{error::Error, time::Instant};
+use std::{error::Error, path::Path, time::Instant};
 
-use cgmath::{Deg, InnerSpace, Matrix4, Vector3, Vector4};
+use cgmath::{Deg, Point3, Vector3};
+use clap::Parser;
+use env_logger::Env;
+use log::{error, info};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use winit::{
     dpi::LogicalSize,
-    event::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Fullscreen, Window, WindowBuilder},
 };
 
-use renderer::Renderer;
+use cli::Cli;
+use controller::{CameraController, ControllerInput, ControllerMode};
+use input::{Capture, InputMap, InputState};
+use limiter::FrameLimiter;
+use renderer::{PostPass, Projection, Renderer, RendererError};
+use scene::Scene;
+use watcher::ShaderWatcher;
+
+const BINDINGS_PATH: &str = "config/input.ron";
+// The bindings changed while running, over those of BINDINGS_PATH. Not tracked by git.
+const USER_BINDINGS_PATH: &str = "config/input.user.ron";
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    // The verbosity is picked with RUST_LOG, like RUST_LOG=debug or RUST_LOG=vulkan::validation=warn.
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let headless_frames = cli.headless_frames();
+
     // Init Window
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("vk-rs")
-        .with_inner_size(LogicalSize::new(800, 600))
+        .with_inner_size(LogicalSize::new(cli.width, cli.height))
+        .with_fullscreen(cli.fullscreen.then_some(Fullscreen::Borderless(None)))
+        .with_visible(headless_frames.is_none() && !cli.list_devices)
         .build(&event_loop)?;
 
+    if cli.list_devices {
+        for device in
+            Renderer::list_devices(window.raw_display_handle(), window.raw_window_handle())?
+        {
+            println!(
+                "{}: {} ({:?}) - {}",
+                device.index,
+                device.name,
+                device.device_type,
+                if device.is_suitable() {
+                    "suitable"
+                } else {
+                    "not suitable"
+                }
+            );
+            for (check, passed) in device.checks.iter() {
+                println!("    [{}] {}", if *passed { "x" } else { " " }, check);
+            }
+        }
+        return Ok(());
+    }
+
     // Init App (including Vulkan)
     let mut renderer = Renderer::new(
         window.raw_display_handle(),
         window.raw_window_handle(),
         window.inner_size().width,
         window.inner_size().height,
+        &cli.renderer_config(),
     )?;
-    let m0 = renderer.load_model("models/cube.obj", "textures/cube.png", false)?;
-    let m1 = renderer.load_model("models/viking_room.obj", "textures/viking_room.png", false)?;
-    renderer.model(m0).position.x = -5.0;
-    renderer.model(m0).position.y = -0.25;
-    renderer.model(m0).position.z = -6.0;
-    renderer.model(m0).theta = 0.0;
-    renderer.model(m1).position.x = 0.0;
-    renderer.model(m1).position.y = -0.25;
-    renderer.model(m1).position.z = -6.0;
-    renderer.model(m1).theta = -90.0;
+    info!(
+        "Surface format {:?} in {:?}, output transform {:?}.",
+        renderer.surface_format().format,
+        renderer.surface_format().color_space,
+        renderer.output_transform()
+    );
+
+    let is_model = cli.path.extension().and_then(|e| e.to_str()) == Some("obj");
+    let (mut scene, scene_path) = if is_model {
+        let texture = match &cli.texture {
+            Some(texture) => texture,
+            None => Err("A texture is needed to show a model, use --texture !")?,
+        };
+        let scene = Scene::from_model(&cli.path.to_string_lossy(), &texture.to_string_lossy());
+        // Saving writes a new scene instead of overwriting the model.
+        let scene_path = Path::new("scenes")
+            .join(cli.path.file_stem().unwrap_or_default())
+            .with_extension("ron");
+        (scene, scene_path)
+    } else {
+        (Scene::load(&cli.path)?, cli.path.clone())
+    };
+    if let Some(texture) = &cli.texture {
+        for material in scene.materials.values_mut() {
+            material.texture = texture.to_string_lossy().into_owned();
+        }
+    }
+    let models = scene.build(&mut renderer)?;
+    // The cube can be moved around with the keyboard, the camera orbits around the room or the model.
+    let cube = models.get("cube").copied();
+    let room = if is_model {
+        models.values().next().copied()
+    } else {
+        models.get("viking_room").copied()
+    };
     let mut minimized = false;
     let mut tp1 = Instant::now();
+    let mut frame = 0;
+    let mut limiter = cli.max_fps.map(FrameLimiter::new);
+    let mut shader_watcher = ShaderWatcher::new();
 
-    let mut yaw = 0.0;
-    let mut pitch = 0.0;
+    let mut controller = CameraController::new(ControllerMode::Fly);
+    if let Some(room) = room {
+        controller.target = renderer.model(room).world_bounding_sphere().center;
+    }
+    if is_model {
+        // Back off far enough to see the whole model.
+        let sphere = renderer.model(room.unwrap_or(0)).world_bounding_sphere();
+        renderer.camera.position = sphere.center + Vector3::new(0.0, 0.0, sphere.radius * 2.0);
+        controller.set_mode(ControllerMode::Orbit, &mut renderer.camera);
+    }
 
-    let mut keys = [false; 256];
+    let bindings_path = Path::new(BINDINGS_PATH);
+    let default_map = if bindings_path.exists() {
+        InputMap::load(bindings_path)?
+    } else {
+        InputMap::default()
+    };
+    let user_bindings_path = Path::new(USER_BINDINGS_PATH);
+    let mut input_map = default_map.clone();
+    if user_bindings_path.exists() {
+        input_map.overlay(InputMap::load(user_bindings_path)?);
+    }
+    let mut input = InputState::new(input_map);
+    // Dropped and created again when the device is lost.
+    let mut renderer = Some(renderer);
+    let mut device_lost = false;
+    let mut last_error: Option<String> = None;
 
     // Main Loop
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
 
         match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::KeyboardInput {
-                    input:
-                        KeyboardInput {
-                            state,
-                            virtual_keycode: Some(keycode),
-                            ..
-                        },
-                    ..
-                } => match (state, keycode) {
-                    (ElementState::Pressed, VirtualKeyCode::Escape) => {
-                        *control_flow = ControlFlow::Exit
-                    }
-                    (ElementState::Released, VirtualKeyCode::R) => {
-                        renderer.camera.x = 0.0;
-                        renderer.camera.y = 0.0;
-                        renderer.camera.z = 0.0;
-                        yaw = 0.0;
-                        pitch = 0.0;
-                    }
-                    (ElementState::Pressed, _) => keys[keycode as usize] = true,
-                    (ElementState::Released, _) => keys[keycode as usize] = false,
-                },
-                WindowEvent::Resized(size) => {
-                    if size.width == 0 || size.height == 0 {
-                        minimized = true;
-                    } else {
-                        minimized = false;
-                        renderer.window_resized(size.width, size.height);
+            Event::WindowEvent { event, .. } => {
+                input.handle_window_event(&event);
+                match event {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::Resized(size) => {
+                        if size.width == 0 || size.height == 0 {
+                            minimized = true;
+                        } else {
+                            minimized = false;
+                            if let Some(renderer) = &mut renderer {
+                                renderer.window_resized(size.width, size.height);
+                            }
+                        }
                     }
+                    _ => (),
                 }
-                _ => (),
-            },
-            Event::DeviceEvent {
-                device_id: _,
-                event,
-            } => match event {
-                DeviceEvent::MouseMotion { delta: (dx, dy) } => {
-                    yaw -= dx as f32 * 0.1;
-                    pitch -= dy as f32 * 0.1;
-                }
-                _ => (),
-            },
+            }
+            Event::DeviceEvent { event, .. } => input.handle_device_event(&event),
             Event::MainEventsCleared => {
+                if device_lost {
+                    device_lost = false;
+                    if let Err(err) = recreate_renderer(&mut renderer, &window, &cli) {
+                        error!("Error recreating renderer : {}", err);
+                        *control_flow = ControlFlow::ExitWithCode(1);
+                        return;
+                    }
+                    info!("Renderer recreated after the device was lost.");
+                }
+                let renderer = renderer.as_mut().expect("Missing renderer !");
                 if !minimized {
+                    if let Some(limiter) = &mut limiter {
+                        limiter.wait();
+                    }
                     let tp2 = Instant::now();
                     let time = tp2.duration_since(tp1).as_secs_f32();
                     tp1 = tp2;
 
-                    let target = Vector4::new(0.0, 0.0, -1.0, 1.0);
-                    let camera_rotation =
-                        Matrix4::from_angle_y(Deg(yaw)) * Matrix4::from_angle_x(Deg(pitch));
-                    let look_dir = (camera_rotation * target).truncate();
-
-                    let forward = look_dir * 6.0 * time;
-                    let right =
-                        look_dir.cross(Vector3::new(0.0, 1.0, 0.0)).normalize() * 6.0 * time;
-
-                    if keys[VirtualKeyCode::W as usize] {
-                        // move forward
-                        renderer.camera += forward;
+                    if input.pressed("quit") {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    if input.pressed("reset_camera") {
+                        renderer.camera.position = Point3::new(0.0, 0.0, 0.0);
+                        renderer.camera.yaw = Deg(0.0);
+                        renderer.camera.pitch = Deg(0.0);
+                        controller.set_mode(controller.mode(), &mut renderer.camera);
+                    }
+                    if input.pressed("switch_camera_mode") {
+                        if let Some(room) = room {
+                            controller.target =
+                                renderer.model(room).world_bounding_sphere().center;
+                        }
+                        controller.cycle_mode(&mut renderer.camera);
+                    }
+                    if input.pressed("toggle_projection") {
+                        renderer.camera.projection = match renderer.camera.projection {
+                            Projection::Perspective { .. } => {
+                                Projection::Orthographic { height: 10.0 }
+                            }
+                            Projection::Orthographic { .. } => {
+                                Projection::Perspective { fov_y: Deg(90.0) }
+                            }
+                        };
+                    }
+                    if input.pressed("cycle_present_mode") {
+                        let present_mode = renderer.present_mode().next();
+                        if let Err(err) = renderer.set_present_mode(present_mode) {
+                            error!("Error changing present mode : {}", err);
+                        }
                     }
-                    if keys[VirtualKeyCode::S as usize] {
-                        // move backwards
-                        renderer.camera -= forward;
+                    if input.pressed("cycle_render_mode") {
+                        // Skips the wireframe modes when the device can't draw them.
+                        let mut render_mode = renderer.render_mode().next();
+                        while !renderer.supports_render_mode(render_mode) {
+                            render_mode = render_mode.next();
+                        }
+                        if let Err(err) = renderer.set_render_mode(render_mode) {
+                            error!("Error changing render mode : {}", err);
+                        }
                     }
-                    if keys[VirtualKeyCode::A as usize] {
-                        // strafe left
-                        renderer.camera -= right;
+                    if input.pressed("cycle_msaa") {
+                        // Back to no multisampling past the highest count the device supports.
+                        let samples = renderer.msaa_samples();
+                        let mut result = renderer.set_msaa_samples(samples * 2);
+                        if result.is_ok() && renderer.msaa_samples() == samples {
+                            result = renderer.set_msaa_samples(1);
+                        }
+                        if let Err(err) = result {
+                            error!("Error changing multisampling : {}", err);
+                        }
                     }
-                    if keys[VirtualKeyCode::D as usize] {
-                        // strafe right
-                        renderer.camera += right;
+                    if input.pressed("cycle_tonemap") {
+                        renderer.tonemap = renderer.tonemap.next();
                     }
-                    if keys[VirtualKeyCode::Space as usize] {
-                        // move up
-                        renderer.camera.y += 6.0 * time;
+                    if input.pressed("toggle_post_processing") {
+                        // Turns everything back on once all the passes are off.
+                        let enabled = renderer.post_passes().iter().all(|pass| !pass.enabled);
+                        let post_passes = renderer
+                            .post_passes()
+                            .iter()
+                            .map(|pass| PostPass {
+                                enabled,
+                                ..pass.clone()
+                            })
+                            .collect();
+                        if let Err(err) = renderer.set_post_passes(post_passes) {
+                            error!("Error changing post-processing : {}", err);
+                        }
                     }
-                    if keys[VirtualKeyCode::C as usize] {
-                        // move down
-                        renderer.camera.y -= 6.0 * time;
+                    if input.pressed("toggle_reversed_z") {
+                        renderer.camera.reversed_z = !renderer.camera.reversed_z;
                     }
-                    if keys[VirtualKeyCode::Q as usize] {
-                        // look left
-                        yaw += 20.0 * time;
+                    if input.pressed("rebind") {
+                        input.start_capture();
                     }
-                    if keys[VirtualKeyCode::E as usize] {
-                        // look right
-                        yaw -= 20.0 * time;
+                    if input.pressed("save_scene") {
+                        if let Err(err) = Scene::capture(renderer).save(&scene_path) {
+                            error!("Error saving scene : {}", err);
+                        }
                     }
 
-                    renderer.target = renderer.camera - look_dir;
+                    let controller_input = ControllerInput {
+                        forward: input.axis("move_forward"),
+                        right: input.axis("move_right"),
+                        up: input.axis("move_up"),
+                        turn: input.axis("turn"),
+                        look_x: input.axis("look_x"),
+                        look_y: input.axis("look_y"),
+                        zoom: input.axis("zoom"),
+                        jump: input.held("jump"),
+                    };
+                    controller.update(&mut renderer.camera, &controller_input, time);
 
-                    if keys[VirtualKeyCode::Up as usize] {
-                        renderer.model(m0).position.z -= 8.0 * time;
+                    if let Some(cube) = cube {
+                        renderer.model(cube).position.x += input.axis("model_x") * 8.0 * time;
+                        renderer.model(cube).position.z += input.axis("model_z") * 8.0 * time;
                     }
 
-                    if keys[VirtualKeyCode::Down as usize] {
-                        renderer.model(m0).position.z += 8.0 * time;
-                    }
+                    let capture = input.capture().cloned();
+                    input.end_frame();
 
-                    if keys[VirtualKeyCode::Left as usize] {
-                        renderer.model(m0).position.x -= 8.0 * time;
+                    let changed_shaders = shader_watcher.changed();
+                    if !changed_shaders.is_empty() {
+                        let changed_shaders: Vec<(&str, &str)> = changed_shaders
+                            .iter()
+                            .map(|(source, output)| (source.as_str(), output.as_str()))
+                            .collect();
+                        if let Err(err) = renderer.reload_shaders(&changed_shaders) {
+                            error!("Error reloading shaders : {}", err);
+                        }
                     }
 
-                    if keys[VirtualKeyCode::Right as usize] {
-                        renderer.model(m0).position.x += 8.0 * time;
+                    if headless_frames == Some(frame + 1) {
+                        if let Err(err) = renderer.request_screenshot() {
+                            error!("Error taking screenshot : {}", err);
+                            *control_flow = ControlFlow::ExitWithCode(1);
+                            return;
+                        }
                     }
-
-                    renderer.draw_frame();
-                    window.set_title(
-                        format!(
-                            "vk-rs - XYZ: {:>11.5}, {:>11.5}, {:>11.5} - FPS: {:>5.0}",
-                            renderer.camera.x,
-                            renderer.camera.y,
-                            renderer.camera.z,
+                    match renderer.draw_frame() {
+                        Ok(()) => last_error = None,
+                        Err(RendererError::SurfaceLost) => {
+                            if let Err(err) = renderer
+                                .recreate_surface(window.raw_display_handle(), window.raw_window_handle())
+                            {
+                                error!("Error recreating surface : {}", err);
+                                *control_flow = ControlFlow::ExitWithCode(1);
+                            }
+                            return;
+                        }
+                        Err(RendererError::DeviceLost) => {
+                            device_lost = true;
+                            return;
+                        }
+                        Err(err) => {
+                            // Keeps trying, the error may go away with the next resize or shader reload.
+                            let message = err.to_string();
+                            if last_error.as_ref() != Some(&message) {
+                                error!("Error drawing frame : {}", message);
+                                last_error = Some(message);
+                            }
+                            if headless_frames.is_some() {
+                                *control_flow = ControlFlow::ExitWithCode(1);
+                            }
+                            return;
+                        }
+                    }
+                    frame += 1;
+                    if headless_frames == Some(frame) {
+                        let output = cli.output.as_ref().expect("Missing screenshot output !");
+                        *control_flow = match renderer.save_screenshot(output) {
+                            Ok(()) => ControlFlow::Exit,
+                            Err(err) => {
+                                error!("Error saving screenshot : {}", err);
+                                ControlFlow::ExitWithCode(1)
+                            }
+                        };
+                    }
+                    let lod = room.map_or(0, |room| renderer.model(room).lod());
+                    let stats = renderer.frame_stats();
+                    let status = match &capture {
+                        Some(Capture::SelectBinding) => {
+                            "Press the binding of the action or axis direction to change".to_owned()
+                        }
+                        Some(Capture::NewBinding(target)) => {
+                            format!("Press the new binding for {}", target)
+                        }
+                        None => format!(
+                            "{:?} - XYZ: {:>11.5}, {:>11.5}, {:>11.5} - FPS: {:>5.0} ({:?}) - GPU: {:>6.2} ms - {:?} - {:?} - MSAA: {}x - Draws: {} - Triangles: {} - Culled: {} - LOD: {}",
+                            controller.mode(),
+                            renderer.camera.position.x,
+                            renderer.camera.position.y,
+                            renderer.camera.position.z,
                             1.0 / time,
-                        )
-                        .as_str(),
-                    );
+                            renderer.active_present_mode(),
+                            renderer
+                                .frame_timings()
+                                .and_then(|timings| timings.gpu)
+                                .map_or(0.0, |gpu| gpu.as_secs_f32() * 1000.0),
+                            renderer.render_mode(),
+                            renderer.tonemap,
+                            renderer.msaa_samples(),
+                            stats.draw_calls,
+                            stats.triangles,
+                            stats.culled_objects,
+                            lod,
+                        ),
+                    };
+                    window.set_title(format!("vk-rs - {}", status).as_str());
+
+                    if matches!(capture, Some(Capture::NewBinding(_))) && input.capture().is_none()
+                    {
+                        let changes = input.map.changes_from(&default_map);
+                        if let Err(err) = changes.save(user_bindings_path) {
+                            error!("Error saving bindings : {}", err);
+                        }
+                    }
                 }
             }
             Event::LoopDestroyed => {
-                renderer.wait_idle();
+                if let Some(renderer) = &renderer {
+                    renderer.wait_idle();
+                    if let Some(path) = &cli.profile {
+                        save_profile(renderer, path);
+                    }
+                }
             }
             _ => (),
         }
     });
 }
+
+// Logs the frame and pass times over the last frames and saves them as CSV.
+fn save_profile(renderer: &Renderer, path: &Path) {
+    let report = renderer.profile_report();
+    for row in report.rows.iter() {
+        info!(
+            "{}: CPU {:.3} ms (p95 {:.3}), GPU {}",
+            row.name,
+            row.cpu.average,
+            row.cpu.p95,
+            row.gpu.map_or("-".to_owned(), |gpu| format!(
+                "{:.3} ms (p95 {:.3})",
+                gpu.average, gpu.p95
+            ))
+        );
+    }
+    match report.save_csv(path) {
+        Ok(()) => info!(
+            "Profile of {} frames saved to {}.",
+            report.frames,
+            path.display()
+        ),
+        Err(err) => error!("Error saving profile : {}", err),
+    }
+}
+
+// Starts over with a new renderer after the device was lost, with the same scene and settings.
+fn recreate_renderer(
+    renderer: &mut Option<Renderer>,
+    window: &Window,
+    cli: &Cli,
+) -> Result<(), Box<dyn Error>> {
+    let old = renderer.take().expect("Missing renderer !");
+    let scene = Scene::capture(&old);
+    // The scene doesn't keep the projection or the settings changed from the keyboard.
+    let camera = old.camera;
+    let (present_mode, render_mode, msaa_samples) =
+        (old.present_mode(), old.render_mode(), old.msaa_samples());
+    // The window can only have one swapchain, the old one has to go first.
+    drop(old);
+
+    let mut new = Renderer::new(
+        window.raw_display_handle(),
+        window.raw_window_handle(),
+        window.inner_size().width,
+        window.inner_size().height,
+        &cli.renderer_config(),
+    )?;
+    // The models are added in the same order, their indices don't change.
+    scene.build(&mut new)?;
+    new.camera = camera;
+    new.set_present_mode(present_mode)?;
+    new.set_msaa_samples(msaa_samples)?;
+    new.set_render_mode(render_mode)?;
+    *renderer = Some(new);
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, MetricSpace, Point3, Transform, Vector4};
+
+use super::types::Vertex;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+        for vertex in vertices.iter() {
+            min.x = min.x.min(vertex.pos[0]);
+            min.y = min.y.min(vertex.pos[1]);
+            min.z = min.z.min(vertex.pos[2]);
+            max.x = max.x.max(vertex.pos[0]);
+            max.y = max.y.max(vertex.pos[1]);
+            max.z = max.z.max(vertex.pos[2]);
+        }
+        if vertices.is_empty() {
+            min = Point3::new(0.0, 0.0, 0.0);
+            max = Point3::new(0.0, 0.0, 0.0);
+        }
+        Aabb { min, max }
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        Point3::new(
+            (self.min.x + self.max.x) * 0.5,
+            (self.min.y + self.max.y) * 0.5,
+            (self.min.z + self.max.z) * 0.5,
+        )
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        [
+            Point3::new(self.min.x, self.min.y, self.min.z),
+            Point3::new(self.max.x, self.min.y, self.min.z),
+            Point3::new(self.min.x, self.max.y, self.min.z),
+            Point3::new(self.max.x, self.max.y, self.min.z),
+            Point3::new(self.min.x, self.min.y, self.max.z),
+            Point3::new(self.max.x, self.min.y, self.max.z),
+            Point3::new(self.min.x, self.max.y, self.max.z),
+            Point3::new(self.max.x, self.max.y, self.max.z),
+        ]
+    }
+
+    // The box enclosing this box once transformed, used to bring a model space box into world space.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+        for corner in self.corners().iter() {
+            let p = matrix.transform_point(*corner);
+            min.x = min.x.min(p.x);
+            min.y = min.y.min(p.y);
+            min.z = min.z.min(p.z);
+            max.x = max.x.max(p.x);
+            max.y = max.y.max(p.y);
+            max.z = max.z.max(p.z);
+        }
+        Aabb { min, max }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // Centered on the bounding box, which is not the smallest sphere but is good enough for culling.
+    pub fn from_vertices(vertices: &[Vertex], aabb: &Aabb) -> Self {
+        let center = aabb.center();
+        let radius = vertices
+            .iter()
+            .map(|v| center.distance(Point3::new(v.pos[0], v.pos[1], v.pos[2])))
+            .fold(0.0, f32::max);
+        BoundingSphere { center, radius }
+    }
+}
+
+// Planes are stored as (a, b, c, d) with the normal pointing inside, so a point p is inside when a*x + b*y + c*z + d >= 0.
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // Extracts the planes from a view projection matrix (Gribb & Hartmann), using Vulkan's 0..1 depth range.
+    pub fn from_matrix(m: &Matrix4<f32>) -> Self {
+        let r0 = m.row(0);
+        let r1 = m.row(1);
+        let r2 = m.row(2);
+        let r3 = m.row(3);
+        let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2];
+        for plane in planes.iter_mut() {
+            let length = plane.truncate().magnitude();
+            if length > 0.0 {
+                *plane /= length;
+            }
+        }
+        Frustum { planes }
+    }
+
+    fn distance(plane: &Vector4<f32>, p: Point3<f32>) -> f32 {
+        plane.x * p.x + plane.y * p.y + plane.z * p.z + plane.w
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Self::distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Only the corner furthest along the plane normal needs to be tested.
+            let pick = |n: f32, min: f32, max: f32| if n >= 0.0 { max } else { min };
+            let p = Point3::new(
+                pick(plane.x, aabb.min.x, aabb.max.x),
+                pick(plane.y, aabb.min.y, aabb.max.y),
+                pick(plane.z, aabb.min.z, aabb.max.z),
+            );
+            Self::distance(plane, p) >= 0.0
+        })
+    }
+}
@@ -0,0 +1,130 @@
+use cgmath::{Angle, Deg, InnerSpace, Matrix4, Point3, Vector3, Vector4};
+
+use super::bounds::Frustum;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective { fov_y: Deg<f32> },
+    // Height of the view volume in world units, the width follows the aspect ratio.
+    Orthographic { height: f32 },
+}
+
+#[derive(Clone, Copy)]
+pub struct Camera {
+    pub position: Point3<f32>,
+    // Rotation around the Y axis, a yaw of 0 looks down -Z.
+    pub yaw: Deg<f32>,
+    pub pitch: Deg<f32>,
+    pub projection: Projection,
+    pub near: f32,
+    pub far: f32,
+    pub aspect_ratio: f32,
+    // Maps the near plane to depth 1 and the far plane to depth 0, which spreads float depth precision more evenly.
+    pub reversed_z: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Point3::new(0.0, 0.0, 0.0),
+            yaw: Deg(0.0),
+            pitch: Deg(0.0),
+            projection: Projection::Perspective { fov_y: Deg(90.0) },
+            near: 0.1,
+            far: 1000.0,
+            aspect_ratio: 4.0 / 3.0,
+            reversed_z: false,
+        }
+    }
+}
+
+impl Camera {
+    pub fn forward(&self) -> Vector3<f32> {
+        let rotation = Matrix4::from_angle_y(self.yaw) * Matrix4::from_angle_x(self.pitch);
+        (rotation * Vector4::new(0.0, 0.0, -1.0, 0.0)).truncate()
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.forward().cross(Vector3::unit_y()).normalize()
+    }
+
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let direction = target - self.position;
+        if direction.magnitude2() > 0.0 {
+            let direction = direction.normalize();
+            self.yaw = Deg::atan2(-direction.x, -direction.z);
+            self.pitch = Deg::asin(direction.y);
+        }
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
+    }
+
+    // Right handed view space to Vulkan clip space: Y points down and depth goes from 0 to 1.
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        let (n, f) = (self.near, self.far);
+        match self.projection {
+            Projection::Perspective { fov_y } => {
+                let focal_length = 1.0 / (fov_y / 2.0).tan();
+                let (z_scale, z_offset) = if self.reversed_z {
+                    (n / (f - n), n * f / (f - n))
+                } else {
+                    (f / (n - f), n * f / (n - f))
+                };
+                Matrix4::new(
+                    focal_length / self.aspect_ratio,
+                    0.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    -focal_length,
+                    0.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    z_scale,
+                    -1.0,
+                    0.0,
+                    0.0,
+                    z_offset,
+                    0.0,
+                )
+            }
+            Projection::Orthographic { height } => {
+                let width = height * self.aspect_ratio;
+                let (z_scale, z_offset) = if self.reversed_z {
+                    (1.0 / (f - n), f / (f - n))
+                } else {
+                    (-1.0 / (f - n), -n / (f - n))
+                };
+                Matrix4::new(
+                    2.0 / width,
+                    0.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    -2.0 / height,
+                    0.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    z_scale,
+                    0.0,
+                    0.0,
+                    0.0,
+                    z_offset,
+                    1.0,
+                )
+            }
+        }
+    }
+
+    pub fn view_projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.view_projection_matrix())
+    }
+}
@@ -0,0 +1,354 @@
+use std::{env, path::PathBuf};
+
+use ash::vk;
+use serde::{Deserialize, Serialize};
+
+use super::pipeline::{BlendMode, CullMode, PipelineDesc, PolygonMode};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PresentMode {
+    // Waits for vertical blank, always supported.
+    Fifo,
+    // Like Fifo, but a late frame is shown right away instead of waiting for the next blank.
+    FifoRelaxed,
+    // Waits for vertical blank but replaces the queued frame, so rendering never blocks.
+    Mailbox,
+    // No vsync, may tear.
+    Immediate,
+}
+
+impl PresentMode {
+    pub(super) fn vk(self) -> vk::PresentModeKHR {
+        match self {
+            PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+            PresentMode::FifoRelaxed => vk::PresentModeKHR::FIFO_RELAXED,
+            PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+            PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+        }
+    }
+    // The closest mode to try when this one isn't supported, Fifo being always available.
+    pub(super) fn fallback(self) -> Option<PresentMode> {
+        match self {
+            PresentMode::Immediate => Some(PresentMode::Mailbox),
+            PresentMode::Mailbox | PresentMode::FifoRelaxed => Some(PresentMode::Fifo),
+            PresentMode::Fifo => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            PresentMode::Fifo => PresentMode::FifoRelaxed,
+            PresentMode::FifoRelaxed => PresentMode::Mailbox,
+            PresentMode::Mailbox => PresentMode::Immediate,
+            PresentMode::Immediate => PresentMode::Fifo,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SurfaceFormat {
+    // 8 bits per channel in the sRGB color space, always supported.
+    Srgb,
+    // 10 bits per channel in the sRGB color space.
+    Srgb10,
+    // Linear half floats with Rec.709 primaries, 1.0 being 80 nits and values above it brighter than SDR white.
+    ScRgb,
+    // 10 bits per channel with Rec.2020 primaries and the PQ curve.
+    Hdr10,
+}
+
+const fn surface_format(
+    format: vk::Format,
+    color_space: vk::ColorSpaceKHR,
+) -> vk::SurfaceFormatKHR {
+    vk::SurfaceFormatKHR {
+        format,
+        color_space,
+    }
+}
+
+const SRGB_FORMATS: [vk::SurfaceFormatKHR; 4] = [
+    surface_format(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    surface_format(vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    surface_format(
+        vk::Format::B8G8R8A8_UNORM,
+        vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    ),
+    surface_format(
+        vk::Format::R8G8B8A8_UNORM,
+        vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    ),
+];
+const SRGB10_FORMATS: [vk::SurfaceFormatKHR; 2] = [
+    surface_format(
+        vk::Format::A2B10G10R10_UNORM_PACK32,
+        vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    ),
+    surface_format(
+        vk::Format::A2R10G10B10_UNORM_PACK32,
+        vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    ),
+];
+const SCRGB_FORMATS: [vk::SurfaceFormatKHR; 1] = [surface_format(
+    vk::Format::R16G16B16A16_SFLOAT,
+    vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+)];
+const HDR10_FORMATS: [vk::SurfaceFormatKHR; 2] = [
+    surface_format(
+        vk::Format::A2B10G10R10_UNORM_PACK32,
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    ),
+    surface_format(
+        vk::Format::A2R10G10B10_UNORM_PACK32,
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    ),
+];
+
+impl SurfaceFormat {
+    // Surface formats matching this preference, best first.
+    pub(super) fn candidates(self) -> &'static [vk::SurfaceFormatKHR] {
+        match self {
+            SurfaceFormat::Srgb => &SRGB_FORMATS,
+            SurfaceFormat::Srgb10 => &SRGB10_FORMATS,
+            SurfaceFormat::ScRgb => &SCRGB_FORMATS,
+            SurfaceFormat::Hdr10 => &HDR10_FORMATS,
+        }
+    }
+}
+
+// What the final pass does to its linear Rec.709 output so that it matches the swapchain format.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputTransform {
+    // The format does the sRGB encoding.
+    None,
+    // sRGB curve, for UNORM formats in the sRGB color space.
+    Srgb,
+    // Scaled so that SDR white is as bright as the paper white.
+    ScRgb,
+    // Converted to Rec.2020 and encoded with the PQ curve, the paper white setting the brightness.
+    Pq,
+}
+
+impl OutputTransform {
+    pub(super) fn new(surface_format: vk::SurfaceFormatKHR) -> Self {
+        match surface_format.color_space {
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => OutputTransform::ScRgb,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT => OutputTransform::Pq,
+            _ => match surface_format.format {
+                vk::Format::B8G8R8A8_SRGB
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::A8B8G8R8_SRGB_PACK32 => OutputTransform::None,
+                _ => OutputTransform::Srgb,
+            },
+        }
+    }
+
+    // Matches the OUTPUT_* defines of tonemap.frag.
+    pub(super) fn id(self) -> u32 {
+        match self {
+            OutputTransform::None => 0,
+            OutputTransform::Srgb => 1,
+            OutputTransform::ScRgb => 2,
+            OutputTransform::Pq => 3,
+        }
+    }
+}
+
+// Curve mapping the HDR scene to the displayable range, after scaling it by the exposure.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tonemap {
+    // Filmic curve, a bit of contrast and desaturated highlights.
+    Aces,
+    // x / (1 + x), keeps the hues but looks flat.
+    Reinhard,
+    // 1 - e^-x, like film exposed for a given time.
+    Exposure,
+}
+
+impl Tonemap {
+    // Matches the TONEMAP_* defines of tonemap.frag.
+    pub(super) fn id(self) -> u32 {
+        match self {
+            Tonemap::Aces => 0,
+            Tonemap::Reinhard => 1,
+            Tonemap::Exposure => 2,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Tonemap::Aces => Tonemap::Reinhard,
+            Tonemap::Reinhard => Tonemap::Exposure,
+            Tonemap::Exposure => Tonemap::Aces,
+        }
+    }
+}
+
+// What the scene pass draws, the debug modes replacing the lit and textured models.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderMode {
+    Solid,
+    // Triangle edges only, needs the fillModeNonSolid device feature like WireframeOverSolid.
+    Wireframe,
+    WireframeOverSolid,
+    // Checkerboard in texture space, shows stretching and seams.
+    UvChecker,
+    // World space normals mapped to colors.
+    Normals,
+    // Distance to the camera on a log scale between the near and far planes, white being close.
+    Depth,
+    // Every fragment adds up without depth testing, the brightest areas being drawn the most.
+    Overdraw,
+}
+
+impl RenderMode {
+    // Matches the DEBUG_* defines of debug.frag, for the modes drawn with it.
+    pub(super) fn id(self) -> u32 {
+        match self {
+            RenderMode::Solid => 0,
+            RenderMode::Wireframe | RenderMode::WireframeOverSolid => 1,
+            RenderMode::UvChecker => 2,
+            RenderMode::Normals => 3,
+            RenderMode::Depth => 4,
+            RenderMode::Overdraw => 5,
+        }
+    }
+
+    pub(super) fn wireframe(self) -> bool {
+        matches!(self, RenderMode::Wireframe | RenderMode::WireframeOverSolid)
+    }
+
+    // Drawn instead of the materials' pipelines, or over them for WireframeOverSolid.
+    pub(super) fn pipeline_desc(self) -> Option<PipelineDesc> {
+        let debug = PipelineDesc {
+            frag_shader: "shaders/debug_frag.spv".to_owned(),
+            ..Default::default()
+        };
+        match self {
+            RenderMode::Solid => None,
+            RenderMode::Wireframe => Some(PipelineDesc {
+                polygon_mode: PolygonMode::Line,
+                cull_mode: CullMode::None,
+                ..debug
+            }),
+            RenderMode::WireframeOverSolid => Some(PipelineDesc {
+                polygon_mode: PolygonMode::Line,
+                depth_write: false,
+                depth_bias: true,
+                ..debug
+            }),
+            RenderMode::UvChecker | RenderMode::Normals | RenderMode::Depth => Some(debug),
+            RenderMode::Overdraw => Some(PipelineDesc {
+                cull_mode: CullMode::None,
+                blend: BlendMode::Additive,
+                depth_test: false,
+                depth_write: false,
+                ..debug
+            }),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Solid => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::WireframeOverSolid,
+            RenderMode::WireframeOverSolid => RenderMode::UvChecker,
+            RenderMode::UvChecker => RenderMode::Normals,
+            RenderMode::Normals => RenderMode::Depth,
+            RenderMode::Depth => RenderMode::Overdraw,
+            RenderMode::Overdraw => RenderMode::Solid,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ValidationFeature {
+    // Instruments the shaders to catch out of bounds and uninitialized descriptor accesses. Much slower.
+    GpuAssisted,
+    // Hazards between commands that are missing a barrier.
+    Synchronization,
+    // Valid but inefficient or non portable use of the API.
+    BestPractices,
+}
+
+impl ValidationFeature {
+    pub(super) fn vk(self) -> &'static [vk::ValidationFeatureEnableEXT] {
+        match self {
+            ValidationFeature::GpuAssisted => &[
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT,
+            ],
+            ValidationFeature::Synchronization => {
+                &[vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION]
+            }
+            ValidationFeature::BestPractices => &[vk::ValidationFeatureEnableEXT::BEST_PRACTICES],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RendererConfig {
+    // Index of the device in enumeration order, see Renderer::list_devices. The first suitable one is
+    // used when not set.
+    pub device: Option<usize>,
+    // Validation layers and debug messenger, in any build. Ignored when the layers aren't installed.
+    pub validation: bool,
+    // Checks on top of the default validation.
+    pub validation_features: Vec<ValidationFeature>,
+    // Validation messages not logged, by id name (VUID-...) or id number in decimal or 0x hex.
+    pub suppressed_messages: Vec<String>,
+    // Panics once the frame is submitted when validation reported errors, so that tests fail.
+    pub panic_on_validation_error: bool,
+    // Falls back to the closest supported mode, see PresentMode::fallback.
+    pub present_mode: PresentMode,
+    // Tried in order, falling back to Srgb and then to whatever the surface supports first.
+    pub surface_formats: Vec<SurfaceFormat>,
+    // Brightness of SDR white in nits on HDR surfaces.
+    pub paper_white: f32,
+    // Brightest the display goes in nits, which Vulkan can't tell. The tonemapper maps to it on HDR
+    // surfaces instead of to paper white.
+    pub peak_brightness: f32,
+    // Upper bound on the samples per pixel, lowered to what the device supports. 1 turns
+    // multisampling off.
+    pub msaa_samples: u32,
+    // Shades more than once per pixel when multisampling, if the device supports it.
+    pub sample_shading: bool,
+    // Where compiled pipelines are kept between runs, not kept at all when not set.
+    pub pipeline_cache: Option<PathBuf>,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        RendererConfig {
+            device: None,
+            validation: cfg!(debug_assertions),
+            validation_features: vec![],
+            suppressed_messages: vec![],
+            panic_on_validation_error: false,
+            present_mode: PresentMode::Mailbox,
+            surface_formats: vec![SurfaceFormat::Srgb],
+            paper_white: 200.0,
+            peak_brightness: 1000.0,
+            msaa_samples: 4,
+            sample_shading: false,
+            pipeline_cache: Some(default_pipeline_cache_path()),
+        }
+    }
+}
+
+// In the user cache directory, or in target/ when there is none.
+pub fn default_pipeline_cache_path() -> PathBuf {
+    let cache_dir = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Caches"))
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+    match cache_dir {
+        Some(cache_dir) => cache_dir.join("vk-rs").join("pipeline_cache.bin"),
+        None => PathBuf::from("target/pipeline_cache.bin"),
+    }
+}
@@ -0,0 +1,73 @@
+use std::ffi::CString;
+
+use ash::{extensions::ext::DebugUtils, vk};
+use log::trace;
+
+// Object names and command buffer labels shown by the validation messages and by tools like
+// RenderDoc. Does nothing without VK_EXT_debug_utils.
+pub struct DebugNames {
+    debug_utils: Option<DebugUtils>,
+    device: vk::Device,
+}
+
+impl DebugNames {
+    pub fn new(debug_utils: Option<DebugUtils>, device: vk::Device) -> Self {
+        DebugNames {
+            debug_utils,
+            device,
+        }
+    }
+
+    pub fn set<H: vk::Handle>(&self, handle: H, name: &str) {
+        if let Some(debug_utils) = &self.debug_utils {
+            let name = CString::new(name).unwrap_or_default();
+            let name_info = vk::DebugUtilsObjectNameInfoEXT {
+                object_type: H::TYPE,
+                object_handle: handle.as_raw(),
+                p_object_name: name.as_ptr(),
+                ..Default::default()
+            };
+            // Only a debugging help, nothing to do about it failing.
+            if let Err(err) =
+                unsafe { debug_utils.set_debug_utils_object_name(self.device, &name_info) }
+            {
+                trace!("Error naming {:?} : {}", H::TYPE, err);
+            }
+        }
+    }
+
+    // Names each handle after its index, `name 0`, `name 1`, ...
+    pub fn set_all<H: vk::Handle + Copy>(&self, handles: &[H], name: &str) {
+        for (i, &handle) in handles.iter().enumerate() {
+            self.set(handle, &format!("{} {}", name, i));
+        }
+    }
+
+    pub fn begin_label(&self, command_buffer: vk::CommandBuffer, name: &str) {
+        if let Some(debug_utils) = &self.debug_utils {
+            let name = CString::new(name).unwrap_or_default();
+            let label = vk::DebugUtilsLabelEXT {
+                p_label_name: name.as_ptr(),
+                ..Default::default()
+            };
+            unsafe { debug_utils.cmd_begin_debug_utils_label(command_buffer, &label) };
+        }
+    }
+
+    pub fn end_label(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(debug_utils) = &self.debug_utils {
+            unsafe { debug_utils.cmd_end_debug_utils_label(command_buffer) };
+        }
+    }
+
+    pub fn insert_label(&self, command_buffer: vk::CommandBuffer, name: &str) {
+        if let Some(debug_utils) = &self.debug_utils {
+            let name = CString::new(name).unwrap_or_default();
+            let label = vk::DebugUtilsLabelEXT {
+                p_label_name: name.as_ptr(),
+                ..Default::default()
+            };
+            unsafe { debug_utils.cmd_insert_debug_utils_label(command_buffer, &label) };
+        }
+    }
+}
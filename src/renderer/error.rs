@@ -0,0 +1,116 @@
+use std::{fmt::Write, io, str::Utf8Error};
+
+use ash::vk;
+use thiserror::Error;
+
+use super::DeviceInfo;
+
+// Everything the renderer can fail with. The renderer can keep going after the surface is recreated,
+// see Renderer::recreate_surface, but has to be created again when the device is lost.
+#[derive(Debug, Error)]
+pub enum RendererError {
+    #[error("Error creating Vulkan instance : {0}")]
+    Instance(vk::Result),
+    #[error("Validation layers not available : {} !", .0.join(", "))]
+    MissingValidationLayers(Vec<String>),
+    // Every device that was checked, with the checks it failed.
+    #[error("{}", no_suitable_device(*.selected, .devices))]
+    NoSuitableDevice {
+        selected: Option<usize>,
+        devices: Vec<DeviceInfo>,
+    },
+    #[error("Out of device memory !")]
+    OutOfDeviceMemory,
+    #[error("Error loading model {path} : {source}")]
+    Obj {
+        path: String,
+        source: tobj::LoadError,
+    },
+    #[error("Error loading image {path} : {source}")]
+    Image {
+        path: String,
+        source: image::ImageError,
+    },
+    #[error("Error loading lookup table {path} : {message}")]
+    Lut { path: String, message: String },
+    #[error("{path}: {message}")]
+    Shader { path: String, message: String },
+    #[error("Swapchain error : {0}")]
+    Swapchain(vk::Result),
+    // The window surface went away, with the window being moved to another display for instance.
+    #[error("Surface lost !")]
+    SurfaceLost,
+    // The GPU was reset, removed or its driver crashed, every Vulkan object is gone.
+    #[error("Device lost !")]
+    DeviceLost,
+    #[error("Vulkan error : {0}")]
+    Vulkan(vk::Result),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    // A name reported by the driver isn't UTF-8.
+    #[error(transparent)]
+    Utf8(#[from] Utf8Error),
+    // Everything else, an unsupported setting or a bad pipeline description for instance.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl RendererError {
+    pub fn shader(path: &str, message: impl ToString) -> Self {
+        RendererError::Shader {
+            path: path.to_owned(),
+            message: message.to_string(),
+        }
+    }
+
+    // Creating, acquiring from or presenting to the swapchain failed.
+    pub fn swapchain(result: vk::Result) -> Self {
+        match RendererError::from(result) {
+            RendererError::Vulkan(result) => RendererError::Swapchain(result),
+            err => err,
+        }
+    }
+}
+
+impl From<vk::Result> for RendererError {
+    fn from(result: vk::Result) -> Self {
+        match result {
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => RendererError::OutOfDeviceMemory,
+            vk::Result::ERROR_SURFACE_LOST_KHR => RendererError::SurfaceLost,
+            vk::Result::ERROR_DEVICE_LOST => RendererError::DeviceLost,
+            _ => RendererError::Vulkan(result),
+        }
+    }
+}
+
+// Keeps the `Err("... !")?` shorthand for the errors nobody matches on.
+impl From<&str> for RendererError {
+    fn from(message: &str) -> Self {
+        RendererError::Other(message.to_owned())
+    }
+}
+
+impl From<String> for RendererError {
+    fn from(message: String) -> Self {
+        RendererError::Other(message)
+    }
+}
+
+fn no_suitable_device(selected: Option<usize>, devices: &[DeviceInfo]) -> String {
+    let mut text = match selected {
+        Some(index) if devices.is_empty() => format!("Selected device {} not found !", index),
+        Some(index) => format!("Selected device {} is not suitable !", index),
+        None => "No suitable device found !".to_owned(),
+    };
+    for device in devices.iter() {
+        let failed = device
+            .checks
+            .iter()
+            .filter(|(_, passed)| !passed)
+            .map(|(check, _)| *check)
+            .collect::<Vec<&str>>();
+        let _ = write!(text, "\n    {}: {}", device.name, failed.join(", "));
+    }
+
+    text
+}
@@ -0,0 +1,82 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::types::LightData;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Light {
+    // Lights everything from the same direction, like the sun. `direction` is the way the light
+    // travels, not where it comes from.
+    Directional {
+        #[serde(with = "vec3")]
+        direction: Vector3<f32>,
+        color: [f32; 3],
+        intensity: f32,
+    },
+    // Fades out smoothly and stops lighting at `range`.
+    Point {
+        #[serde(with = "vec3")]
+        position: Point3<f32>,
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+    },
+}
+
+impl Light {
+    pub(super) fn data(&self) -> LightData {
+        match *self {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => {
+                let direction = direction.normalize();
+                LightData {
+                    position: [direction.x, direction.y, direction.z, 0.0],
+                    color: [
+                        color[0] * intensity,
+                        color[1] * intensity,
+                        color[2] * intensity,
+                        0.0,
+                    ],
+                }
+            }
+            Light::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => LightData {
+                position: [position.x, position.y, position.z, 1.0],
+                color: [
+                    color[0] * intensity,
+                    color[1] * intensity,
+                    color[2] * intensity,
+                    range,
+                ],
+            },
+        }
+    }
+}
+
+// Vectors and points are written as plain arrays, like the colors.
+mod vec3 {
+    use super::*;
+
+    pub fn serialize<T, S>(v: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<[f32; 3]>,
+        S: Serializer,
+    {
+        (*v).into().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: From<[f32; 3]>,
+        D: Deserializer<'de>,
+    {
+        <[f32; 3]>::deserialize(deserializer).map(T::from)
+    }
+}
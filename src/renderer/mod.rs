@@ -1,81 +1,120 @@
+mod bounds;
+mod camera;
+mod config;
+mod debug_names;
+mod error;
+mod light;
 mod model;
+mod pipeline;
+mod post;
+mod profiler;
+mod reflect;
+mod simplify;
+mod statistics;
 mod tools;
 mod types;
+mod validation;
+
+use std::ffi::{c_void, CStr, CString};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
-#[cfg(debug_assertions)]
-use std::ffi::c_void;
-use std::ffi::{CStr, CString};
-use std::{error::Error, path::Path};
-
-#[cfg(debug_assertions)]
-use ash::extensions::ext::DebugUtils;
 use ash::{
-    extensions::khr::{Surface, Swapchain},
+    extensions::{
+        ext::DebugUtils,
+        khr::{Surface, Swapchain},
+    },
     vk, Device, Entry, Instance,
 };
-use cgmath::{Deg, Matrix4, Point3, SquareMatrix, Vector3};
+use cgmath::MetricSpace;
+use log::{debug, error, info, trace, warn};
 
-use model::{Model, Texture};
-use types::{Align16, QueueFamilyIndices, SwapchainSupportDetails, UniformBufferObject, Vertex};
+use crate::shader_compiler;
+
+pub use camera::{Camera, Projection};
+pub use config::{
+    default_pipeline_cache_path, OutputTransform, PresentMode, RenderMode, RendererConfig,
+    SurfaceFormat, Tonemap, ValidationFeature,
+};
+use debug_names::DebugNames;
+pub use error::RendererError;
+pub use light::Light;
+pub use model::{AlphaMode, Material};
+use model::{Mesh, Model, Texture};
+pub use pipeline::{PipelineDesc, PolygonMode, Topology, VertexLayout};
+use post::{CubeLut, Lut, PostShader, PostTarget, BLOOM_TARGETS, POST_TARGETS};
+pub use post::{PostEffect, PostPass};
+use profiler::Profiler;
+pub use profiler::{FrameTimings, ProfileReport};
+use reflect::{LayoutReflection, ShaderReflection};
+use statistics::PipelineStatistics;
+use types::{
+    Align16, DebugPushConstants, GlobalUniformBufferObject, LightData, ModelUniformBufferObject,
+    PostPushConstants, QueueFamilyIndices, Screenshot, SwapchainSupportDetails,
+    TonemapPushConstants, Vertex, MAX_LIGHTS,
+};
+pub use types::{DeviceInfo, FrameStats};
+use validation::Validation;
 
-#[cfg(debug_assertions)]
 const VALIDATION_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
 
 const DEVICE_EXTENSIONS: [&str; 1] = ["VK_KHR_swapchain"];
+
+// Shaders the scene pass layout is reflected from, those of the default pipeline and the debug modes.
+const SCENE_SHADERS: [&str; 3] = [
+    "shaders/vert.spv",
+    "shaders/frag.spv",
+    "shaders/debug_frag.spv",
+];
+// What the renderer writes in the global (0) and model (1) descriptor sets.
+const SCENE_BINDINGS: [((u32, u32), vk::DescriptorType); 4] = [
+    ((0, 0), vk::DescriptorType::UNIFORM_BUFFER),
+    ((1, 0), vk::DescriptorType::UNIFORM_BUFFER),
+    ((1, 1), vk::DescriptorType::SAMPLED_IMAGE),
+    ((1, 2), vk::DescriptorType::SAMPLER),
+];
 const MAX_FRAMES_IN_FLIGHT: usize = 2;
-const MAX_MODELS: usize = 2;
-
-#[cfg(debug_assertions)]
-unsafe extern "system" fn vk_debug_utils_callback(
-    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
-    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut c_void,
-) -> vk::Bool32 {
-    let msg_severity = match message_severity {
-        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => "[VERBOSE]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => "[INFO]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => "[WARNING]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => "[ERROR]",
-        _ => "[UNKNOWN_SEVERITY]",
-    };
-
-    let msg_type = match message_type {
-        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL => "[GENERAL]",
-        vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE => "[PERFORMANCE]",
-        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION => "[VALIDATION]",
-        _ => "[UNKNOWN_TYPE]",
-    };
-
-    let msg = CStr::from_ptr((*p_callback_data).p_message);
-
-    println!("{} {} {:?}", msg_severity, msg_type, msg);
-
-    vk::FALSE
-}
+const MAX_MODELS: usize = 64;
+// Format of the offscreen image the scene is drawn into, always supported as a color attachment.
+const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const MAX_LUTS: usize = 8;
 
 pub struct Renderer {
     _entry: Entry,
     instance: Instance,
-    #[cfg(debug_assertions)]
-    debug_utils: Option<(DebugUtils, vk::DebugUtilsMessengerEXT)>,
+    debug_utils: Option<DebugUtils>,
+    debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
+    validation: Box<Validation>,
+    debug_names: DebugNames,
     physical_device: vk::PhysicalDevice,
     surface_loader: Surface,
     surface: vk::SurfaceKHR,
     device: Device,
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
+    present_family: u32,
     swapchain_loader: Swapchain,
     swapchain: vk::SwapchainKHR,
     swapchain_images: Vec<vk::Image>,
-    swapchain_image_format: vk::Format,
+    surface_format: vk::SurfaceFormatKHR,
     swapchain_extent: vk::Extent2D,
     swapchain_image_views: Vec<vk::ImageView>,
     render_pass: vk::RenderPass,
     global_descriptor_set_layout: vk::DescriptorSetLayout,
     model_descriptor_set_layout: vk::DescriptorSetLayout,
+    // Declared by the scene shaders, see reflect_scene_layout.
+    scene_layout: LayoutReflection,
     pipeline_layout: vk::PipelineLayout,
-    graphics_pipeline: vk::Pipeline,
+    // Scene pass pipelines by description, created when first drawn, see pipeline.
+    pipelines: HashMap<PipelineDesc, vk::Pipeline>,
+    pipeline_cache: vk::PipelineCache,
+    pipeline_cache_path: Option<PathBuf>,
+    render_mode: RenderMode,
+    fill_mode_non_solid: bool,
     swapchain_framebuffers: Vec<vk::Framebuffer>,
     command_pool: vk::CommandPool,
     command_buffers: Vec<vk::CommandBuffer>,
@@ -86,6 +125,8 @@ pub struct Renderer {
     width: u32,
     height: u32,
     framebuffer_resized: bool,
+    // Set while recreate_swapchain hasn't succeeded, the swapchain objects being null or partly created.
+    swapchain_invalid: bool,
     models: Vec<Model>,
     global_uniform_buffers: Vec<vk::Buffer>,
     global_uniform_buffers_memory: Vec<vk::DeviceMemory>,
@@ -95,9 +136,61 @@ pub struct Renderer {
     depth_image: vk::Image,
     depth_image_memory: vk::DeviceMemory,
     depth_image_view: vk::ImageView,
+    // Null handles when msaa_samples is TYPE_1, the scene then being drawn into the offscreen image
+    // directly.
+    msaa_image: vk::Image,
+    msaa_image_memory: vk::DeviceMemory,
+    msaa_image_view: vk::ImageView,
+    // Samples per pixel of the scene pass, see RendererConfig::msaa_samples.
+    msaa_samples: vk::SampleCountFlags,
+    sample_shading: bool,
+    // The scene is drawn into the offscreen image, which the tonemap pass then draws into the swapchain.
+    offscreen_image: vk::Image,
+    offscreen_image_memory: vk::DeviceMemory,
+    offscreen_image_view: vk::ImageView,
+    offscreen_framebuffer: vk::Framebuffer,
+    tonemap_render_pass: vk::RenderPass,
+    tonemap_pipeline_layout: vk::PipelineLayout,
+    tonemap_pipeline: vk::Pipeline,
+    // Every image the post-process steps and the tonemap pass sample has its own descriptor set with a
+    // single binding, and the steps draw into the post targets, see record_post_passes.
+    post_sampler: vk::Sampler,
+    post_descriptor_set_layout: vk::DescriptorSetLayout,
+    post_descriptor_pool: vk::DescriptorPool,
+    offscreen_descriptor_set: vk::DescriptorSet,
+    post_render_pass: vk::RenderPass,
+    post_pipeline_layout: vk::PipelineLayout,
+    // Indexed like PostShader::ALL.
+    post_pipelines: Vec<vk::Pipeline>,
+    post_targets: Vec<PostTarget>,
+    post_target_descriptor_sets: Vec<vk::DescriptorSet>,
+    post_passes: Vec<PostPass>,
+    post_luts: Vec<Lut>,
+    // Of the last frame read back, and of the one being recorded, counted by record_command_buffer.
+    frame_stats: FrameStats,
+    recorded_stats: Cell<FrameStats>,
+    statistics: PipelineStatistics,
+    // Borrowed mutably while recording the command buffer, see begin_scope.
+    profiler: RefCell<Profiler>,
+    // Whether the pipeline and depth clear value are currently set up for a reversed-Z camera.
+    reversed_z: bool,
+    // The mode asked for and the one the swapchain uses, which differ when it isn't supported.
+    present_mode: PresentMode,
+    active_present_mode: PresentMode,
+    // Formats asked for in order of preference, see choose_swap_surface_format.
+    surface_formats: Vec<SurfaceFormat>,
+    output_transform: OutputTransform,
+    paper_white: f32,
+    peak_brightness: f32,
+    screenshot_requested: bool,
+    screenshot: Option<Screenshot>,
     pub theta: f32,
-    pub camera: Point3<f32>,
-    pub target: Point3<f32>,
+    pub camera: Camera,
+    pub clear_color: [f32; 4],
+    pub ambient_light: [f32; 3],
+    pub lights: Vec<Light>,
+    pub tonemap: Tonemap,
+    pub exposure: f32,
 }
 
 impl Renderer {
@@ -105,57 +198,127 @@ impl Renderer {
         &mut self.models[i]
     }
 
+    pub fn models(&self) -> &[Model] {
+        &self.models
+    }
+
+    // Draw calls, primitives and binds of the latest frame read back, MAX_FRAMES_IN_FLIGHT frames behind
+    // the one drawn.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    // CPU and GPU times of the passes of the latest frame read back, MAX_FRAMES_IN_FLIGHT frames behind
+    // the one drawn.
+    pub fn frame_timings(&self) -> Option<FrameTimings> {
+        self.profiler.borrow().last_frame().cloned()
+    }
+
+    // Averages and percentiles of the pass times over the last frames.
+    pub fn profile_report(&self) -> ProfileReport {
+        self.profiler.borrow().report()
+    }
+
+    // The handles are nulled once dropped, destroying a null handle doing nothing, so that this can run
+    // again after recreate_swapchain failed halfway.
     fn cleanup_swapchain(&mut self) {
+        unsafe {
+            self.device
+                .destroy_framebuffer(self.offscreen_framebuffer, None)
+        };
+        debug!("Offscreen framebuffer dropped.");
+
+        unsafe {
+            self.device
+                .destroy_image_view(self.offscreen_image_view, None)
+        };
+        unsafe { self.device.destroy_image(self.offscreen_image, None) };
+        unsafe { self.device.free_memory(self.offscreen_image_memory, None) };
+        debug!("Offscreen image dropped and memory freed.");
+
+        for target in self.post_targets.iter() {
+            self.cleanup_post_target(target);
+        }
+
+        if self.msaa_image_view != vk::ImageView::null() {
+            unsafe { self.device.destroy_image_view(self.msaa_image_view, None) };
+            unsafe { self.device.destroy_image(self.msaa_image, None) };
+            unsafe { self.device.free_memory(self.msaa_image_memory, None) };
+            debug!("Multisampled color image dropped and memory freed.");
+        }
+
         unsafe { self.device.destroy_image_view(self.depth_image_view, None) };
-        #[cfg(debug_assertions)]
-        println!("Depth image view dropped.");
+        debug!("Depth image view dropped.");
 
         unsafe { self.device.destroy_image(self.depth_image, None) };
-        #[cfg(debug_assertions)]
-        println!("Depth image dropped.");
+        debug!("Depth image dropped.");
 
         unsafe { self.device.free_memory(self.depth_image_memory, None) };
-        #[cfg(debug_assertions)]
-        println!("Depth image memory freed.");
+        debug!("Depth image memory freed.");
 
         for framebuffer in self.swapchain_framebuffers.iter() {
             unsafe { self.device.destroy_framebuffer(*framebuffer, None) }
         }
-        #[cfg(debug_assertions)]
-        println!("Framebuffers dropped.");
+        debug!("Framebuffers dropped.");
+
+        self.cleanup_pipelines();
 
-        unsafe { self.device.destroy_pipeline(self.graphics_pipeline, None) };
-        #[cfg(debug_assertions)]
-        println!("Graphics pipeline dropped.");
+        unsafe { self.device.destroy_render_pass(self.render_pass, None) };
+        debug!("Render pass dropped.");
+
+        unsafe { self.device.destroy_pipeline(self.tonemap_pipeline, None) };
         unsafe {
             self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None)
+                .destroy_pipeline_layout(self.tonemap_pipeline_layout, None)
         };
-        #[cfg(debug_assertions)]
-        println!("Pipeline layout dropped.");
+        debug!("Tonemap pipeline dropped.");
 
-        unsafe { self.device.destroy_render_pass(self.render_pass, None) };
-        #[cfg(debug_assertions)]
-        println!("Render pass dropped.");
+        unsafe {
+            self.device
+                .destroy_render_pass(self.tonemap_render_pass, None)
+        };
+        debug!("Tonemap render pass dropped.");
 
         for image_view in self.swapchain_image_views.iter() {
             unsafe { self.device.destroy_image_view(*image_view, None) }
         }
-        #[cfg(debug_assertions)]
-        println!("Swapchain image views dropped.");
+        debug!("Swapchain image views dropped.");
 
         unsafe {
             self.swapchain_loader
                 .destroy_swapchain(self.swapchain, None)
         };
-        #[cfg(debug_assertions)]
-        println!("Swapchain dropped.");
+        debug!("Swapchain dropped.");
+
+        self.offscreen_framebuffer = vk::Framebuffer::null();
+        self.offscreen_image_view = vk::ImageView::null();
+        self.offscreen_image = vk::Image::null();
+        self.offscreen_image_memory = vk::DeviceMemory::null();
+        self.post_targets.clear();
+        self.msaa_image_view = vk::ImageView::null();
+        self.msaa_image = vk::Image::null();
+        self.msaa_image_memory = vk::DeviceMemory::null();
+        self.depth_image_view = vk::ImageView::null();
+        self.depth_image = vk::Image::null();
+        self.depth_image_memory = vk::DeviceMemory::null();
+        self.swapchain_framebuffers.clear();
+        self.render_pass = vk::RenderPass::null();
+        self.tonemap_pipeline = vk::Pipeline::null();
+        self.tonemap_pipeline_layout = vk::PipelineLayout::null();
+        self.tonemap_render_pass = vk::RenderPass::null();
+        self.swapchain_image_views.clear();
+        self.swapchain_images.clear();
+        self.swapchain = vk::SwapchainKHR::null();
     }
 
-    fn recreate_swapchain(&mut self) -> Result<(), Box<dyn Error>> {
+    fn recreate_swapchain(&mut self) -> Result<(), RendererError> {
         unsafe { self.device.device_wait_idle() }?;
 
         self.cleanup_swapchain();
+        // Each object is stored as soon as it is created, so that the next cleanup_swapchain drops them
+        // when a later one fails. The swapchain objects can't be used until this succeeds, see
+        // draw_frame.
+        self.swapchain_invalid = true;
 
         let swapchain_support_details = Self::query_swapchain_support(
             self.physical_device,
@@ -169,69 +332,279 @@ impl Renderer {
             self.surface,
         )?;
 
-        let (swapchain, swapchain_images, swapchain_image_format, swapchain_extent) =
-            Self::create_swapchain(
-                &self.swapchain_loader,
-                &self.surface,
-                &swapchain_support_details,
-                &device_queue_family_indices,
-                self.width,
-                self.height,
-            )?;
+        let active_present_mode = Self::choose_swap_present_mode(
+            &swapchain_support_details.present_modes,
+            self.present_mode,
+        );
+        let surface_format = Self::choose_swap_surface_format(
+            &swapchain_support_details.formats,
+            &self.surface_formats,
+        );
+        (self.swapchain, self.swapchain_images, self.swapchain_extent) = Self::create_swapchain(
+            &self.swapchain_loader,
+            &self.surface,
+            &swapchain_support_details,
+            &device_queue_family_indices,
+            self.width,
+            self.height,
+            surface_format,
+            active_present_mode,
+        )?;
+        self.active_present_mode = active_present_mode;
+        self.surface_format = surface_format;
+        self.output_transform = OutputTransform::new(surface_format);
 
-        let swapchain_image_views =
-            Self::create_image_views(&self.device, &swapchain_images, swapchain_image_format)?;
+        self.swapchain_image_views =
+            Self::create_image_views(&self.device, &self.swapchain_images, surface_format.format)?;
 
-        let render_pass = Self::create_render_pass(
+        self.render_pass = Self::create_render_pass(
             &self.device,
             &self.instance,
             self.physical_device,
-            swapchain_image_format,
+            HDR_FORMAT,
+            self.msaa_samples,
         )?;
+        self.tonemap_render_pass =
+            Self::create_tonemap_render_pass(&self.device, surface_format.format)?;
 
-        let (pipeline_layout, graphics_pipeline) = Self::create_graphics_pipeline(
+        (
+            self.depth_image,
+            self.depth_image_memory,
+            self.depth_image_view,
+        ) = Self::create_depth_resources(
+            &self.instance,
+            self.physical_device,
             &self.device,
-            swapchain_extent,
-            render_pass,
-            &[
-                self.global_descriptor_set_layout,
-                self.model_descriptor_set_layout,
-            ],
+            self.swapchain_extent,
+            self.msaa_samples,
+            self.graphics_queue,
+            self.command_pool,
+        )?;
+        (
+            self.msaa_image,
+            self.msaa_image_memory,
+            self.msaa_image_view,
+        ) = Self::create_msaa_resources(
+            &self.instance,
+            self.physical_device,
+            &self.device,
+            self.swapchain_extent,
+            self.msaa_samples,
         )?;
 
-        let (depth_image, depth_image_memory, depth_image_view) = Self::create_depth_resources(
+        (
+            self.offscreen_image,
+            self.offscreen_image_memory,
+            self.offscreen_image_view,
+        ) = Self::create_offscreen_resources(
             &self.instance,
             self.physical_device,
             &self.device,
-            swapchain_extent,
-            self.graphics_queue,
-            self.command_pool,
+            self.swapchain_extent,
+        )?;
+        Self::update_post_descriptor_set(
+            &self.device,
+            self.offscreen_descriptor_set,
+            self.offscreen_image_view,
+            self.post_sampler,
+        );
+        self.post_targets = Self::create_post_targets(
+            &self.instance,
+            self.physical_device,
+            &self.device,
+            self.swapchain_extent,
+            self.post_render_pass,
         )?;
+        for (target, &descriptor_set) in self
+            .post_targets
+            .iter()
+            .zip(self.post_target_descriptor_sets.iter())
+        {
+            Self::update_post_descriptor_set(
+                &self.device,
+                descriptor_set,
+                target.view,
+                self.post_sampler,
+            );
+        }
 
-        let swapchain_framebuffers = Self::create_framebuffers(
+        self.offscreen_framebuffer = Self::create_offscreen_framebuffer(
             &self.device,
-            &swapchain_image_views,
-            swapchain_extent,
-            render_pass,
-            &depth_image_view,
+            self.offscreen_image_view,
+            self.msaa_image_view,
+            self.depth_image_view,
+            self.swapchain_extent,
+            self.render_pass,
+        )?;
+        self.swapchain_framebuffers = Self::create_framebuffers(
+            &self.device,
+            &self.swapchain_image_views,
+            self.swapchain_extent,
+            self.tonemap_render_pass,
         )?;
 
-        self.swapchain = swapchain;
-        self.swapchain_images = swapchain_images;
-        self.swapchain_image_format = swapchain_image_format;
-        self.swapchain_extent = swapchain_extent;
-        self.swapchain_image_views = swapchain_image_views;
-        self.render_pass = render_pass;
-        self.pipeline_layout = pipeline_layout;
-        self.graphics_pipeline = graphics_pipeline;
-        self.depth_image = depth_image;
-        self.depth_image_memory = depth_image_memory;
-        self.depth_image_view = depth_image_view;
-        self.swapchain_framebuffers = swapchain_framebuffers;
+        (self.tonemap_pipeline_layout, self.tonemap_pipeline) = Self::create_tonemap_pipeline(
+            &self.device,
+            &self.debug_names,
+            self.swapchain_extent,
+            self.tonemap_render_pass,
+            self.post_descriptor_set_layout,
+            self.pipeline_cache,
+        )?;
+        self.camera.aspect_ratio =
+            self.swapchain_extent.width as f32 / self.swapchain_extent.height as f32;
+        self.swapchain_invalid = false;
+        self.name_swapchain_objects();
 
         Ok(())
     }
 
+    // Names what lives as long as the renderer, the swapchain objects included.
+    fn name_objects(&self) {
+        let names = &self.debug_names;
+        names.set(self.surface, "Window surface");
+        names.set(self.graphics_queue, "Graphics queue");
+        if self.present_queue != self.graphics_queue {
+            names.set(self.present_queue, "Present queue");
+        }
+        names.set(self.command_pool, "Command pool");
+        names.set_all(&self.command_buffers, "Frame command buffer");
+        names.set_all(
+            &self.image_available_semaphores,
+            "Image available semaphore",
+        );
+        names.set_all(
+            &self.render_finished_semaphores,
+            "Render finished semaphore",
+        );
+        names.set_all(&self.in_flight_fences, "In flight fence");
+        names.set(
+            self.global_descriptor_set_layout,
+            "Global descriptor set layout",
+        );
+        names.set(
+            self.model_descriptor_set_layout,
+            "Model descriptor set layout",
+        );
+        names.set(self.pipeline_layout, "Scene pipeline layout");
+        names.set(self.pipeline_cache, "Pipeline cache");
+        let query_pool = self.profiler.borrow().query_pool();
+        if query_pool != vk::QueryPool::null() {
+            names.set(query_pool, "Timestamp query pool");
+        }
+        if self.statistics.query_pool() != vk::QueryPool::null() {
+            names.set(
+                self.statistics.query_pool(),
+                "Pipeline statistics query pool",
+            );
+        }
+        names.set(self.descriptor_pool, "Descriptor pool");
+        names.set_all(&self.global_uniform_buffers, "Global uniform buffer");
+        names.set_all(
+            &self.global_uniform_buffers_memory,
+            "Global uniform buffer memory",
+        );
+        names.set_all(&self.global_descriptor_sets, "Global descriptor set");
+        names.set(self.texture_sampler, "Texture sampler");
+        names.set(self.post_sampler, "Post-process sampler");
+        names.set(
+            self.post_descriptor_set_layout,
+            "Post-process descriptor set layout",
+        );
+        names.set(self.post_descriptor_pool, "Post-process descriptor pool");
+        names.set(self.offscreen_descriptor_set, "Offscreen descriptor set");
+        names.set_all(
+            &self.post_target_descriptor_sets,
+            "Post target descriptor set",
+        );
+        names.set(self.post_render_pass, "Post-process render pass");
+        names.set(self.post_pipeline_layout, "Post-process pipeline layout");
+        for (&pipeline, shader) in self.post_pipelines.iter().zip(PostShader::ALL.iter()) {
+            names.set(pipeline, &format!("{:?} pipeline", shader));
+        }
+        self.name_swapchain_objects();
+    }
+
+    // Names what is created again with the swapchain.
+    fn name_swapchain_objects(&self) {
+        let names = &self.debug_names;
+        names.set(self.swapchain, "Swapchain");
+        names.set_all(&self.swapchain_images, "Swapchain image");
+        names.set_all(&self.swapchain_image_views, "Swapchain image view");
+        names.set_all(&self.swapchain_framebuffers, "Swapchain framebuffer");
+        names.set(self.render_pass, "Scene render pass");
+        names.set(self.depth_image, "Depth image");
+        names.set(self.depth_image_memory, "Depth image memory");
+        names.set(self.depth_image_view, "Depth image view");
+        if self.msaa_image != vk::Image::null() {
+            names.set(self.msaa_image, "MSAA image");
+            names.set(self.msaa_image_memory, "MSAA image memory");
+            names.set(self.msaa_image_view, "MSAA image view");
+        }
+        names.set(self.offscreen_image, "Offscreen image");
+        names.set(self.offscreen_image_memory, "Offscreen image memory");
+        names.set(self.offscreen_image_view, "Offscreen image view");
+        names.set(self.offscreen_framebuffer, "Offscreen framebuffer");
+        for (i, target) in self.post_targets.iter().enumerate() {
+            names.set(target.image, &format!("Post target {} image", i));
+            names.set(target.memory, &format!("Post target {} memory", i));
+            names.set(target.view, &format!("Post target {} view", i));
+            names.set(
+                target.framebuffer,
+                &format!("Post target {} framebuffer", i),
+            );
+        }
+        names.set(self.tonemap_render_pass, "Tonemap render pass");
+        names.set(self.tonemap_pipeline_layout, "Tonemap pipeline layout");
+        names.set(self.tonemap_pipeline, "Tonemap pipeline");
+    }
+
+    // Model resources are named after the OBJ and texture files they come from.
+    fn name_model(&self, model: &Model) {
+        let names = &self.debug_names;
+        let obj = model.obj_path();
+        let texture = model.texture_path();
+        names.set(model.texture_image(), texture);
+        names.set(model.texture_image_memory(), &format!("{} memory", texture));
+        names.set(model.texture_image_view(), &format!("{} view", texture));
+        names.set_all(model.uniform_buffers(), &format!("{} uniform buffer", obj));
+        names.set_all(
+            model.uniform_buffers_memory(),
+            &format!("{} uniform buffer memory", obj),
+        );
+        names.set_all(model.descriptor_sets(), &format!("{} descriptor set", obj));
+        for (i, lod) in model.lods().iter().enumerate() {
+            let mesh = lod.mesh();
+            names.set(
+                mesh.vertex_buffer(),
+                &format!("{} LOD {} vertex buffer", obj, i),
+            );
+            names.set(
+                mesh.vertex_buffer_memory(),
+                &format!("{} LOD {} vertex buffer memory", obj, i),
+            );
+            names.set(
+                mesh.index_buffer(),
+                &format!("{} LOD {} index buffer", obj, i),
+            );
+            names.set(
+                mesh.index_buffer_memory(),
+                &format!("{} LOD {} index buffer memory", obj, i),
+            );
+        }
+    }
+
+    fn name_lut(&self, lut: &Lut) {
+        let names = &self.debug_names;
+        names.set(lut.image(), lut.path());
+        names.set(lut.memory(), &format!("{} memory", lut.path()));
+        names.set(lut.view(), &format!("{} view", lut.path()));
+        names.set(
+            lut.descriptor_set(),
+            &format!("{} descriptor set", lut.path()),
+        );
+    }
+
     fn cleanup_model(&self, model: &Model) {
         for i in 0..MAX_FRAMES_IN_FLIGHT {
             unsafe { self.device.destroy_buffer(model.uniform_buffers()[i], None) };
@@ -240,44 +613,42 @@ impl Renderer {
                     .free_memory(model.uniform_buffers_memory()[i], None)
             };
         }
-        #[cfg(debug_assertions)]
-        println!("Uniform buffers dropped and uniform buffers memory freed.");
+        debug!("Uniform buffers dropped and uniform buffers memory freed.");
 
         unsafe {
             self.device
                 .destroy_image_view(model.texture_image_view(), None)
         };
-        #[cfg(debug_assertions)]
-        println!("Texture image view dropped.");
+        debug!("Texture image view dropped.");
 
         unsafe { self.device.destroy_image(model.texture_image(), None) };
-        #[cfg(debug_assertions)]
-        println!("Texture image dropped.");
+        debug!("Texture image dropped.");
 
         unsafe { self.device.free_memory(model.texture_image_memory(), None) };
-        #[cfg(debug_assertions)]
-        println!("Texture image memory freed.");
+        debug!("Texture image memory freed.");
+
+        for lod in model.lods().iter() {
+            self.cleanup_mesh(lod.mesh());
+        }
+    }
 
-        unsafe { self.device.destroy_buffer(model.index_buffer(), None) };
-        #[cfg(debug_assertions)]
-        println!("Index buffer dropped.");
+    fn cleanup_mesh(&self, mesh: &Mesh) {
+        unsafe { self.device.destroy_buffer(mesh.index_buffer(), None) };
+        debug!("Index buffer dropped.");
 
-        unsafe { self.device.free_memory(model.index_buffer_memory(), None) };
-        #[cfg(debug_assertions)]
-        println!("Index buffer memory freed.");
+        unsafe { self.device.free_memory(mesh.index_buffer_memory(), None) };
+        debug!("Index buffer memory freed.");
 
-        unsafe { self.device.destroy_buffer(model.vertex_buffer(), None) };
-        #[cfg(debug_assertions)]
-        println!("Vertex buffer dropped.");
+        unsafe { self.device.destroy_buffer(mesh.vertex_buffer(), None) };
+        debug!("Vertex buffer dropped.");
 
-        unsafe { self.device.free_memory(model.vertex_buffer_memory(), None) };
-        #[cfg(debug_assertions)]
-        println!("Vertex buffer memory freed.");
+        unsafe { self.device.free_memory(mesh.vertex_buffer_memory(), None) };
+        debug!("Vertex buffer memory freed.");
     }
 
     fn create_sync_objects(
         device: &Device,
-    ) -> Result<(Vec<vk::Semaphore>, Vec<vk::Semaphore>, Vec<vk::Fence>), Box<dyn Error>> {
+    ) -> Result<(Vec<vk::Semaphore>, Vec<vk::Semaphore>, Vec<vk::Fence>), RendererError> {
         let semaphore_info = vk::SemaphoreCreateInfo::default();
         let fence_info = vk::FenceCreateInfo {
             flags: vk::FenceCreateFlags::SIGNALED,
@@ -296,8 +667,7 @@ impl Renderer {
             in_flight_fences.push(unsafe { device.create_fence(&fence_info, None) }?);
         }
 
-        #[cfg(debug_assertions)]
-        println!("Sync objects created.");
+        debug!("Sync objects created.");
 
         Ok((
             image_available_semaphores,
@@ -310,7 +680,9 @@ impl Renderer {
         &self,
         command_buffer: vk::CommandBuffer,
         image_index: u32,
-    ) -> Result<(), Box<dyn Error>> {
+        visible_models: &[usize],
+        screenshot: Option<&Screenshot>,
+    ) -> Result<(), RendererError> {
         let begin_info = vk::CommandBufferBeginInfo {
             ..Default::default()
         };
@@ -318,25 +690,30 @@ impl Renderer {
             self.device
                 .begin_command_buffer(command_buffer, &begin_info)
         }?;
-        #[cfg(debug_assertions)]
-        println!("Begin command buffer.");
-
+        trace!("Begin command buffer.");
+        self.profiler
+            .borrow()
+            .reset_queries(&self.device, command_buffer);
+        self.statistics
+            .begin(&self.device, command_buffer, self.current_frame);
+
+        self.begin_scope(command_buffer, "Scene");
         let clear_values = [
             vk::ClearValue {
                 color: vk::ClearColorValue {
-                    float32: [0f32, 0f32, 0f32, 1f32],
+                    float32: self.clear_color,
                 },
             },
             vk::ClearValue {
                 depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1f32,
+                    depth: if self.reversed_z { 0f32 } else { 1f32 },
                     stencil: 0,
                 },
             },
         ];
         let render_pass_info = vk::RenderPassBeginInfo {
             render_pass: self.render_pass,
-            framebuffer: self.swapchain_framebuffers[image_index as usize],
+            framebuffer: self.offscreen_framebuffer,
             render_area: vk::Rect2D {
                 extent: self.swapchain_extent,
                 ..Default::default()
@@ -352,54 +729,171 @@ impl Renderer {
                 vk::SubpassContents::INLINE,
             )
         };
-        #[cfg(debug_assertions)]
-        println!("Begin render pass command added.");
+        trace!("Begin render pass command added.");
 
         unsafe {
-            self.device.cmd_bind_pipeline(
+            self.device.cmd_bind_descriptor_sets(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.graphics_pipeline,
+                self.pipeline_layout,
+                0,
+                &[self.global_descriptor_sets[self.current_frame]],
+                &[],
             )
         };
-        #[cfg(debug_assertions)]
-        println!("Bind graphics pipeline command added.");
+        self.count(|stats| stats.descriptor_set_binds += 1);
+        trace!("Bind global descriptor sets command added.");
 
+        let push_constants = DebugPushConstants {
+            mode: self.render_mode.id(),
+            near: self.camera.near,
+            far: self.camera.far,
+        };
         unsafe {
-            self.device.cmd_bind_descriptor_sets(
+            self.device.cmd_push_constants(
                 command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
                 self.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
                 0,
-                &[self.global_descriptor_sets[self.current_frame]],
-                &[],
+                std::slice::from_raw_parts(
+                    &push_constants as *const DebugPushConstants as *const u8,
+                    std::mem::size_of::<DebugPushConstants>(),
+                ),
             )
         };
-        #[cfg(debug_assertions)]
-        println!("Bind global descriptor sets command added.");
 
-        for model in self.models.iter() {
+        let viewport = vk::Viewport {
+            width: self.swapchain_extent.width as f32,
+            height: self.swapchain_extent.height as f32,
+            max_depth: 1f32,
+            ..Default::default()
+        };
+        let scissor = vk::Rect2D {
+            extent: self.swapchain_extent,
+            ..Default::default()
+        };
+        unsafe { self.device.cmd_set_viewport(command_buffer, 0, &[viewport]) };
+        unsafe { self.device.cmd_set_scissor(command_buffer, 0, &[scissor]) };
+
+        // The debug render modes replace the materials' pipelines, but the wireframe is drawn over them
+        // in a second pass.
+        let debug_desc = self.render_mode.pipeline_desc();
+        if self.render_mode == RenderMode::WireframeOverSolid {
+            self.record_models(command_buffer, visible_models, None);
+        }
+        self.record_models(command_buffer, visible_models, debug_desc.as_ref());
+
+        unsafe { self.device.cmd_end_render_pass(command_buffer) };
+        trace!("End render pass command added.");
+        self.end_scope(command_buffer);
+
+        self.begin_scope(command_buffer, "Post-processing");
+        let tonemap_input = self.record_post_passes(command_buffer);
+        self.end_scope(command_buffer);
+
+        self.begin_scope(command_buffer, "Tonemap");
+        self.record_tonemap_pass(command_buffer, image_index, tonemap_input);
+        self.end_scope(command_buffer);
+
+        if let Some(screenshot) = screenshot {
+            self.begin_scope(command_buffer, "Screenshot copy");
+            self.record_screenshot_copy(
+                command_buffer,
+                self.swapchain_images[image_index as usize],
+                screenshot,
+            );
+            self.end_scope(command_buffer);
+        }
+
+        self.statistics
+            .end(&self.device, command_buffer, self.current_frame);
+        unsafe { self.device.end_command_buffer(command_buffer) }?;
+        trace!("End command buffer.");
+
+        Ok(())
+    }
+
+    // Debug label and profiler scope around the commands recorded until end_scope.
+    fn begin_scope(&self, command_buffer: vk::CommandBuffer, name: &str) {
+        self.debug_names.begin_label(command_buffer, name);
+        self.profiler
+            .borrow_mut()
+            .begin(&self.device, Some(command_buffer), name);
+    }
+
+    fn end_scope(&self, command_buffer: vk::CommandBuffer) {
+        self.profiler.borrow_mut().end(&self.device);
+        self.debug_names.end_label(command_buffer);
+    }
+
+    // Adds to the stats of the frame being recorded.
+    fn count(&self, update: impl FnOnce(&mut FrameStats)) {
+        let mut stats = self.recorded_stats.get();
+        update(&mut stats);
+        self.recorded_stats.set(stats);
+    }
+
+    // The pipeline statistics replace the primitive and vertex counts when the device has them.
+    fn count_draw(&self, topology: Topology, vertices: u32) {
+        self.count(|stats| {
+            stats.draw_calls += 1;
+            stats.vertices += vertices as u64;
+            stats.triangles += topology.primitives(vertices) as u64;
+        });
+    }
+
+    // Draws with the pipelines of the model materials, or `desc` for all of them. The pipelines have to
+    // exist already, see frame_pipeline_descs.
+    fn record_models(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        visible_models: &[usize],
+        desc: Option<&PipelineDesc>,
+    ) {
+        let mut bound_pipeline = vk::Pipeline::null();
+        for model in visible_models.iter().map(|&i| &self.models[i]) {
+            let model_desc = model.material.pipeline_desc();
+            let desc = desc.unwrap_or(&model_desc);
+            let pipeline = self.pipelines[desc];
+            // One scope per draw group, the models drawn with the same pipeline.
+            if pipeline != bound_pipeline {
+                if bound_pipeline != vk::Pipeline::null() {
+                    self.end_scope(command_buffer);
+                }
+                self.begin_scope(command_buffer, &desc.label());
+                unsafe {
+                    self.device.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline,
+                    )
+                };
+                self.count(|stats| stats.pipeline_binds += 1);
+                trace!("Bind graphics pipeline command added.");
+                bound_pipeline = pipeline;
+            }
+            self.debug_names
+                .insert_label(command_buffer, model.obj_path());
+
             unsafe {
                 self.device.cmd_bind_vertex_buffers(
                     command_buffer,
                     0,
-                    &[model.vertex_buffer()],
+                    &[model.mesh().vertex_buffer()],
                     &[0],
                 )
             }
-            #[cfg(debug_assertions)]
-            println!("Bind vertex buffers command added.");
+            trace!("Bind vertex buffers command added.");
 
             unsafe {
                 self.device.cmd_bind_index_buffer(
                     command_buffer,
-                    model.index_buffer(),
+                    model.mesh().index_buffer(),
                     0,
                     vk::IndexType::UINT32,
                 )
             }
-            #[cfg(debug_assertions)]
-            println!("Bind index buffer command added.");
+            trace!("Bind index buffer command added.");
 
             unsafe {
                 self.device.cmd_bind_descriptor_sets(
@@ -411,114 +905,514 @@ impl Renderer {
                     &[],
                 )
             };
-            #[cfg(debug_assertions)]
-            println!("Bind model descriptor sets command added.");
+            self.count(|stats| stats.descriptor_set_binds += 1);
+            trace!("Bind model descriptor sets command added.");
 
+            let index_count = model.mesh().indices().len() as u32;
             unsafe {
-                self.device.cmd_draw_indexed(
-                    command_buffer,
-                    model.indices().len() as u32,
-                    1,
-                    0,
-                    0,
-                    0,
-                )
+                self.device
+                    .cmd_draw_indexed(command_buffer, index_count, 1, 0, 0, 0)
             };
-            #[cfg(debug_assertions)]
-            println!("Draw indexed command added.");
+            self.count_draw(desc.topology, index_count);
+            trace!("Draw indexed command added.");
+        }
+        if bound_pipeline != vk::Pipeline::null() {
+            self.end_scope(command_buffer);
         }
-
-        unsafe { self.device.cmd_end_render_pass(command_buffer) };
-        #[cfg(debug_assertions)]
-        println!("End render pass command added.");
-
-        unsafe { self.device.end_command_buffer(command_buffer) }?;
-        #[cfg(debug_assertions)]
-        println!("End command buffer.");
-
-        Ok(())
     }
 
-    fn create_command_buffers(
-        device: &Device,
-        command_pool: vk::CommandPool,
-    ) -> Result<Vec<vk::CommandBuffer>, Box<dyn Error>> {
-        let alloc_info = vk::CommandBufferAllocateInfo {
-            command_pool,
-            command_buffer_count: MAX_FRAMES_IN_FLIGHT as u32,
-            ..Default::default()
-        };
-        let command_buffers = unsafe { device.allocate_command_buffers(&alloc_info) }?;
-        #[cfg(debug_assertions)]
-        println!("Command buffers allocated.");
+    // Every pipeline record_models will need for this frame.
+    fn frame_pipeline_descs(&self, visible_models: &[usize]) -> Vec<PipelineDesc> {
+        let mut descs = vec![];
+        if self.render_mode == RenderMode::Solid
+            || self.render_mode == RenderMode::WireframeOverSolid
+        {
+            for &i in visible_models.iter() {
+                let desc = self.models[i].material.pipeline_desc();
+                if !descs.contains(&desc) {
+                    descs.push(desc);
+                }
+            }
+        }
+        descs.extend(self.render_mode.pipeline_desc());
 
-        Ok(command_buffers)
+        descs
     }
 
-    fn find_memory_type(
-        instance: &Instance,
-        physical_device: &vk::PhysicalDevice,
-        type_filter: u32,
-        properties: vk::MemoryPropertyFlags,
-    ) -> Result<u32, Box<dyn Error>> {
-        let mem_properties =
-            unsafe { instance.get_physical_device_memory_properties(*physical_device) };
+    // Runs the enabled post-process passes on the offscreen image, alternating between the two full
+    // resolution targets. Returns the descriptor set of the image holding the result.
+    fn record_post_passes(&self, command_buffer: vk::CommandBuffer) -> vk::DescriptorSet {
+        let full = self.swapchain_extent;
+        let half = self.post_targets[BLOOM_TARGETS[0]].extent;
+        let texel_size =
+            |extent: vk::Extent2D| [1.0 / extent.width as f32, 1.0 / extent.height as f32];
+        let [bloom_a, bloom_b] = BLOOM_TARGETS;
+        let mut input = self.offscreen_descriptor_set;
+        let mut output = 0;
+        for pass in self.post_passes.iter().filter(|pass| pass.enabled) {
+            match &pass.effect {
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                } => {
+                    let mut push_constants = PostPushConstants {
+                        texel_size: texel_size(full),
+                        ..Default::default()
+                    };
+                    push_constants.params[0][0] = *threshold;
+                    self.record_post_step(
+                        command_buffer,
+                        PostShader::Threshold,
+                        bloom_a,
+                        [input, input],
+                        &push_constants,
+                    );
+
+                    let bloom_a_set = self.post_target_descriptor_sets[bloom_a];
+                    let bloom_b_set = self.post_target_descriptor_sets[bloom_b];
+                    for (source, source_set, target, direction) in [
+                        (bloom_a, bloom_a_set, bloom_b, [1.0, 0.0]),
+                        (bloom_b, bloom_b_set, bloom_a, [0.0, 1.0]),
+                    ] {
+                        let push_constants = PostPushConstants {
+                            texel_size: texel_size(self.post_targets[source].extent),
+                            direction,
+                            ..Default::default()
+                        };
+                        self.record_post_step(
+                            command_buffer,
+                            PostShader::Blur,
+                            target,
+                            [source_set, source_set],
+                            &push_constants,
+                        );
+                    }
 
-        for i in 0..mem_properties.memory_type_count {
-            if type_filter & (1 << i) > 0
-                && mem_properties.memory_types[i as usize].property_flags & properties == properties
-            {
-                return Ok(i);
+                    let mut push_constants = PostPushConstants {
+                        texel_size: texel_size(half),
+                        ..Default::default()
+                    };
+                    push_constants.params[0][0] = *intensity;
+                    self.record_post_step(
+                        command_buffer,
+                        PostShader::Bloom,
+                        output,
+                        [input, bloom_a_set],
+                        &push_constants,
+                    );
+                }
+                PostEffect::Fxaa => {
+                    // Edges are found on the tonemapped luma, as they will be seen.
+                    let mut push_constants = PostPushConstants {
+                        texel_size: texel_size(full),
+                        ..Default::default()
+                    };
+                    push_constants.params[0] = [self.tonemap.id() as f32, self.exposure, 0.0, 0.0];
+                    self.record_post_step(
+                        command_buffer,
+                        PostShader::Fxaa,
+                        output,
+                        [input, input],
+                        &push_constants,
+                    );
+                }
+                PostEffect::Vignette { intensity, radius } => {
+                    let mut push_constants = PostPushConstants {
+                        texel_size: texel_size(full),
+                        ..Default::default()
+                    };
+                    push_constants.params[0] = [*intensity, *radius, 0.0, 0.0];
+                    self.record_post_step(
+                        command_buffer,
+                        PostShader::Vignette,
+                        output,
+                        [input, input],
+                        &push_constants,
+                    );
+                }
+                PostEffect::ColorGrading { lut, strength } => {
+                    let lut = match self.post_luts.iter().find(|l| l.path() == lut) {
+                        Some(lut) => lut,
+                        // Only happens if the pass was changed without going through set_post_passes.
+                        None => continue,
+                    };
+                    let [min_r, min_g, min_b] = lut.domain_min();
+                    let [max_r, max_g, max_b] = lut.domain_max();
+                    let push_constants = PostPushConstants {
+                        texel_size: texel_size(full),
+                        params: [
+                            [*strength, lut.size() as f32, 0.0, 0.0],
+                            [min_r, min_g, min_b, 0.0],
+                            [max_r, max_g, max_b, 0.0],
+                        ],
+                        ..Default::default()
+                    };
+                    self.record_post_step(
+                        command_buffer,
+                        PostShader::ColorGrading,
+                        output,
+                        [input, lut.descriptor_set()],
+                        &push_constants,
+                    );
+                }
             }
+            input = self.post_target_descriptor_sets[output];
+            output = 1 - output;
         }
 
-        Err("No suitable memory type found !")?
+        input
     }
 
-    fn create_buffer(
-        instance: &Instance,
-        physical_device: &vk::PhysicalDevice,
-        device: &Device,
-        size: vk::DeviceSize,
-        usage: vk::BufferUsageFlags,
-        properties: vk::MemoryPropertyFlags,
-    ) -> Result<(vk::Buffer, vk::DeviceMemory), Box<dyn Error>> {
-        let buffer_info = vk::BufferCreateInfo {
-            size,
-            usage,
-            sharing_mode: vk::SharingMode::EXCLUSIVE,
+    // Draws a fullscreen triangle into a post target, `descriptor_sets` being the input image and the
+    // one the shader samples besides it, if any.
+    fn record_post_step(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        shader: PostShader,
+        target: usize,
+        descriptor_sets: [vk::DescriptorSet; 2],
+        push_constants: &PostPushConstants,
+    ) {
+        let target = &self.post_targets[target];
+        self.begin_scope(command_buffer, &format!("{:?}", shader));
+        let render_pass_info = vk::RenderPassBeginInfo {
+            render_pass: self.post_render_pass,
+            framebuffer: target.framebuffer,
+            render_area: vk::Rect2D {
+                extent: target.extent,
+                ..Default::default()
+            },
             ..Default::default()
         };
-        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Buffer created.");
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
 
-        let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
-        let alloc_info = vk::MemoryAllocateInfo {
-            allocation_size: mem_requirements.size,
-            memory_type_index: Self::find_memory_type(
-                instance,
-                physical_device,
-                mem_requirements.memory_type_bits,
-                properties,
-            )?,
+        let pipeline = PostShader::ALL
+            .iter()
+            .position(|&s| s == shader)
+            .map(|i| self.post_pipelines[i])
+            .expect("Missing post-process pipeline !");
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline)
+        };
+        self.count(|stats| stats.pipeline_binds += 1);
+        let viewport = vk::Viewport {
+            width: target.extent.width as f32,
+            height: target.extent.height as f32,
+            max_depth: 1f32,
             ..Default::default()
         };
-        let buffer_memory = unsafe { device.allocate_memory(&alloc_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Buffer memory allocated.");
-
-        unsafe { device.bind_buffer_memory(buffer, buffer_memory, 0) }?;
+        let scissor = vk::Rect2D {
+            extent: target.extent,
+            ..Default::default()
+        };
+        unsafe { self.device.cmd_set_viewport(command_buffer, 0, &[viewport]) };
+        unsafe { self.device.cmd_set_scissor(command_buffer, 0, &[scissor]) };
+        unsafe {
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.post_pipeline_layout,
+                0,
+                &descriptor_sets,
+                &[],
+            )
+        };
+        self.count(|stats| stats.descriptor_set_binds += 1);
+        unsafe {
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.post_pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                std::slice::from_raw_parts(
+                    push_constants as *const PostPushConstants as *const u8,
+                    std::mem::size_of::<PostPushConstants>(),
+                ),
+            )
+        };
 
-        Ok((buffer, buffer_memory))
+        unsafe { self.device.cmd_draw(command_buffer, 3, 1, 0, 0) };
+        self.count_draw(Topology::Triangles, 3);
+        unsafe { self.device.cmd_end_render_pass(command_buffer) };
+        self.end_scope(command_buffer);
+        trace!("{:?} post-process step commands added.", shader);
     }
 
-    fn copy_buffer(
+    // Draws the image holding the scene into the swapchain image with a fullscreen triangle.
+    fn record_tonemap_pass(
         &self,
-        src_buffer: vk::Buffer,
-        dst_buffer: vk::Buffer,
-        size: vk::DeviceSize,
-    ) -> Result<(), Box<dyn Error>> {
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        input: vk::DescriptorSet,
+    ) {
+        let render_pass_info = vk::RenderPassBeginInfo {
+            render_pass: self.tonemap_render_pass,
+            framebuffer: self.swapchain_framebuffers[image_index as usize],
+            render_area: vk::Rect2D {
+                extent: self.swapchain_extent,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+        trace!("Begin tonemap render pass command added.");
+
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.tonemap_pipeline,
+            )
+        };
+        self.count(|stats| stats.pipeline_binds += 1);
+        unsafe {
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.tonemap_pipeline_layout,
+                0,
+                &[input],
+                &[],
+            )
+        };
+        self.count(|stats| stats.descriptor_set_binds += 1);
+
+        let push_constants = TonemapPushConstants {
+            tonemap: self.tonemap.id(),
+            exposure: self.exposure,
+            transform: self.output_transform.id(),
+            paper_white: self.paper_white,
+            peak_brightness: self.peak_brightness,
+        };
+        unsafe {
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.tonemap_pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                std::slice::from_raw_parts(
+                    &push_constants as *const TonemapPushConstants as *const u8,
+                    std::mem::size_of::<TonemapPushConstants>(),
+                ),
+            )
+        };
+
+        unsafe { self.device.cmd_draw(command_buffer, 3, 1, 0, 0) };
+        self.count_draw(Topology::Triangles, 3);
+        trace!("Tonemap draw command added.");
+
+        unsafe { self.device.cmd_end_render_pass(command_buffer) };
+        trace!("End tonemap render pass command added.");
+    }
+
+    // Copies the swapchain image to the screenshot buffer once rendering is done, then hands the image
+    // back for presentation.
+    fn record_screenshot_copy(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        screenshot: &Screenshot,
+    ) {
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let to_transfer = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+            old_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            new_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            image,
+            subresource_range,
+            ..Default::default()
+        };
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            )
+        };
+
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D {
+                width: screenshot.extent.width,
+                height: screenshot.extent.height,
+                depth: 1,
+            },
+        };
+        unsafe {
+            self.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                screenshot.buffer,
+                &[region],
+            )
+        };
+
+        let to_present = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::TRANSFER_READ,
+            dst_access_mask: vk::AccessFlags::empty(),
+            old_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            new_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            image,
+            subresource_range,
+            ..Default::default()
+        };
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_present],
+            )
+        };
+        trace!("Screenshot copy commands added.");
+    }
+
+    fn create_screenshot(&self) -> Result<Screenshot, RendererError> {
+        let extent = self.swapchain_extent;
+        let (buffer, memory) = Self::create_buffer(
+            &self.instance,
+            &self.physical_device,
+            &self.device,
+            (extent.width * extent.height * 4) as u64,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        debug!("Screenshot buffer created.");
+        self.debug_names.set(buffer, "Screenshot buffer");
+        self.debug_names.set(memory, "Screenshot buffer memory");
+
+        Ok(Screenshot {
+            buffer,
+            memory,
+            extent,
+            format: self.surface_format.format,
+        })
+    }
+
+    fn cleanup_screenshot(&self, screenshot: &Screenshot) {
+        unsafe { self.device.destroy_buffer(screenshot.buffer, None) };
+        unsafe { self.device.free_memory(screenshot.memory, None) };
+        debug!("Screenshot buffer dropped and memory freed.");
+    }
+
+    fn create_command_buffers(
+        device: &Device,
+        command_pool: vk::CommandPool,
+    ) -> Result<Vec<vk::CommandBuffer>, RendererError> {
+        let alloc_info = vk::CommandBufferAllocateInfo {
+            command_pool,
+            command_buffer_count: MAX_FRAMES_IN_FLIGHT as u32,
+            ..Default::default()
+        };
+        let command_buffers = unsafe { device.allocate_command_buffers(&alloc_info) }?;
+        debug!("Command buffers allocated.");
+
+        Ok(command_buffers)
+    }
+
+    fn find_memory_type(
+        instance: &Instance,
+        physical_device: &vk::PhysicalDevice,
+        type_filter: u32,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<u32, RendererError> {
+        let mem_properties =
+            unsafe { instance.get_physical_device_memory_properties(*physical_device) };
+
+        for i in 0..mem_properties.memory_type_count {
+            if type_filter & (1 << i) > 0
+                && mem_properties.memory_types[i as usize].property_flags & properties == properties
+            {
+                return Ok(i);
+            }
+        }
+
+        Err("No suitable memory type found !")?
+    }
+
+    fn create_buffer(
+        instance: &Instance,
+        physical_device: &vk::PhysicalDevice,
+        device: &Device,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<(vk::Buffer, vk::DeviceMemory), RendererError> {
+        let buffer_info = vk::BufferCreateInfo {
+            size,
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
+        debug!("Buffer created.");
+
+        let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let alloc_info = vk::MemoryAllocateInfo {
+            allocation_size: mem_requirements.size,
+            memory_type_index: Self::find_memory_type(
+                instance,
+                physical_device,
+                mem_requirements.memory_type_bits,
+                properties,
+            )?,
+            ..Default::default()
+        };
+        let buffer_memory = unsafe { device.allocate_memory(&alloc_info, None) }?;
+        debug!("Buffer memory allocated.");
+
+        unsafe { device.bind_buffer_memory(buffer, buffer_memory, 0) }?;
+
+        Ok((buffer, buffer_memory))
+    }
+
+    fn copy_buffer(
+        &self,
+        src_buffer: vk::Buffer,
+        dst_buffer: vk::Buffer,
+        size: vk::DeviceSize,
+    ) -> Result<(), RendererError> {
         let command_buffer = Self::begin_single_time_commands(&self.device, self.command_pool)?;
 
         let copy_region = vk::BufferCopy {
@@ -531,8 +1425,7 @@ impl Renderer {
             self.device
                 .cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &[copy_region])
         };
-        #[cfg(debug_assertions)]
-        println!("Copy command added.");
+        trace!("Copy command added.");
 
         Self::end_single_time_commands(
             &self.device,
@@ -547,7 +1440,7 @@ impl Renderer {
     fn create_vertex_buffer(
         &self,
         vertices: &[Vertex],
-    ) -> Result<(vk::Buffer, vk::DeviceMemory), Box<dyn Error>> {
+    ) -> Result<(vk::Buffer, vk::DeviceMemory), RendererError> {
         let buffer_size = (std::mem::size_of::<Vertex>() * vertices.len()) as u64;
         let (staging_buffer, staging_buffer_memory) = Self::create_buffer(
             &self.instance,
@@ -557,8 +1450,7 @@ impl Renderer {
             vk::BufferUsageFlags::TRANSFER_SRC,
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
         )?;
-        #[cfg(debug_assertions)]
-        println!("Vertex staging buffer created.");
+        debug!("Vertex staging buffer created.");
 
         let data = unsafe {
             self.device.map_memory(
@@ -570,8 +1462,7 @@ impl Renderer {
         }? as *mut Vertex;
         unsafe { data.copy_from_nonoverlapping(vertices.as_ptr(), vertices.len()) };
         unsafe { self.device.unmap_memory(staging_buffer_memory) };
-        #[cfg(debug_assertions)]
-        println!("Vertex staging buffer memory copied.");
+        debug!("Vertex staging buffer memory copied.");
 
         let (vertex_buffer, vertex_buffer_memory) = Self::create_buffer(
             &self.instance,
@@ -581,19 +1472,15 @@ impl Renderer {
             vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
-        #[cfg(debug_assertions)]
-        println!("Vertex buffer created.");
+        debug!("Vertex buffer created.");
 
         self.copy_buffer(staging_buffer, vertex_buffer, buffer_size)?;
-        #[cfg(debug_assertions)]
-        println!("Vertex staging buffer copied to vertex buffer.");
+        debug!("Vertex staging buffer copied to vertex buffer.");
 
         unsafe { self.device.destroy_buffer(staging_buffer, None) };
-        #[cfg(debug_assertions)]
-        println!("Vertex staging buffer dropped.");
+        debug!("Vertex staging buffer dropped.");
         unsafe { self.device.free_memory(staging_buffer_memory, None) };
-        #[cfg(debug_assertions)]
-        println!("Vertex staging buffer memory freed.");
+        debug!("Vertex staging buffer memory freed.");
 
         Ok((vertex_buffer, vertex_buffer_memory))
     }
@@ -601,7 +1488,7 @@ impl Renderer {
     fn create_index_buffer(
         &self,
         indices: &[u32],
-    ) -> Result<(vk::Buffer, vk::DeviceMemory), Box<dyn Error>> {
+    ) -> Result<(vk::Buffer, vk::DeviceMemory), RendererError> {
         let buffer_size = (std::mem::size_of::<u32>() * indices.len()) as u64;
         let (staging_buffer, staging_buffer_memory) = Self::create_buffer(
             &self.instance,
@@ -611,8 +1498,7 @@ impl Renderer {
             vk::BufferUsageFlags::TRANSFER_SRC,
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
         )?;
-        #[cfg(debug_assertions)]
-        println!("Index staging buffer created.");
+        debug!("Index staging buffer created.");
 
         let data = unsafe {
             self.device.map_memory(
@@ -624,8 +1510,7 @@ impl Renderer {
         }? as *mut u32;
         unsafe { data.copy_from_nonoverlapping(indices.as_ptr(), indices.len()) };
         unsafe { self.device.unmap_memory(staging_buffer_memory) };
-        #[cfg(debug_assertions)]
-        println!("Index staging buffer memory copied.");
+        debug!("Index staging buffer memory copied.");
 
         let (index_buffer, index_buffer_memory) = Self::create_buffer(
             &self.instance,
@@ -635,19 +1520,15 @@ impl Renderer {
             vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
-        #[cfg(debug_assertions)]
-        println!("Index buffer created.");
+        debug!("Index buffer created.");
 
         self.copy_buffer(staging_buffer, index_buffer, buffer_size)?;
-        #[cfg(debug_assertions)]
-        println!("Index staging buffer copied to index buffer.");
+        debug!("Index staging buffer copied to index buffer.");
 
         unsafe { self.device.destroy_buffer(staging_buffer, None) };
-        #[cfg(debug_assertions)]
-        println!("Index staging buffer dropped.");
+        debug!("Index staging buffer dropped.");
         unsafe { self.device.free_memory(staging_buffer_memory, None) };
-        #[cfg(debug_assertions)]
-        println!("Index staging buffer memory freed.");
+        debug!("Index staging buffer memory freed.");
 
         Ok((index_buffer, index_buffer_memory))
     }
@@ -656,8 +1537,8 @@ impl Renderer {
         instance: &Instance,
         physical_device: &vk::PhysicalDevice,
         device: &Device,
-    ) -> Result<(Vec<vk::Buffer>, Vec<vk::DeviceMemory>), Box<dyn Error>> {
-        let buffer_size = std::mem::size_of::<UniformBufferObject>() as u64;
+    ) -> Result<(Vec<vk::Buffer>, Vec<vk::DeviceMemory>), RendererError> {
+        let buffer_size = std::mem::size_of::<GlobalUniformBufferObject>() as u64;
 
         let mut uniform_buffers = vec![];
         let mut uniform_buffers_memory = vec![];
@@ -674,16 +1555,15 @@ impl Renderer {
             uniform_buffers_memory.push(uniform_buffer_memory);
         }
 
-        #[cfg(debug_assertions)]
-        println!("Uniform buffers and uniform buffers memory created.");
+        debug!("Uniform buffers and uniform buffers memory created.");
 
         Ok((uniform_buffers, uniform_buffers_memory))
     }
 
     fn create_model_uniform_buffers(
         &self,
-    ) -> Result<(Vec<vk::Buffer>, Vec<vk::DeviceMemory>), Box<dyn Error>> {
-        let buffer_size = std::mem::size_of::<UniformBufferObject>() as u64;
+    ) -> Result<(Vec<vk::Buffer>, Vec<vk::DeviceMemory>), RendererError> {
+        let buffer_size = std::mem::size_of::<ModelUniformBufferObject>() as u64;
 
         let mut uniform_buffers = vec![];
         let mut uniform_buffers_memory = vec![];
@@ -700,13 +1580,12 @@ impl Renderer {
             uniform_buffers_memory.push(uniform_buffer_memory);
         }
 
-        #[cfg(debug_assertions)]
-        println!("Uniform buffers and uniform buffers memory created.");
+        debug!("Uniform buffers and uniform buffers memory created.");
 
         Ok((uniform_buffers, uniform_buffers_memory))
     }
 
-    fn create_descriptor_pool(device: &Device) -> Result<vk::DescriptorPool, Box<dyn Error>> {
+    fn create_descriptor_pool(device: &Device) -> Result<vk::DescriptorPool, RendererError> {
         let pool_sizes = [
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::UNIFORM_BUFFER,
@@ -714,10 +1593,14 @@ impl Renderer {
                 ..Default::default()
             },
             vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                ty: vk::DescriptorType::SAMPLED_IMAGE,
                 descriptor_count: (MAX_MODELS * MAX_FRAMES_IN_FLIGHT) as u32,
                 ..Default::default()
             },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLER,
+                descriptor_count: (MAX_MODELS * MAX_FRAMES_IN_FLIGHT) as u32,
+            },
         ];
         let pool_info = vk::DescriptorPoolCreateInfo {
             pool_size_count: pool_sizes.len() as u32,
@@ -726,8 +1609,7 @@ impl Renderer {
             ..Default::default()
         };
         let descriptor_pool = unsafe { device.create_descriptor_pool(&pool_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Descriptor pool created.");
+        debug!("Descriptor pool created.");
 
         Ok(descriptor_pool)
     }
@@ -737,7 +1619,7 @@ impl Renderer {
         descriptor_pool: vk::DescriptorPool,
         descriptor_set_layout: vk::DescriptorSetLayout,
         uniform_buffers: &[vk::Buffer],
-    ) -> Result<Vec<vk::DescriptorSet>, Box<dyn Error>> {
+    ) -> Result<Vec<vk::DescriptorSet>, RendererError> {
         let layouts = vec![descriptor_set_layout; MAX_FRAMES_IN_FLIGHT];
         let alloc_info = vk::DescriptorSetAllocateInfo {
             descriptor_pool,
@@ -746,14 +1628,13 @@ impl Renderer {
             ..Default::default()
         };
         let descriptor_sets = unsafe { device.allocate_descriptor_sets(&alloc_info) }?;
-        #[cfg(debug_assertions)]
-        println!("Global descriptor sets created.");
+        debug!("Global descriptor sets created.");
 
         for i in 0..MAX_FRAMES_IN_FLIGHT {
             let buffer_info = vk::DescriptorBufferInfo {
                 buffer: uniform_buffers[i],
                 offset: 0,
-                range: std::mem::size_of::<UniformBufferObject>() as u64,
+                range: std::mem::size_of::<GlobalUniformBufferObject>() as u64,
             };
             let descriptor_writes = [vk::WriteDescriptorSet {
                 dst_set: descriptor_sets[i],
@@ -774,7 +1655,7 @@ impl Renderer {
         &self,
         uniform_buffers: &[vk::Buffer],
         texture_image_view: vk::ImageView,
-    ) -> Result<Vec<vk::DescriptorSet>, Box<dyn Error>> {
+    ) -> Result<Vec<vk::DescriptorSet>, RendererError> {
         let layouts = vec![self.model_descriptor_set_layout; MAX_FRAMES_IN_FLIGHT];
         let alloc_info = vk::DescriptorSetAllocateInfo {
             descriptor_pool: self.descriptor_pool,
@@ -783,19 +1664,22 @@ impl Renderer {
             ..Default::default()
         };
         let descriptor_sets = unsafe { self.device.allocate_descriptor_sets(&alloc_info) }?;
-        #[cfg(debug_assertions)]
-        println!("Model descriptor sets created.");
+        debug!("Model descriptor sets created.");
 
         for i in 0..MAX_FRAMES_IN_FLIGHT {
             let buffer_info = vk::DescriptorBufferInfo {
                 buffer: uniform_buffers[i],
                 offset: 0,
-                range: std::mem::size_of::<UniformBufferObject>() as u64,
+                range: std::mem::size_of::<ModelUniformBufferObject>() as u64,
             };
             let image_info = vk::DescriptorImageInfo {
                 image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
                 image_view: texture_image_view,
+                ..Default::default()
+            };
+            let sampler_info = vk::DescriptorImageInfo {
                 sampler: self.texture_sampler,
+                ..Default::default()
             };
             let descriptor_writes = [
                 vk::WriteDescriptorSet {
@@ -811,11 +1695,20 @@ impl Renderer {
                     dst_set: descriptor_sets[i],
                     dst_binding: 1,
                     dst_array_element: 0,
-                    descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    descriptor_type: vk::DescriptorType::SAMPLED_IMAGE,
                     descriptor_count: 1,
                     p_image_info: &image_info,
                     ..Default::default()
                 },
+                vk::WriteDescriptorSet {
+                    dst_set: descriptor_sets[i],
+                    dst_binding: 2,
+                    dst_array_element: 0,
+                    descriptor_type: vk::DescriptorType::SAMPLER,
+                    descriptor_count: 1,
+                    p_image_info: &sampler_info,
+                    ..Default::default()
+                },
             ];
             unsafe { self.device.update_descriptor_sets(&descriptor_writes, &[]) };
         }
@@ -823,10 +1716,113 @@ impl Renderer {
         Ok(descriptor_sets)
     }
 
+    // Separate from the main pool, as lookup tables come and go.
+    fn create_post_descriptor_pool(device: &Device) -> Result<vk::DescriptorPool, RendererError> {
+        // The offscreen image, the post targets and the lookup tables, twice as many of those since
+        // set_post_passes loads the new ones before dropping the unused ones.
+        let max_sets = (1 + POST_TARGETS + 2 * MAX_LUTS) as u32;
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLED_IMAGE,
+                descriptor_count: max_sets,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::SAMPLER,
+                descriptor_count: max_sets,
+            },
+        ];
+        let pool_info = vk::DescriptorPoolCreateInfo {
+            flags: vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+            pool_size_count: pool_sizes.len() as u32,
+            p_pool_sizes: pool_sizes.as_ptr(),
+            max_sets,
+            ..Default::default()
+        };
+        let descriptor_pool = unsafe { device.create_descriptor_pool(&pool_info, None) }?;
+        debug!("Post-process descriptor pool created.");
+
+        Ok(descriptor_pool)
+    }
+
+    fn allocate_post_descriptor_set(
+        device: &Device,
+        descriptor_pool: vk::DescriptorPool,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> Result<vk::DescriptorSet, RendererError> {
+        let alloc_info = vk::DescriptorSetAllocateInfo {
+            descriptor_pool,
+            descriptor_set_count: 1,
+            p_set_layouts: &descriptor_set_layout,
+            ..Default::default()
+        };
+        let descriptor_sets = unsafe { device.allocate_descriptor_sets(&alloc_info) }?;
+        debug!("Post-process descriptor set created.");
+
+        Ok(descriptor_sets[0])
+    }
+
+    // Images sampled by the post-process steps change with the swapchain, but keep their descriptor set.
+    fn update_post_descriptor_set(
+        device: &Device,
+        descriptor_set: vk::DescriptorSet,
+        image_view: vk::ImageView,
+        post_sampler: vk::Sampler,
+    ) {
+        let image_info = vk::DescriptorImageInfo {
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            image_view,
+            ..Default::default()
+        };
+        let sampler_info = vk::DescriptorImageInfo {
+            sampler: post_sampler,
+            ..Default::default()
+        };
+        let descriptor_writes = [
+            vk::WriteDescriptorSet {
+                dst_set: descriptor_set,
+                dst_binding: 0,
+                dst_array_element: 0,
+                descriptor_type: vk::DescriptorType::SAMPLED_IMAGE,
+                descriptor_count: 1,
+                p_image_info: &image_info,
+                ..Default::default()
+            },
+            vk::WriteDescriptorSet {
+                dst_set: descriptor_set,
+                dst_binding: 1,
+                dst_array_element: 0,
+                descriptor_type: vk::DescriptorType::SAMPLER,
+                descriptor_count: 1,
+                p_image_info: &sampler_info,
+                ..Default::default()
+            },
+        ];
+        unsafe { device.update_descriptor_sets(&descriptor_writes, &[]) };
+    }
+
+    fn create_post_descriptor_set(
+        &self,
+        image_view: vk::ImageView,
+    ) -> Result<vk::DescriptorSet, RendererError> {
+        let descriptor_set = Self::allocate_post_descriptor_set(
+            &self.device,
+            self.post_descriptor_pool,
+            self.post_descriptor_set_layout,
+        )?;
+        Self::update_post_descriptor_set(
+            &self.device,
+            descriptor_set,
+            image_view,
+            self.post_sampler,
+        );
+
+        Ok(descriptor_set)
+    }
+
     fn create_command_pool(
         device: &Device,
         device_queue_family_indices: &QueueFamilyIndices,
-    ) -> Result<vk::CommandPool, Box<dyn Error>> {
+    ) -> Result<vk::CommandPool, RendererError> {
         let pool_info = vk::CommandPoolCreateInfo {
             flags: vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
             queue_family_index: device_queue_family_indices
@@ -835,8 +1831,7 @@ impl Renderer {
             ..Default::default()
         };
         let command_pool = unsafe { device.create_command_pool(&pool_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Command pool created.");
+        debug!("Command pool created.");
 
         Ok(command_pool)
     }
@@ -844,7 +1839,7 @@ impl Renderer {
     fn begin_single_time_commands(
         device: &Device,
         command_pool: vk::CommandPool,
-    ) -> Result<vk::CommandBuffer, Box<dyn Error>> {
+    ) -> Result<vk::CommandBuffer, RendererError> {
         let alloc_info = vk::CommandBufferAllocateInfo {
             level: vk::CommandBufferLevel::PRIMARY,
             command_pool,
@@ -852,16 +1847,14 @@ impl Renderer {
             ..Default::default()
         };
         let command_buffer = unsafe { device.allocate_command_buffers(&alloc_info) }?[0];
-        #[cfg(debug_assertions)]
-        println!("Single time command buffer allocated.");
+        debug!("Single time command buffer allocated.");
 
         let begin_info = vk::CommandBufferBeginInfo {
             flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
             ..Default::default()
         };
         unsafe { device.begin_command_buffer(command_buffer, &begin_info) }?;
-        #[cfg(debug_assertions)]
-        println!("Begin single time command buffer.");
+        debug!("Begin single time command buffer.");
 
         Ok(command_buffer)
     }
@@ -871,10 +1864,9 @@ impl Renderer {
         command_buffer: vk::CommandBuffer,
         command_pool: vk::CommandPool,
         graphics_queue: vk::Queue,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), RendererError> {
         unsafe { device.end_command_buffer(command_buffer) }?;
-        #[cfg(debug_assertions)]
-        println!("End single time command buffer.");
+        debug!("End single time command buffer.");
 
         let submit_info = vk::SubmitInfo {
             command_buffer_count: 1,
@@ -882,15 +1874,12 @@ impl Renderer {
             ..Default::default()
         };
         unsafe { device.queue_submit(graphics_queue, &[submit_info], vk::Fence::null()) }?;
-        #[cfg(debug_assertions)]
-        println!("Single time command buffer submitted.");
+        debug!("Single time command buffer submitted.");
         unsafe { device.queue_wait_idle(graphics_queue) }?;
-        #[cfg(debug_assertions)]
-        println!("Graphics queue idle.");
+        debug!("Graphics queue idle.");
 
         unsafe { device.free_command_buffers(command_pool, &[command_buffer]) };
-        #[cfg(debug_assertions)]
-        println!("Single time command buffer freed.");
+        debug!("Single time command buffer freed.");
 
         Ok(())
     }
@@ -901,17 +1890,23 @@ impl Renderer {
         device: &Device,
         width: u32,
         height: u32,
+        depth: u32,
+        samples: vk::SampleCountFlags,
         format: vk::Format,
         tiling: vk::ImageTiling,
         usage: vk::ImageUsageFlags,
         properties: vk::MemoryPropertyFlags,
-    ) -> Result<(vk::Image, vk::DeviceMemory), Box<dyn Error>> {
+    ) -> Result<(vk::Image, vk::DeviceMemory), RendererError> {
         let image_info = vk::ImageCreateInfo {
-            image_type: vk::ImageType::TYPE_2D,
+            image_type: if depth > 1 {
+                vk::ImageType::TYPE_3D
+            } else {
+                vk::ImageType::TYPE_2D
+            },
             extent: vk::Extent3D {
                 width,
                 height,
-                depth: 1,
+                depth,
             },
             mip_levels: 1,
             array_layers: 1,
@@ -920,12 +1915,11 @@ impl Renderer {
             initial_layout: vk::ImageLayout::UNDEFINED,
             usage,
             sharing_mode: vk::SharingMode::EXCLUSIVE,
-            samples: vk::SampleCountFlags::TYPE_1,
+            samples,
             ..Default::default()
         };
         let image = unsafe { device.create_image(&image_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Image created.");
+        debug!("Image created.");
 
         let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
         let alloc_info = vk::MemoryAllocateInfo {
@@ -939,8 +1933,7 @@ impl Renderer {
             ..Default::default()
         };
         let image_memory = unsafe { device.allocate_memory(&alloc_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Image memory allocated.");
+        debug!("Image memory allocated.");
 
         unsafe { device.bind_image_memory(image, image_memory, 0) }?;
 
@@ -953,7 +1946,8 @@ impl Renderer {
         image: vk::Image,
         width: u32,
         height: u32,
-    ) -> Result<(), Box<dyn Error>> {
+        depth: u32,
+    ) -> Result<(), RendererError> {
         let command_buffer = Self::begin_single_time_commands(&self.device, self.command_pool)?;
 
         let region = vk::BufferImageCopy {
@@ -971,7 +1965,7 @@ impl Renderer {
             image_extent: vk::Extent3D {
                 width,
                 height,
-                depth: 1,
+                depth,
             },
             ..Default::default()
         };
@@ -1004,7 +1998,7 @@ impl Renderer {
         format: vk::Format,
         old_layout: vk::ImageLayout,
         new_layout: vk::ImageLayout,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), RendererError> {
         let command_buffer = Self::begin_single_time_commands(device, command_pool)?;
 
         let src_access_mask;
@@ -1085,7 +2079,7 @@ impl Renderer {
     fn create_texture_image(
         &self,
         texture: &Texture,
-    ) -> Result<(vk::Image, vk::DeviceMemory), Box<dyn Error>> {
+    ) -> Result<(vk::Image, vk::DeviceMemory), RendererError> {
         let image_size = (texture.width() * texture.height() * 4) as vk::DeviceSize;
 
         let (staging_buffer, staging_buffer_memory) = Self::create_buffer(
@@ -1096,8 +2090,7 @@ impl Renderer {
             vk::BufferUsageFlags::TRANSFER_SRC,
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
         )?;
-        #[cfg(debug_assertions)]
-        println!("Texture staging buffer created.");
+        debug!("Texture staging buffer created.");
 
         let data = unsafe {
             self.device.map_memory(
@@ -1109,8 +2102,7 @@ impl Renderer {
         }? as *mut u8;
         unsafe { data.copy_from_nonoverlapping(texture.pixels().as_ptr(), texture.pixels().len()) };
         unsafe { self.device.unmap_memory(staging_buffer_memory) };
-        #[cfg(debug_assertions)]
-        println!("Texture staging buffer memory copied.");
+        debug!("Texture staging buffer memory copied.");
 
         let (texture_image, texture_image_memory) = Self::create_image(
             &self.instance,
@@ -1118,13 +2110,14 @@ impl Renderer {
             &self.device,
             texture.width(),
             texture.height(),
+            1,
+            vk::SampleCountFlags::TYPE_1,
             vk::Format::R8G8B8A8_SRGB,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
-        #[cfg(debug_assertions)]
-        println!("Texture image created.");
+        debug!("Texture image created.");
 
         Self::transition_image_layout(
             &self.device,
@@ -1140,6 +2133,7 @@ impl Renderer {
             texture_image,
             texture.width(),
             texture.height(),
+            1,
         )?;
         Self::transition_image_layout(
             &self.device,
@@ -1152,27 +2146,118 @@ impl Renderer {
         )?;
 
         unsafe { self.device.destroy_buffer(staging_buffer, None) };
-        #[cfg(debug_assertions)]
-        println!("Texture staging buffer dropped.");
+        debug!("Texture staging buffer dropped.");
         unsafe { self.device.free_memory(staging_buffer_memory, None) };
-        #[cfg(debug_assertions)]
-        println!("Texture staging buffer memory freed.");
+        debug!("Texture staging buffer memory freed.");
 
         Ok((texture_image, texture_image_memory))
     }
 
-    fn create_image_view(
-        device: &Device,
-        image: vk::Image,
-        format: vk::Format,
-        aspect_flags: vk::ImageAspectFlags,
-    ) -> Result<vk::ImageView, Box<dyn Error>> {
-        let view_info = vk::ImageViewCreateInfo {
-            image,
-            view_type: vk::ImageViewType::TYPE_2D,
-            format,
-            subresource_range: vk::ImageSubresourceRange {
-                aspect_mask: aspect_flags,
+    fn create_lut_image(
+        &self,
+        lut: &CubeLut,
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), RendererError> {
+        let format = vk::Format::A2B10G10R10_UNORM_PACK32;
+        let packed = lut.packed();
+        let image_size = std::mem::size_of_val(packed.as_slice()) as vk::DeviceSize;
+
+        let (staging_buffer, staging_buffer_memory) = Self::create_buffer(
+            &self.instance,
+            &self.physical_device,
+            &self.device,
+            image_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        let data = unsafe {
+            self.device.map_memory(
+                staging_buffer_memory,
+                0,
+                image_size,
+                vk::MemoryMapFlags::default(),
+            )
+        }? as *mut u32;
+        unsafe { data.copy_from_nonoverlapping(packed.as_ptr(), packed.len()) };
+        unsafe { self.device.unmap_memory(staging_buffer_memory) };
+
+        let (image, image_memory) = Self::create_image(
+            &self.instance,
+            &self.physical_device,
+            &self.device,
+            lut.size(),
+            lut.size(),
+            lut.size(),
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        Self::transition_image_layout(
+            &self.device,
+            self.graphics_queue,
+            self.command_pool,
+            image,
+            format,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        )?;
+        self.copy_buffer_to_image(staging_buffer, image, lut.size(), lut.size(), lut.size())?;
+        Self::transition_image_layout(
+            &self.device,
+            self.graphics_queue,
+            self.command_pool,
+            image,
+            format,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )?;
+
+        unsafe { self.device.destroy_buffer(staging_buffer, None) };
+        unsafe { self.device.free_memory(staging_buffer_memory, None) };
+
+        let view_info = vk::ImageViewCreateInfo {
+            image,
+            view_type: vk::ImageViewType::TYPE_3D,
+            format,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                level_count: 1,
+                layer_count: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let image_view = unsafe { self.device.create_image_view(&view_info, None) }?;
+        debug!("Lookup table image created.");
+
+        Ok((image, image_memory, image_view))
+    }
+
+    fn cleanup_lut(&self, lut: &Lut) {
+        unsafe { self.device.destroy_image_view(lut.view(), None) };
+        unsafe { self.device.destroy_image(lut.image(), None) };
+        unsafe { self.device.free_memory(lut.memory(), None) };
+        unsafe {
+            self.device
+                .free_descriptor_sets(self.post_descriptor_pool, &[lut.descriptor_set()])
+        }
+        .expect("Error freeing lookup table descriptor set !");
+        debug!("Lookup table dropped.");
+    }
+
+    fn create_image_view(
+        device: &Device,
+        image: vk::Image,
+        format: vk::Format,
+        aspect_flags: vk::ImageAspectFlags,
+    ) -> Result<vk::ImageView, RendererError> {
+        let view_info = vk::ImageViewCreateInfo {
+            image,
+            view_type: vk::ImageViewType::TYPE_2D,
+            format,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: aspect_flags,
                 base_mip_level: 0,
                 level_count: 1,
                 base_array_layer: 0,
@@ -1190,15 +2275,14 @@ impl Renderer {
     fn create_texture_image_view(
         &self,
         texture_image: vk::Image,
-    ) -> Result<vk::ImageView, Box<dyn Error>> {
+    ) -> Result<vk::ImageView, RendererError> {
         let image_view = Self::create_image_view(
             &self.device,
             texture_image,
             vk::Format::R8G8B8A8_SRGB,
             vk::ImageAspectFlags::COLOR,
         )?;
-        #[cfg(debug_assertions)]
-        println!("Texture image view created.");
+        debug!("Texture image view created.");
 
         Ok(image_view)
     }
@@ -1207,7 +2291,7 @@ impl Renderer {
         instance: &Instance,
         physical_device: vk::PhysicalDevice,
         device: &Device,
-    ) -> Result<vk::Sampler, Box<dyn Error>> {
+    ) -> Result<vk::Sampler, RendererError> {
         let properties = unsafe { instance.get_physical_device_properties(physical_device) };
         let sampler_info = vk::SamplerCreateInfo {
             mag_filter: vk::Filter::LINEAR,
@@ -1229,23 +2313,42 @@ impl Renderer {
         };
 
         let texture_sampler = unsafe { device.create_sampler(&sampler_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Texture sampler created.");
+        debug!("Texture sampler created.");
 
         Ok(texture_sampler)
     }
 
+    // Filtered for the half resolution bloom and the lookup tables, but never repeated.
+    fn create_post_sampler(device: &Device) -> Result<vk::Sampler, RendererError> {
+        let sampler_info = vk::SamplerCreateInfo {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            border_color: vk::BorderColor::INT_OPAQUE_BLACK,
+            unnormalized_coordinates: vk::FALSE,
+            compare_op: vk::CompareOp::ALWAYS,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            ..Default::default()
+        };
+
+        let post_sampler = unsafe { device.create_sampler(&sampler_info, None) }?;
+        debug!("Post-process sampler created.");
+
+        Ok(post_sampler)
+    }
+
     fn create_framebuffers(
         device: &Device,
         swapchain_image_views: &[vk::ImageView],
         swapchain_extent: vk::Extent2D,
         render_pass: vk::RenderPass,
-        depth_image_view: &vk::ImageView,
-    ) -> Result<Vec<vk::Framebuffer>, Box<dyn Error>> {
+    ) -> Result<Vec<vk::Framebuffer>, RendererError> {
         let swapchain_framebuffers = swapchain_image_views
             .iter()
             .map(|swapchain_image_view| {
-                let attachments = [*swapchain_image_view, *depth_image_view];
+                let attachments = [*swapchain_image_view];
                 let framebuffer_info = vk::FramebufferCreateInfo {
                     render_pass,
                     attachment_count: attachments.len() as u32,
@@ -1260,29 +2363,87 @@ impl Renderer {
                 framebuffer
             })
             .collect();
-        #[cfg(debug_assertions)]
-        println!("Framebuffers created.");
+        debug!("Framebuffers created.");
 
         Ok(swapchain_framebuffers)
     }
 
+    // Follows the attachments of create_render_pass, the offscreen image being the resolve attachment
+    // when there is a multisampled one.
+    fn create_offscreen_framebuffer(
+        device: &Device,
+        offscreen_image_view: vk::ImageView,
+        msaa_image_view: vk::ImageView,
+        depth_image_view: vk::ImageView,
+        swapchain_extent: vk::Extent2D,
+        render_pass: vk::RenderPass,
+    ) -> Result<vk::Framebuffer, RendererError> {
+        let attachments = if msaa_image_view == vk::ImageView::null() {
+            vec![offscreen_image_view, depth_image_view]
+        } else {
+            vec![msaa_image_view, depth_image_view, offscreen_image_view]
+        };
+        let framebuffer_info = vk::FramebufferCreateInfo {
+            render_pass,
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            width: swapchain_extent.width,
+            height: swapchain_extent.height,
+            layers: 1,
+            ..Default::default()
+        };
+        let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None) }?;
+        debug!("Offscreen framebuffer created.");
+
+        Ok(framebuffer)
+    }
+
     fn create_render_pass(
         device: &Device,
         instance: &Instance,
         physical_device: vk::PhysicalDevice,
-        swapchain_image_format: vk::Format,
-    ) -> Result<vk::RenderPass, Box<dyn Error>> {
+        color_format: vk::Format,
+        samples: vk::SampleCountFlags,
+    ) -> Result<vk::RenderPass, RendererError> {
+        let multisampled = samples != vk::SampleCountFlags::TYPE_1;
+        // Left ready to be sampled by the tonemap pass, unless it is resolved into the offscreen image.
         let color_attachment = vk::AttachmentDescription {
-            format: swapchain_image_format,
-            samples: vk::SampleCountFlags::TYPE_1,
+            format: color_format,
+            samples,
             load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: if multisampled {
+                vk::AttachmentStoreOp::DONT_CARE
+            } else {
+                vk::AttachmentStoreOp::STORE
+            },
             stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
             stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
             initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            final_layout: if multisampled {
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            },
+            ..Default::default()
+        };
+
+        let resolve_attachment = vk::AttachmentDescription {
+            format: color_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
             ..Default::default()
         };
 
+        let resolve_attachment_ref = vk::AttachmentReference {
+            attachment: 2,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+
         let color_attachment_ref = vk::AttachmentReference {
             layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
             ..Default::default()
@@ -1291,7 +2452,7 @@ impl Renderer {
         let depth_format = Self::find_depth_format(instance, physical_device)?;
         let depth_attachment = vk::AttachmentDescription {
             format: depth_format,
-            samples: vk::SampleCountFlags::TYPE_1,
+            samples,
             load_op: vk::AttachmentLoadOp::CLEAR,
             store_op: vk::AttachmentStoreOp::DONT_CARE,
             stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
@@ -1311,25 +2472,100 @@ impl Renderer {
             pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
             color_attachment_count: 1,
             p_color_attachments: &color_attachment_ref,
+            p_resolve_attachments: if multisampled {
+                &resolve_attachment_ref
+            } else {
+                std::ptr::null()
+            },
             p_depth_stencil_attachment: &depth_attachment_ref,
             ..Default::default()
         };
 
+        let dependencies = [
+            // The previous frame's tonemap pass has to be done reading the offscreen image.
+            vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ..Default::default()
+            },
+            vk::SubpassDependency {
+                src_subpass: 0,
+                dst_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                ..Default::default()
+            },
+        ];
+
+        let attachments = if multisampled {
+            vec![color_attachment, depth_attachment, resolve_attachment]
+        } else {
+            vec![color_attachment, depth_attachment]
+        };
+        let render_pass_info = vk::RenderPassCreateInfo {
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            subpass_count: 1,
+            p_subpasses: &subpass,
+            dependency_count: dependencies.len() as u32,
+            p_dependencies: dependencies.as_ptr(),
+            ..Default::default()
+        };
+
+        let render_pass = unsafe { device.create_render_pass(&render_pass_info, None) }?;
+        debug!("Render pass created.");
+
+        Ok(render_pass)
+    }
+
+    fn create_tonemap_render_pass(
+        device: &Device,
+        swapchain_image_format: vk::Format,
+    ) -> Result<vk::RenderPass, RendererError> {
+        // Every pixel is drawn, so the previous content doesn't matter.
+        let color_attachment = vk::AttachmentDescription {
+            format: swapchain_image_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            ..Default::default()
+        };
+
+        let color_attachment_ref = vk::AttachmentReference {
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        };
+
+        let subpass = vk::SubpassDescription {
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            color_attachment_count: 1,
+            p_color_attachments: &color_attachment_ref,
+            ..Default::default()
+        };
+
         let dependency = vk::SubpassDependency {
             src_subpass: vk::SUBPASS_EXTERNAL,
-            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
-                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
             ..Default::default()
         };
 
-        let attachments = [color_attachment, depth_attachment];
         let render_pass_info = vk::RenderPassCreateInfo {
-            attachment_count: attachments.len() as u32,
-            p_attachments: attachments.as_ptr(),
+            attachment_count: 1,
+            p_attachments: &color_attachment,
             subpass_count: 1,
             p_subpasses: &subpass,
             dependency_count: 1,
@@ -1338,38 +2574,100 @@ impl Renderer {
         };
 
         let render_pass = unsafe { device.create_render_pass(&render_pass_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Render pass created.");
+        debug!("Tonemap render pass created.");
+
+        Ok(render_pass)
+    }
+
+    // Like the tonemap render pass, but leaves the target ready to be sampled by the next step.
+    fn create_post_render_pass(device: &Device) -> Result<vk::RenderPass, RendererError> {
+        let color_attachment = vk::AttachmentDescription {
+            format: HDR_FORMAT,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ..Default::default()
+        };
+
+        let color_attachment_ref = vk::AttachmentReference {
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        };
+
+        let subpass = vk::SubpassDescription {
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            color_attachment_count: 1,
+            p_color_attachments: &color_attachment_ref,
+            ..Default::default()
+        };
+
+        let dependencies = [
+            // Earlier steps, or the previous frame, have to be done reading the target.
+            vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                ..Default::default()
+            },
+            vk::SubpassDependency {
+                src_subpass: 0,
+                dst_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                ..Default::default()
+            },
+        ];
+
+        let render_pass_info = vk::RenderPassCreateInfo {
+            attachment_count: 1,
+            p_attachments: &color_attachment,
+            subpass_count: 1,
+            p_subpasses: &subpass,
+            dependency_count: dependencies.len() as u32,
+            p_dependencies: dependencies.as_ptr(),
+            ..Default::default()
+        };
+
+        let render_pass = unsafe { device.create_render_pass(&render_pass_info, None) }?;
+        debug!("Post-process render pass created.");
 
         Ok(render_pass)
     }
 
     fn create_shader_module(
         device: &Device,
-        shader: &[u8],
-    ) -> Result<vk::ShaderModule, Box<dyn Error>> {
+        debug_names: &DebugNames,
+        path: &str,
+        shader: &[u32],
+    ) -> Result<vk::ShaderModule, RendererError> {
         let shader_module_create_info = vk::ShaderModuleCreateInfo {
-            code_size: shader.len(),
-            p_code: shader.as_ptr() as *const u32,
+            code_size: std::mem::size_of_val(shader),
+            p_code: shader.as_ptr(),
             ..Default::default()
         };
 
         let shader_module =
             unsafe { device.create_shader_module(&shader_module_create_info, None) }?;
+        debug_names.set(shader_module, path);
 
         Ok(shader_module)
     }
 
-    fn create_global_descriptor_set_layout(
+    // One of the sets of the scene pass, as declared by its shaders.
+    fn create_scene_descriptor_set_layout(
         device: &Device,
-    ) -> Result<vk::DescriptorSetLayout, Box<dyn Error>> {
-        let bindings = [vk::DescriptorSetLayoutBinding {
-            binding: 0,
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-            descriptor_count: 1,
-            stage_flags: vk::ShaderStageFlags::VERTEX,
-            ..Default::default()
-        }];
+        layout: &LayoutReflection,
+        set: u32,
+    ) -> Result<vk::DescriptorSetLayout, RendererError> {
+        let bindings = layout.set_bindings(set);
         let layout_info = vk::DescriptorSetLayoutCreateInfo {
             binding_count: bindings.len() as u32,
             p_bindings: bindings.as_ptr(),
@@ -1381,20 +2679,21 @@ impl Renderer {
         Ok(descriptor_set_layout)
     }
 
-    fn create_model_descriptor_set_layout(
+    fn create_post_descriptor_set_layout(
         device: &Device,
-    ) -> Result<vk::DescriptorSetLayout, Box<dyn Error>> {
+    ) -> Result<vk::DescriptorSetLayout, RendererError> {
+        // The image and the sampler are separate so that the shaders compile with naga, see shader_compiler.
         let bindings = [
             vk::DescriptorSetLayoutBinding {
                 binding: 0,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_type: vk::DescriptorType::SAMPLED_IMAGE,
                 descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::VERTEX,
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
                 ..Default::default()
             },
             vk::DescriptorSetLayoutBinding {
                 binding: 1,
-                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_type: vk::DescriptorType::SAMPLER,
                 descriptor_count: 1,
                 stage_flags: vk::ShaderStageFlags::FRAGMENT,
                 ..Default::default()
@@ -1411,62 +2710,350 @@ impl Renderer {
         Ok(descriptor_set_layout)
     }
 
-    fn create_graphics_pipeline(
+    // The descriptor sets and push constants the scene pass shaders declare, which the pipelines of the
+    // scene materials have to stick to.
+    fn reflect_scene_layout() -> Result<LayoutReflection, RendererError> {
+        let mut shaders = vec![];
+        for path in SCENE_SHADERS {
+            let words = tools::read_shader(Path::new(path))?;
+            shaders.push((path, ShaderReflection::new(path, &words)?));
+        }
+        let layout = LayoutReflection::merge(&shaders)?;
+        layout.check_bindings(&SCENE_BINDINGS)?;
+        debug!("Scene shaders reflected.");
+
+        Ok(layout)
+    }
+
+    // Shared by every pipeline of the scene pass, see PipelineDesc.
+    fn create_pipeline_layout(
         device: &Device,
-        swapchain_extent: vk::Extent2D,
-        render_pass: vk::RenderPass,
         descriptor_set_layouts: &[vk::DescriptorSetLayout],
-    ) -> Result<(vk::PipelineLayout, vk::Pipeline), Box<dyn Error>> {
-        let vert_shader = tools::read_shader(Path::new("shaders/vert.spv"))?;
-        let vert_shader_module = Self::create_shader_module(device, &vert_shader)?;
-        let vert_shader_entrypoint = CString::new("main").unwrap();
-        let vert_shader_stage_info = vk::PipelineShaderStageCreateInfo {
-            stage: vk::ShaderStageFlags::VERTEX,
-            module: vert_shader_module,
-            p_name: vert_shader_entrypoint.as_ptr(),
+        layout: &LayoutReflection,
+    ) -> Result<vk::PipelineLayout, RendererError> {
+        // Only used by debug.frag, see record_command_buffer.
+        let push_constant_ranges = layout.push_constants.as_slice();
+        let size = push_constant_ranges.iter().map(|range| range.size).max();
+        if size.is_some_and(|size| size != std::mem::size_of::<DebugPushConstants>() as u32) {
+            Err("The push constants of the shaders don't match DebugPushConstants !")?
+        }
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo {
+            set_layout_count: descriptor_set_layouts.len() as u32,
+            p_set_layouts: descriptor_set_layouts.as_ptr(),
+            push_constant_range_count: push_constant_ranges.len() as u32,
+            p_push_constant_ranges: push_constant_ranges.as_ptr(),
             ..Default::default()
         };
-        #[cfg(debug_assertions)]
-        println!("Vertex shader loaded.");
 
-        let frag_shader = tools::read_shader(Path::new("shaders/frag.spv"))?;
-        let frag_shader_module = Self::create_shader_module(device, &frag_shader)?;
-        let frag_shader_entrypoint = CString::new("main").unwrap();
-        let frag_shader_stage_info = vk::PipelineShaderStageCreateInfo {
-            stage: vk::ShaderStageFlags::FRAGMENT,
-            module: frag_shader_module,
-            p_name: frag_shader_entrypoint.as_ptr(),
-            ..Default::default()
-        };
-        #[cfg(debug_assertions)]
-        println!("Fragment shader loaded.");
+        let pipeline_layout =
+            unsafe { device.create_pipeline_layout(&pipeline_layout_info, None) }?;
+        debug!("Pipeline layout created.");
+
+        Ok(pipeline_layout)
+    }
 
-        let shader_stages = [vert_shader_stage_info, frag_shader_stage_info];
+    // The viewport is dynamic, so the pipelines only have to be rebuilt when the render pass, the sample
+    // count or the depth direction change.
+    fn create_graphics_pipeline(&self, desc: &PipelineDesc) -> Result<vk::Pipeline, RendererError> {
+        if desc.polygon_mode != PolygonMode::Fill && !self.fill_mode_non_solid {
+            Err("Wireframes need the fillModeNonSolid device feature !")?
+        }
+
+        let vert_shader = tools::read_shader(Path::new(&desc.vert_shader))?;
+        let frag_shader = tools::read_shader(Path::new(&desc.frag_shader))?;
+        let vert_reflection = ShaderReflection::new(&desc.vert_shader, &vert_shader)?;
+        let frag_reflection = ShaderReflection::new(&desc.frag_shader, &frag_shader)?;
+        vert_reflection.check_layout(&desc.vert_shader, &self.scene_layout)?;
+        frag_reflection.check_layout(&desc.frag_shader, &self.scene_layout)?;
+        match desc.vertex_layout {
+            VertexLayout::Mesh => vert_reflection
+                .check_inputs(&desc.vert_shader, &Vertex::get_attribute_descriptions())?,
+            VertexLayout::Empty => vert_reflection.check_inputs(&desc.vert_shader, &[])?,
+        }
+        let vert_shader_module = Self::create_shader_module(
+            &self.device,
+            &self.debug_names,
+            &desc.vert_shader,
+            &vert_shader,
+        )?;
+        let frag_shader_module = Self::create_shader_module(
+            &self.device,
+            &self.debug_names,
+            &desc.frag_shader,
+            &frag_shader,
+        )?;
+        let entrypoint = CString::new("main").unwrap();
+        let shader_stages = [
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::VERTEX,
+                module: vert_shader_module,
+                p_name: entrypoint.as_ptr(),
+                ..Default::default()
+            },
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::FRAGMENT,
+                module: frag_shader_module,
+                p_name: entrypoint.as_ptr(),
+                ..Default::default()
+            },
+        ];
+        debug!("Shaders loaded.");
 
         let binding_description = Vertex::get_binding_description();
         let attribute_descriptions = Vertex::get_attribute_descriptions();
-        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
-            vertex_binding_description_count: 1,
-            vertex_attribute_description_count: attribute_descriptions.len() as u32,
-            p_vertex_binding_descriptions: &binding_description,
-            p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
-            ..Default::default()
+        let vertex_input_info = match desc.vertex_layout {
+            VertexLayout::Mesh => vk::PipelineVertexInputStateCreateInfo {
+                vertex_binding_description_count: 1,
+                vertex_attribute_description_count: attribute_descriptions.len() as u32,
+                p_vertex_binding_descriptions: &binding_description,
+                p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
+                ..Default::default()
+            },
+            VertexLayout::Empty => vk::PipelineVertexInputStateCreateInfo::default(),
         };
 
         let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            topology: desc.topology.vk(),
             primitive_restart_enable: vk::FALSE,
             ..Default::default()
         };
 
-        let viewport = vk::Viewport {
-            width: swapchain_extent.width as f32,
-            height: swapchain_extent.height as f32,
-            max_depth: 1f32,
+        let viewport_state = vk::PipelineViewportStateCreateInfo {
+            viewport_count: 1,
+            scissor_count: 1,
             ..Default::default()
         };
 
-        let scissor = vk::Rect2D {
+        // Lines drawn over faces are pulled by a bit more than the depth difference of their rasterization.
+        let depth_bias = if self.reversed_z { 1f32 } else { -1f32 };
+        let rasterizer = vk::PipelineRasterizationStateCreateInfo {
+            line_width: 1f32,
+            polygon_mode: desc.polygon_mode.vk(),
+            cull_mode: desc.cull_mode.vk(),
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_bias_enable: desc.depth_bias.into(),
+            depth_bias_constant_factor: if desc.depth_bias { depth_bias } else { 0f32 },
+            depth_bias_slope_factor: if desc.depth_bias { depth_bias } else { 0f32 },
+            ..Default::default()
+        };
+
+        // Sample shading also smooths the aliasing inside the triangles, from textures and specular
+        // highlights, at the cost of running the fragment shader more than once per pixel.
+        let multisampling = vk::PipelineMultisampleStateCreateInfo {
+            rasterization_samples: self.msaa_samples,
+            sample_shading_enable: self.sample_shading.into(),
+            min_sample_shading: if self.sample_shading { 0.2f32 } else { 1f32 },
+            ..Default::default()
+        };
+
+        let color_blend_attachment = desc.blend.vk();
+        let color_blending = vk::PipelineColorBlendStateCreateInfo {
+            logic_op: vk::LogicOp::COPY,
+            attachment_count: 1,
+            p_attachments: &color_blend_attachment,
+            ..Default::default()
+        };
+
+        // Geometry that doesn't write depth is usually drawn over what does, at the same depth.
+        let depth_compare_op = match (self.reversed_z, desc.depth_write) {
+            (false, true) => vk::CompareOp::LESS,
+            (false, false) => vk::CompareOp::LESS_OR_EQUAL,
+            (true, true) => vk::CompareOp::GREATER,
+            (true, false) => vk::CompareOp::GREATER_OR_EQUAL,
+        };
+        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo {
+            depth_test_enable: desc.depth_test.into(),
+            depth_write_enable: desc.depth_write.into(),
+            depth_compare_op,
+            depth_bounds_test_enable: vk::FALSE,
+            min_depth_bounds: 0.0,
+            max_depth_bounds: 0.0,
+            stencil_test_enable: vk::FALSE,
+            front: vk::StencilOpState::default(),
+            back: vk::StencilOpState::default(),
+            ..Default::default()
+        };
+
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo {
+            dynamic_state_count: dynamic_states.len() as u32,
+            p_dynamic_states: dynamic_states.as_ptr(),
+            ..Default::default()
+        };
+
+        let pipeline_infos = [vk::GraphicsPipelineCreateInfo {
+            stage_count: shader_stages.len() as u32,
+            p_stages: shader_stages.as_ptr(),
+            p_vertex_input_state: &vertex_input_info,
+            p_input_assembly_state: &input_assembly,
+            p_viewport_state: &viewport_state,
+            p_rasterization_state: &rasterizer,
+            p_multisample_state: &multisampling,
+            p_depth_stencil_state: &depth_stencil,
+            p_color_blend_state: &color_blending,
+            p_dynamic_state: &dynamic_state,
+            layout: self.pipeline_layout,
+            render_pass: self.render_pass,
+            base_pipeline_handle: vk::Pipeline::null(),
+            base_pipeline_index: -1,
+            ..Default::default()
+        }];
+
+        let result = unsafe {
+            self.device
+                .create_graphics_pipelines(self.pipeline_cache, &pipeline_infos, None)
+        };
+
+        unsafe { self.device.destroy_shader_module(frag_shader_module, None) };
+        unsafe { self.device.destroy_shader_module(vert_shader_module, None) };
+        debug!("Shaders dropped.");
+
+        let pipelines = result.map_err(|(_, err)| err)?;
+        debug!("Graphics pipeline created for {:?}.", desc);
+        self.debug_names.set(pipelines[0], &desc.label());
+
+        Ok(pipelines[0])
+    }
+
+    // The pipeline for `desc`, created the first time it is asked for.
+    fn pipeline(&mut self, desc: &PipelineDesc) -> Result<vk::Pipeline, RendererError> {
+        if let Some(&pipeline) = self.pipelines.get(desc) {
+            return Ok(pipeline);
+        }
+        let pipeline = self.create_graphics_pipeline(desc)?;
+        self.pipelines.insert(desc.clone(), pipeline);
+
+        Ok(pipeline)
+    }
+
+    // Starts from the data saved by a previous run, unless it was made by another driver or device.
+    fn create_pipeline_cache(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &Device,
+        path: Option<&Path>,
+    ) -> Result<vk::PipelineCache, RendererError> {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let data = match path.map(|path| (path, fs::read(path))) {
+            None => vec![],
+            // The first run, or the cache was deleted.
+            Some((path, Err(err))) if err.kind() == io::ErrorKind::NotFound => {
+                debug!("No pipeline cache at {}, starting empty.", path.display());
+                vec![]
+            }
+            Some((path, Err(err))) => {
+                warn!("Error reading pipeline cache {} : {}", path.display(), err);
+                vec![]
+            }
+            Some((path, Ok(data))) => {
+                // Header length and version, vendor ID, device ID and cache UUID.
+                let header =
+                    |i: usize| u32::from_ne_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+                if data.len() >= 32
+                    && header(4) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+                    && header(8) == properties.vendor_id
+                    && header(12) == properties.device_id
+                    && data[16..32] == properties.pipeline_cache_uuid
+                {
+                    data
+                } else {
+                    warn!(
+                        "Pipeline cache {} is corrupt or from another device or driver, starting empty.",
+                        path.display()
+                    );
+                    vec![]
+                }
+            }
+        };
+
+        let pipeline_cache_info = vk::PipelineCacheCreateInfo {
+            initial_data_size: data.len(),
+            p_initial_data: data.as_ptr() as *const c_void,
+            ..Default::default()
+        };
+        let pipeline_cache = unsafe { device.create_pipeline_cache(&pipeline_cache_info, None) }?;
+        debug!("Pipeline cache created.");
+
+        Ok(pipeline_cache)
+    }
+
+    fn save_pipeline_cache(&self) -> Result<(), RendererError> {
+        if let Some(path) = &self.pipeline_cache_path {
+            let data = unsafe { self.device.get_pipeline_cache_data(self.pipeline_cache) }?;
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, data)?;
+            debug!("Pipeline cache saved.");
+        }
+
+        Ok(())
+    }
+
+    // Dropped with the render pass they were made for, see pipeline.
+    fn cleanup_pipelines(&mut self) {
+        for (_, pipeline) in self.pipelines.drain() {
+            unsafe { self.device.destroy_pipeline(pipeline, None) };
+        }
+        debug!("Graphics pipelines dropped.");
+    }
+
+    // Fullscreen triangle without vertex input nor depth, see shaders/fullscreen.vert.
+    fn create_tonemap_pipeline(
+        device: &Device,
+        debug_names: &DebugNames,
+        swapchain_extent: vk::Extent2D,
+        render_pass: vk::RenderPass,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<(vk::PipelineLayout, vk::Pipeline), RendererError> {
+        let vert_shader = tools::read_shader(Path::new("shaders/fullscreen_vert.spv"))?;
+        let vert_shader_module = Self::create_shader_module(
+            device,
+            debug_names,
+            "shaders/fullscreen_vert.spv",
+            &vert_shader,
+        )?;
+        let frag_shader = tools::read_shader(Path::new("shaders/tonemap_frag.spv"))?;
+        let frag_shader_module = Self::create_shader_module(
+            device,
+            debug_names,
+            "shaders/tonemap_frag.spv",
+            &frag_shader,
+        )?;
+        let entrypoint = CString::new("main").unwrap();
+        let shader_stages = [
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::VERTEX,
+                module: vert_shader_module,
+                p_name: entrypoint.as_ptr(),
+                ..Default::default()
+            },
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::FRAGMENT,
+                module: frag_shader_module,
+                p_name: entrypoint.as_ptr(),
+                ..Default::default()
+            },
+        ];
+        debug!("Tonemap shaders loaded.");
+
+        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default();
+
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart_enable: vk::FALSE,
+            ..Default::default()
+        };
+
+        let viewport = vk::Viewport {
+            width: swapchain_extent.width as f32,
+            height: swapchain_extent.height as f32,
+            max_depth: 1f32,
+            ..Default::default()
+        };
+
+        let scissor = vk::Rect2D {
             extent: swapchain_extent,
             ..Default::default()
         };
@@ -1481,7 +3068,7 @@ impl Renderer {
 
         let rasterizer = vk::PipelineRasterizationStateCreateInfo {
             line_width: 1f32,
-            cull_mode: vk::CullModeFlags::BACK,
+            cull_mode: vk::CullModeFlags::NONE,
             front_face: vk::FrontFace::COUNTER_CLOCKWISE,
             ..Default::default()
         };
@@ -1493,12 +3080,7 @@ impl Renderer {
         };
 
         let color_blend_attachment = vk::PipelineColorBlendAttachmentState {
-            color_write_mask: vk::ColorComponentFlags::R
-                | vk::ColorComponentFlags::G
-                | vk::ColorComponentFlags::B
-                | vk::ColorComponentFlags::A,
-            src_color_blend_factor: vk::BlendFactor::ONE,
-            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
             ..Default::default()
         };
 
@@ -1509,40 +3091,26 @@ impl Renderer {
             ..Default::default()
         };
 
-        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::LINE_WIDTH];
-
-        let _dynamic_state = vk::PipelineDynamicStateCreateInfo {
-            dynamic_state_count: 2,
-            p_dynamic_states: dynamic_states.as_ptr(),
-            ..Default::default()
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: std::mem::size_of::<TonemapPushConstants>() as u32,
         };
 
         let pipeline_layout_info = vk::PipelineLayoutCreateInfo {
-            set_layout_count: descriptor_set_layouts.len() as u32,
-            p_set_layouts: descriptor_set_layouts.as_ptr(),
+            set_layout_count: 1,
+            p_set_layouts: &descriptor_set_layout,
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &push_constant_range,
             ..Default::default()
         };
 
         let pipeline_layout =
             unsafe { device.create_pipeline_layout(&pipeline_layout_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Pipeline layout created.");
-
-        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo {
-            depth_test_enable: vk::TRUE,
-            depth_write_enable: vk::TRUE,
-            depth_compare_op: vk::CompareOp::LESS,
-            depth_bounds_test_enable: vk::FALSE,
-            min_depth_bounds: 0.0,
-            max_depth_bounds: 0.0,
-            stencil_test_enable: vk::FALSE,
-            front: vk::StencilOpState::default(),
-            back: vk::StencilOpState::default(),
-            ..Default::default()
-        };
+        debug!("Tonemap pipeline layout created.");
 
         let pipeline_infos = [vk::GraphicsPipelineCreateInfo {
-            stage_count: 2,
+            stage_count: shader_stages.len() as u32,
             p_stages: shader_stages.as_ptr(),
             p_vertex_input_state: &vertex_input_info,
             p_input_assembly_state: &input_assembly,
@@ -1554,46 +3122,206 @@ impl Renderer {
             render_pass,
             base_pipeline_handle: vk::Pipeline::null(),
             base_pipeline_index: -1,
-            p_depth_stencil_state: &depth_stencil,
             ..Default::default()
         }];
 
-        let graphics_pipelines = unsafe {
-            device.create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
-        }
-        .expect("Error creating graphics pipeline !");
-        #[cfg(debug_assertions)]
-        println!("Graphics pipeline created.");
+        let pipelines =
+            unsafe { device.create_graphics_pipelines(pipeline_cache, &pipeline_infos, None) }
+                .map_err(|(_, err)| err)?;
+        debug!("Tonemap pipeline created.");
 
         unsafe { device.destroy_shader_module(frag_shader_module, None) };
-        #[cfg(debug_assertions)]
-        println!("Fragment shader dropped.");
+        unsafe { device.destroy_shader_module(vert_shader_module, None) };
+        debug!("Tonemap shaders dropped.");
+
+        Ok((pipeline_layout, pipelines[0]))
+    }
+
+    // One pipeline per PostShader, sharing a layout with the input image in set 0 and the optional
+    // second image in set 1. The viewport is dynamic since bloom draws at half resolution, so they
+    // don't depend on the swapchain.
+    fn create_post_pipeline_layout(
+        device: &Device,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> Result<vk::PipelineLayout, RendererError> {
+        let set_layouts = [descriptor_set_layout, descriptor_set_layout];
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: std::mem::size_of::<PostPushConstants>() as u32,
+        };
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo {
+            set_layout_count: set_layouts.len() as u32,
+            p_set_layouts: set_layouts.as_ptr(),
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &push_constant_range,
+            ..Default::default()
+        };
+        let pipeline_layout =
+            unsafe { device.create_pipeline_layout(&pipeline_layout_info, None) }?;
+        debug!("Post-process pipeline layout created.");
+
+        Ok(pipeline_layout)
+    }
+
+    // The pipelines of `shaders`, in the same order.
+    fn create_post_pipelines(
+        device: &Device,
+        debug_names: &DebugNames,
+        render_pass: vk::RenderPass,
+        pipeline_layout: vk::PipelineLayout,
+        pipeline_cache: vk::PipelineCache,
+        shaders: &[PostShader],
+    ) -> Result<Vec<vk::Pipeline>, RendererError> {
+        let vert_shader = tools::read_shader(Path::new("shaders/fullscreen_vert.spv"))?;
+        let vert_shader_module = Self::create_shader_module(
+            device,
+            debug_names,
+            "shaders/fullscreen_vert.spv",
+            &vert_shader,
+        )?;
+        let mut frag_shader_modules = vec![];
+        for shader in shaders.iter() {
+            let module = tools::read_shader(Path::new(shader.path())).and_then(|frag_shader| {
+                Self::create_shader_module(device, debug_names, shader.path(), &frag_shader)
+            });
+            match module {
+                Ok(module) => frag_shader_modules.push(module),
+                // Happens when reloading a broken shader, the modules created so far have to go.
+                Err(err) => {
+                    for &module in frag_shader_modules.iter().chain([&vert_shader_module]) {
+                        unsafe { device.destroy_shader_module(module, None) };
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        let entrypoint = CString::new("main").unwrap();
+        let shader_stages = frag_shader_modules
+            .iter()
+            .map(|&frag_shader_module| {
+                [
+                    vk::PipelineShaderStageCreateInfo {
+                        stage: vk::ShaderStageFlags::VERTEX,
+                        module: vert_shader_module,
+                        p_name: entrypoint.as_ptr(),
+                        ..Default::default()
+                    },
+                    vk::PipelineShaderStageCreateInfo {
+                        stage: vk::ShaderStageFlags::FRAGMENT,
+                        module: frag_shader_module,
+                        p_name: entrypoint.as_ptr(),
+                        ..Default::default()
+                    },
+                ]
+            })
+            .collect::<Vec<[vk::PipelineShaderStageCreateInfo; 2]>>();
+        debug!("Post-process shaders loaded.");
+
+        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default();
+
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart_enable: vk::FALSE,
+            ..Default::default()
+        };
+
+        let viewport_state = vk::PipelineViewportStateCreateInfo {
+            viewport_count: 1,
+            scissor_count: 1,
+            ..Default::default()
+        };
+
+        let rasterizer = vk::PipelineRasterizationStateCreateInfo {
+            line_width: 1f32,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            ..Default::default()
+        };
+
+        let multisampling = vk::PipelineMultisampleStateCreateInfo {
+            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            min_sample_shading: 1f32,
+            ..Default::default()
+        };
+
+        let color_blend_attachment = vk::PipelineColorBlendAttachmentState {
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+            ..Default::default()
+        };
+
+        let color_blending = vk::PipelineColorBlendStateCreateInfo {
+            logic_op: vk::LogicOp::COPY,
+            attachment_count: 1,
+            p_attachments: &color_blend_attachment,
+            ..Default::default()
+        };
 
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo {
+            dynamic_state_count: dynamic_states.len() as u32,
+            p_dynamic_states: dynamic_states.as_ptr(),
+            ..Default::default()
+        };
+
+        let pipeline_infos = shader_stages
+            .iter()
+            .map(|stages| vk::GraphicsPipelineCreateInfo {
+                stage_count: stages.len() as u32,
+                p_stages: stages.as_ptr(),
+                p_vertex_input_state: &vertex_input_info,
+                p_input_assembly_state: &input_assembly,
+                p_viewport_state: &viewport_state,
+                p_rasterization_state: &rasterizer,
+                p_multisample_state: &multisampling,
+                p_color_blend_state: &color_blending,
+                p_dynamic_state: &dynamic_state,
+                layout: pipeline_layout,
+                render_pass,
+                base_pipeline_handle: vk::Pipeline::null(),
+                base_pipeline_index: -1,
+                ..Default::default()
+            })
+            .collect::<Vec<vk::GraphicsPipelineCreateInfo>>();
+
+        let result =
+            unsafe { device.create_graphics_pipelines(pipeline_cache, &pipeline_infos, None) };
+
+        for frag_shader_module in frag_shader_modules {
+            unsafe { device.destroy_shader_module(frag_shader_module, None) };
+        }
         unsafe { device.destroy_shader_module(vert_shader_module, None) };
-        #[cfg(debug_assertions)]
-        println!("Vertex shader dropped.");
+        debug!("Post-process shaders dropped.");
+
+        let pipelines = result.map_err(|(pipelines, err)| {
+            for pipeline in pipelines {
+                unsafe { device.destroy_pipeline(pipeline, None) };
+            }
+            err
+        })?;
+        debug!("Post-process pipelines created.");
 
-        Ok((pipeline_layout, graphics_pipelines[0]))
+        Ok(pipelines)
     }
 
     fn query_swapchain_support(
         physical_device: vk::PhysicalDevice,
         surface_loader: &Surface,
         surface: vk::SurfaceKHR,
-    ) -> Result<SwapchainSupportDetails, Box<dyn Error>> {
+    ) -> Result<SwapchainSupportDetails, RendererError> {
         let capabilities = unsafe {
             surface_loader.get_physical_device_surface_capabilities(physical_device, surface)
         }
-        .expect("Error querying swapchain capabilities !");
+        .map_err(RendererError::swapchain)?;
 
         let formats =
             unsafe { surface_loader.get_physical_device_surface_formats(physical_device, surface) }
-                .expect("Error querying swapchain formats !");
+                .map_err(RendererError::swapchain)?;
 
         let present_modes = unsafe {
             surface_loader.get_physical_device_surface_present_modes(physical_device, surface)
         }
-        .expect("Error querying swapchain present modes !");
+        .map_err(RendererError::swapchain)?;
 
         Ok(SwapchainSupportDetails {
             capabilities,
@@ -1604,12 +3332,20 @@ impl Renderer {
 
     fn choose_swap_surface_format(
         available_formats: &[vk::SurfaceFormatKHR],
+        surface_formats: &[SurfaceFormat],
     ) -> vk::SurfaceFormatKHR {
-        for available_format in available_formats.iter() {
-            if available_format.format == vk::Format::B8G8R8A8_SRGB
-                && available_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-            {
-                return *available_format;
+        for surface_format in surface_formats.iter().chain([SurfaceFormat::Srgb].iter()) {
+            for candidate in surface_format.candidates() {
+                if available_formats.contains(candidate) {
+                    if surface_formats.first() != Some(surface_format) {
+                        warn!(
+                            "Surface format {:?} not supported, using {:?}.",
+                            surface_formats.first(),
+                            surface_format
+                        );
+                    }
+                    return *candidate;
+                }
             }
         }
         return available_formats[0];
@@ -1617,13 +3353,23 @@ impl Renderer {
 
     fn choose_swap_present_mode(
         available_present_modes: &[vk::PresentModeKHR],
-    ) -> vk::PresentModeKHR {
-        for available_present_mode in available_present_modes.iter() {
-            if *available_present_mode == vk::PresentModeKHR::MAILBOX {
-                return *available_present_mode;
+        present_mode: PresentMode,
+    ) -> PresentMode {
+        let mut chosen = present_mode;
+        while !available_present_modes.contains(&chosen.vk()) {
+            match chosen.fallback() {
+                Some(fallback) => chosen = fallback,
+                None => break,
             }
         }
-        return vk::PresentModeKHR::FIFO;
+        if chosen != present_mode {
+            warn!(
+                "Present mode {:?} not supported, using {:?}.",
+                present_mode, chosen
+            );
+        }
+
+        chosen
     }
 
     fn choose_swap_extent(
@@ -1656,9 +3402,9 @@ impl Renderer {
         device_queue_family_indices: &QueueFamilyIndices,
         width: u32,
         height: u32,
-    ) -> Result<(vk::SwapchainKHR, Vec<vk::Image>, vk::Format, vk::Extent2D), Box<dyn Error>> {
-        let surface_format = Self::choose_swap_surface_format(&swapchain_support_details.formats);
-        let present_mode = Self::choose_swap_present_mode(&swapchain_support_details.present_modes);
+        surface_format: vk::SurfaceFormatKHR,
+        present_mode: PresentMode,
+    ) -> Result<(vk::SwapchainKHR, Vec<vk::Image>, vk::Extent2D), RendererError> {
         let extent =
             Self::choose_swap_extent(&swapchain_support_details.capabilities, width, height);
         // Require at least one more image than the minimum to avoid waiting for the driver to complete its job.
@@ -1676,7 +3422,10 @@ impl Renderer {
             image_color_space: surface_format.color_space,
             image_extent: extent,
             image_array_layers: 1,
-            image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            // Transfers are only used for screenshots, which are not available when unsupported.
+            image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | (swapchain_support_details.capabilities.supported_usage_flags
+                    & vk::ImageUsageFlags::TRANSFER_SRC),
             image_sharing_mode: if device_queue_family_indices.graphics_family
                 != device_queue_family_indices.present_family
             {
@@ -1708,26 +3457,27 @@ impl Renderer {
             },
             pre_transform: swapchain_support_details.capabilities.current_transform,
             composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
-            present_mode,
+            present_mode: present_mode.vk(),
             clipped: vk::TRUE,
             old_swapchain: vk::SwapchainKHR::null(),
             ..Default::default()
         };
 
-        let swapchain = unsafe { swapchain_loader.create_swapchain(&swapchain_create_info, None) }?;
-        #[cfg(debug_assertions)]
-        println!("Swapchain created.");
+        let swapchain = unsafe { swapchain_loader.create_swapchain(&swapchain_create_info, None) }
+            .map_err(RendererError::swapchain)?;
+        debug!("Swapchain created.");
 
-        let swapchain_images = unsafe { swapchain_loader.get_swapchain_images(swapchain) }?;
+        let swapchain_images = unsafe { swapchain_loader.get_swapchain_images(swapchain) }
+            .map_err(RendererError::swapchain)?;
 
-        Ok((swapchain, swapchain_images, surface_format.format, extent))
+        Ok((swapchain, swapchain_images, extent))
     }
 
     fn create_image_views(
         device: &Device,
         swapchain_images: &[vk::Image],
         swapchain_image_format: vk::Format,
-    ) -> Result<Vec<vk::ImageView>, Box<dyn Error>> {
+    ) -> Result<Vec<vk::ImageView>, RendererError> {
         let swapchain_image_views = swapchain_images
             .iter()
             .map(|image| {
@@ -1740,8 +3490,7 @@ impl Renderer {
                 Ok(image_view)
             })
             .collect();
-        #[cfg(debug_assertions)]
-        println!("Swapchain image views created.");
+        debug!("Swapchain image views created.");
 
         swapchain_image_views
     }
@@ -1752,7 +3501,7 @@ impl Renderer {
         candidates: &[vk::Format],
         tiling: vk::ImageTiling,
         features: vk::FormatFeatureFlags,
-    ) -> Result<vk::Format, Box<dyn Error>> {
+    ) -> Result<vk::Format, RendererError> {
         for &format in candidates.iter() {
             let props =
                 unsafe { instance.get_physical_device_format_properties(physical_device, format) };
@@ -1771,7 +3520,7 @@ impl Renderer {
     fn find_depth_format(
         instance: &Instance,
         physical_device: vk::PhysicalDevice,
-    ) -> Result<vk::Format, Box<dyn Error>> {
+    ) -> Result<vk::Format, RendererError> {
         Self::find_supported_format(
             instance,
             physical_device,
@@ -1794,9 +3543,10 @@ impl Renderer {
         physical_device: vk::PhysicalDevice,
         device: &Device,
         swapchain_extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
         graphics_queue: vk::Queue,
         command_pool: vk::CommandPool,
-    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), Box<dyn Error>> {
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), RendererError> {
         let depth_format = Self::find_depth_format(instance, physical_device)?;
         let (depth_image, depth_image_memory) = Self::create_image(
             instance,
@@ -1804,6 +3554,8 @@ impl Renderer {
             device,
             swapchain_extent.width,
             swapchain_extent.height,
+            1,
+            samples,
             depth_format,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
@@ -1829,114 +3581,333 @@ impl Renderer {
         Ok((depth_image, depth_image_memory, depth_image_view))
     }
 
-    fn find_queue_families(
+    // The multisampled color image the scene is drawn into, resolved into the offscreen image at the end
+    // of the render pass. Never stored, so it may live in lazily allocated memory on tilers.
+    fn create_msaa_resources(
         instance: &Instance,
         physical_device: vk::PhysicalDevice,
-        surface_loader: &Surface,
-        surface: vk::SurfaceKHR,
-    ) -> Result<QueueFamilyIndices, Box<dyn Error>> {
-        // Vulkan commands are submitted in queues. There are multiple families of queues and each family allows certain commands.
-        // We need to find the indices of the queue families that allow the commands we need.
-        let device_queue_families_properties =
-            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
-        let mut device_queue_family_indices = QueueFamilyIndices::default();
-        let mut index = 0;
-        for device_queue_family_property in device_queue_families_properties.iter() {
-            if device_queue_family_property.queue_count > 0
-                && device_queue_family_property
-                    .queue_flags
-                    .contains(vk::QueueFlags::GRAPHICS)
-            {
-                device_queue_family_indices.graphics_family = Some(index);
-            }
-
-            if device_queue_family_property.queue_count > 0
-                && unsafe {
-                    surface_loader.get_physical_device_surface_support(
-                        physical_device,
-                        index,
-                        surface,
-                    )
-                }?
-            {
-                device_queue_family_indices.present_family = Some(index);
-            }
+        device: &Device,
+        swapchain_extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), RendererError> {
+        if samples == vk::SampleCountFlags::TYPE_1 {
+            return Ok((
+                vk::Image::null(),
+                vk::DeviceMemory::null(),
+                vk::ImageView::null(),
+            ));
+        }
 
-            if device_queue_family_indices.is_complete() {
-                break;
-            }
+        let (msaa_image, msaa_image_memory) = Self::create_image(
+            instance,
+            &physical_device,
+            device,
+            swapchain_extent.width,
+            swapchain_extent.height,
+            1,
+            samples,
+            HDR_FORMAT,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        let msaa_image_view =
+            Self::create_image_view(device, msaa_image, HDR_FORMAT, vk::ImageAspectFlags::COLOR)?;
+        debug!("Multisampled color image created.");
 
-            index += 1;
-        }
-        Ok(device_queue_family_indices)
+        Ok((msaa_image, msaa_image_memory, msaa_image_view))
     }
 
-    fn pick_physical_device(
+    // Highest sample count both color and depth framebuffers support, at most `requested`.
+    fn choose_sample_count(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        requested: u32,
+    ) -> vk::SampleCountFlags {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let counts = properties.limits.framebuffer_color_sample_counts
+            & properties.limits.framebuffer_depth_sample_counts;
+        [
+            vk::SampleCountFlags::TYPE_64,
+            vk::SampleCountFlags::TYPE_32,
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .find(|&count| count.as_raw() <= requested && counts.contains(count))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+    }
+
+    fn create_offscreen_resources(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &Device,
+        swapchain_extent: vk::Extent2D,
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), RendererError> {
+        let (offscreen_image, offscreen_image_memory) = Self::create_image(
+            instance,
+            &physical_device,
+            device,
+            swapchain_extent.width,
+            swapchain_extent.height,
+            1,
+            vk::SampleCountFlags::TYPE_1,
+            HDR_FORMAT,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        let offscreen_image_view = Self::create_image_view(
+            device,
+            offscreen_image,
+            HDR_FORMAT,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+        debug!("Offscreen image created.");
+
+        Ok((
+            offscreen_image,
+            offscreen_image_memory,
+            offscreen_image_view,
+        ))
+    }
+
+    // See POST_TARGETS for what each one is used for.
+    fn create_post_targets(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &Device,
+        swapchain_extent: vk::Extent2D,
+        render_pass: vk::RenderPass,
+    ) -> Result<Vec<PostTarget>, RendererError> {
+        let half_extent = vk::Extent2D {
+            width: (swapchain_extent.width / 2).max(1),
+            height: (swapchain_extent.height / 2).max(1),
+        };
+        let post_targets = (0..POST_TARGETS)
+            .map(|i| {
+                let extent = if BLOOM_TARGETS.contains(&i) {
+                    half_extent
+                } else {
+                    swapchain_extent
+                };
+                let (image, memory) = Self::create_image(
+                    instance,
+                    &physical_device,
+                    device,
+                    extent.width,
+                    extent.height,
+                    1,
+                    vk::SampleCountFlags::TYPE_1,
+                    HDR_FORMAT,
+                    vk::ImageTiling::OPTIMAL,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                )?;
+                let view = Self::create_image_view(
+                    device,
+                    image,
+                    HDR_FORMAT,
+                    vk::ImageAspectFlags::COLOR,
+                )?;
+                let framebuffer_info = vk::FramebufferCreateInfo {
+                    render_pass,
+                    attachment_count: 1,
+                    p_attachments: &view,
+                    width: extent.width,
+                    height: extent.height,
+                    layers: 1,
+                    ..Default::default()
+                };
+                let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None) }?;
+
+                Ok(PostTarget {
+                    image,
+                    memory,
+                    view,
+                    framebuffer,
+                    extent,
+                })
+            })
+            .collect::<Result<Vec<PostTarget>, RendererError>>()?;
+        debug!("Post-process targets created.");
+
+        Ok(post_targets)
+    }
+
+    fn cleanup_post_target(&self, target: &PostTarget) {
+        unsafe { self.device.destroy_framebuffer(target.framebuffer, None) };
+        unsafe { self.device.destroy_image_view(target.view, None) };
+        unsafe { self.device.destroy_image(target.image, None) };
+        unsafe { self.device.free_memory(target.memory, None) };
+        debug!("Post-process target dropped.");
+    }
+
+    fn find_queue_families(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        surface_loader: &Surface,
+        surface: vk::SurfaceKHR,
+    ) -> Result<QueueFamilyIndices, RendererError> {
+        // Vulkan commands are submitted in queues. There are multiple families of queues and each family allows certain commands.
+        // We need to find the indices of the queue families that allow the commands we need.
+        let device_queue_families_properties =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let mut device_queue_family_indices = QueueFamilyIndices::default();
+        let mut index = 0;
+        for device_queue_family_property in device_queue_families_properties.iter() {
+            if device_queue_family_property.queue_count > 0
+                && device_queue_family_property
+                    .queue_flags
+                    .contains(vk::QueueFlags::GRAPHICS)
+            {
+                device_queue_family_indices.graphics_family = Some(index);
+            }
+
+            if device_queue_family_property.queue_count > 0
+                && unsafe {
+                    surface_loader.get_physical_device_surface_support(
+                        physical_device,
+                        index,
+                        surface,
+                    )
+                }?
+            {
+                device_queue_family_indices.present_family = Some(index);
+            }
+
+            if device_queue_family_indices.is_complete() {
+                break;
+            }
+
+            index += 1;
+        }
+        Ok(device_queue_family_indices)
+    }
+
+    // Runs every check a device has to pass to be used, the queue families and swapchain support are
+    // only meaningful when the device is suitable.
+    fn check_physical_device(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        index: usize,
+        surface_loader: &Surface,
+        surface: vk::SurfaceKHR,
+    ) -> Result<(DeviceInfo, QueueFamilyIndices, SwapchainSupportDetails), RendererError> {
+        let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let device_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let device_queue_family_indices =
+            Self::find_queue_families(instance, physical_device, surface_loader, surface)?;
+        let swapchain_support_details =
+            Self::query_swapchain_support(physical_device, surface_loader, surface)?;
+
+        let name = unsafe {
+            CStr::from_ptr(device_properties.device_name.as_ptr())
+                .to_str()
+                .to_owned()
+        }?
+        .to_owned();
+        let checks = vec![
+            (
+                "Discrete GPU",
+                device_properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU,
+            ),
+            (
+                "Geometry shader",
+                device_features.geometry_shader == vk::TRUE,
+            ),
+            (
+                "Graphics and present queues",
+                device_queue_family_indices.is_complete(),
+            ),
+            (
+                "Device extensions",
+                Self::check_device_extensions_support(
+                    instance,
+                    physical_device,
+                    &DEVICE_EXTENSIONS,
+                )?,
+            ),
+            (
+                "Sampler anisotropy",
+                device_features.sampler_anisotropy == vk::TRUE,
+            ),
+            (
+                "Surface formats and present modes",
+                !swapchain_support_details.formats.is_empty()
+                    && !swapchain_support_details.present_modes.is_empty(),
+            ),
+        ];
+
+        Ok((
+            DeviceInfo {
+                index,
+                name,
+                device_type: device_properties.device_type,
+                checks,
+            },
+            device_queue_family_indices,
+            swapchain_support_details,
+        ))
+    }
+
+    fn pick_physical_device(
         instance: &Instance,
         surface_loader: &Surface,
         surface: vk::SurfaceKHR,
+        selected_device: Option<usize>,
     ) -> Result<
         (
             vk::PhysicalDevice,
             QueueFamilyIndices,
             SwapchainSupportDetails,
         ),
-        Box<dyn Error>,
+        RendererError,
     > {
         let physical_devices = unsafe { instance.enumerate_physical_devices() }?;
 
-        for &physical_device in physical_devices.iter() {
-            let device_properties =
-                unsafe { instance.get_physical_device_properties(physical_device) };
-            let device_features = unsafe { instance.get_physical_device_features(physical_device) };
-            let device_queue_family_indices =
-                Self::find_queue_families(instance, physical_device, surface_loader, surface)?;
-
-            if device_properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU
-                && device_features.geometry_shader == vk::TRUE
-                && device_queue_family_indices.is_complete()
-                && Self::check_device_extensions_support(
+        // Kept to tell why no device was picked.
+        let mut devices = vec![];
+        for (index, &physical_device) in physical_devices.iter().enumerate() {
+            if selected_device.is_some() && selected_device != Some(index) {
+                continue;
+            }
+
+            let (device_info, device_queue_family_indices, swapchain_support_details) =
+                Self::check_physical_device(
                     instance,
                     physical_device,
-                    &DEVICE_EXTENSIONS,
-                )?
-                && device_features.sampler_anisotropy == vk::TRUE
-            {
-                let swapchain_support_details =
-                    Self::query_swapchain_support(physical_device, surface_loader, surface)?;
+                    index,
+                    surface_loader,
+                    surface,
+                )?;
 
-                if !swapchain_support_details.formats.is_empty()
-                    && !swapchain_support_details.present_modes.is_empty()
-                {
-                    #[cfg(debug_assertions)]
-                    {
-                        let device_name = unsafe {
-                            CStr::from_ptr(device_properties.device_name.as_ptr())
-                                .to_str()
-                                .to_owned()
-                        }?;
-
-                        println!("Found suitable device : {} !", device_name);
-                    }
+            if device_info.is_suitable() {
+                debug!("Found suitable device : {} !", device_info.name);
 
-                    return Ok((
-                        physical_device,
-                        device_queue_family_indices,
-                        swapchain_support_details,
-                    ));
-                }
+                return Ok((
+                    physical_device,
+                    device_queue_family_indices,
+                    swapchain_support_details,
+                ));
             }
+            devices.push(device_info);
         }
 
-        Err("No suitable device found !")?
+        Err(RendererError::NoSuitableDevice {
+            selected: selected_device,
+            devices,
+        })
     }
 
     fn create_logical_device(
-        #[cfg(debug_assertions)] enable_validation_layers: bool,
+        enable_validation_layers: bool,
         instance: &Instance,
         physical_device: vk::PhysicalDevice,
         device_queue_family_indices: &QueueFamilyIndices,
-    ) -> Result<Device, Box<dyn Error>> {
+    ) -> Result<Device, RendererError> {
         let queue_priority = 1f32;
         let device_queue_create_info = vk::DeviceQueueCreateInfo {
             queue_family_index: device_queue_family_indices
@@ -1946,12 +3917,18 @@ impl Renderer {
             p_queue_priorities: &queue_priority,
             ..Default::default()
         };
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
         let device_features = vk::PhysicalDeviceFeatures {
             sampler_anisotropy: vk::TRUE,
+            // Optional, see RendererConfig::sample_shading.
+            sample_rate_shading: supported_features.sample_rate_shading,
+            // Optional, for the wireframe render modes.
+            fill_mode_non_solid: supported_features.fill_mode_non_solid,
+            // Optional, see FrameStats.
+            pipeline_statistics_query: supported_features.pipeline_statistics_query,
             ..Default::default()
         };
 
-        let device_create_info;
         let enabled_extension_names = DEVICE_EXTENSIONS
             .iter()
             .map(|e| CString::new(*e).unwrap())
@@ -1961,89 +3938,53 @@ impl Renderer {
             .map(|e| e.as_ptr())
             .collect::<Vec<*const i8>>();
 
-        #[cfg(debug_assertions)]
-        {
-            if enable_validation_layers {
-                let enabled_layer_names = VALIDATION_LAYERS
-                    .iter()
-                    .map(|l| CString::new(*l).unwrap())
-                    .collect::<Vec<CString>>();
-                let p_enabled_layer_names = enabled_layer_names
-                    .iter()
-                    .map(|l| l.as_ptr())
-                    .collect::<Vec<*const i8>>();
-
-                device_create_info = vk::DeviceCreateInfo {
-                    p_queue_create_infos: &device_queue_create_info,
-                    queue_create_info_count: 1,
-                    p_enabled_features: &device_features,
-                    enabled_extension_count: p_enabled_extension_names.len() as u32,
-                    pp_enabled_extension_names: p_enabled_extension_names.as_ptr(),
-                    enabled_layer_count: p_enabled_layer_names.len() as u32,
-                    pp_enabled_layer_names: p_enabled_layer_names.as_ptr(),
-                    ..Default::default()
-                };
-
-                let device = unsafe { instance.create_device(physical_device, &device_create_info, None) }?;
-                #[cfg(debug_assertions)]
-                println!("Logical device created.");
-
-                Ok(device)
+        // Only for older implementations, device layers are the instance ones nowadays.
+        let enabled_layer_names = VALIDATION_LAYERS
+            .iter()
+            .map(|l| CString::new(*l).unwrap())
+            .collect::<Vec<CString>>();
+        let p_enabled_layer_names = enabled_layer_names
+            .iter()
+            .map(|l| l.as_ptr())
+            .collect::<Vec<*const i8>>();
+        let device_create_info = vk::DeviceCreateInfo {
+            p_queue_create_infos: &device_queue_create_info,
+            queue_create_info_count: 1,
+            p_enabled_features: &device_features,
+            enabled_extension_count: p_enabled_extension_names.len() as u32,
+            pp_enabled_extension_names: p_enabled_extension_names.as_ptr(),
+            enabled_layer_count: if enable_validation_layers {
+                p_enabled_layer_names.len() as u32
             } else {
-                device_create_info = vk::DeviceCreateInfo {
-                    p_queue_create_infos: &device_queue_create_info,
-                    queue_create_info_count: 1,
-                    p_enabled_features: &device_features,
-                    enabled_extension_count: p_enabled_extension_names.len() as u32,
-                    pp_enabled_extension_names: p_enabled_extension_names.as_ptr(),
-                    enabled_layer_count: 0,
-                    ..Default::default()
-                };
-
-                let device = unsafe { instance.create_device(physical_device, &device_create_info, None) }?;
-                #[cfg(debug_assertions)]
-                println!("Logical device created.");
-
-                Ok(device)
-            }
-        }
-
-        #[cfg(not(debug_assertions))]
-        {
-            device_create_info = vk::DeviceCreateInfo {
-                p_queue_create_infos: &device_queue_create_info,
-                queue_create_info_count: 1,
-                p_enabled_features: &device_features,
-                enabled_extension_count: p_enabled_extension_names.len() as u32,
-                pp_enabled_extension_names: p_enabled_extension_names.as_ptr(),
-                enabled_layer_count: 0,
-                ..Default::default()
-            };
-
+                0
+            },
+            pp_enabled_layer_names: p_enabled_layer_names.as_ptr(),
+            ..Default::default()
+        };
 
-            let device = unsafe { instance.create_device(physical_device, &device_create_info, None) }?;
+        let device = unsafe { instance.create_device(physical_device, &device_create_info, None) }?;
+        debug!("Logical device created.");
 
-            Ok(device)
-        }
+        Ok(device)
     }
 
-    #[cfg(debug_assertions)]
     fn check_validation_layers_support(
         entry: &Entry,
         layer_names: &[&str],
-    ) -> Result<bool, Box<dyn Error>> {
+    ) -> Result<(), RendererError> {
         let available_layers_properties = entry.enumerate_instance_layer_properties()?;
 
-        println!("Available Vulkan layers :");
+        debug!("Available Vulkan layers :");
         for layer in available_layers_properties.iter() {
             let layer_name = unsafe {
                 CStr::from_ptr(layer.layer_name.as_ptr())
                     .to_str()
                     .to_owned()
             }?;
-            println!("{}", layer_name);
+            debug!("    {}", layer_name);
         }
 
+        let mut missing_layers = vec![];
         for layer_name in layer_names.iter() {
             let mut layer_is_available = false;
             for available_layer in available_layers_properties.iter() {
@@ -2058,29 +3999,47 @@ impl Renderer {
                 }
             }
             if !layer_is_available {
-                return Ok(false);
+                missing_layers.push(layer_name.to_string());
             }
         }
+        if !missing_layers.is_empty() {
+            Err(RendererError::MissingValidationLayers(missing_layers))?
+        }
 
-        Ok(true)
+        Ok(())
+    }
+
+    // Extensions of the implementation, or of a layer when given.
+    fn check_instance_extension_support(
+        entry: &Entry,
+        layer_name: Option<&CStr>,
+        extension_name: &CStr,
+    ) -> Result<bool, RendererError> {
+        let available_extensions_properties =
+            entry.enumerate_instance_extension_properties(layer_name)?;
+
+        Ok(available_extensions_properties.iter().any(|extension| {
+            let name = unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) };
+            name == extension_name
+        }))
     }
 
     fn check_device_extensions_support(
         instance: &Instance,
         physical_device: vk::PhysicalDevice,
         extension_names: &[&str],
-    ) -> Result<bool, Box<dyn Error>> {
+    ) -> Result<bool, RendererError> {
         let available_extensions_properties =
             unsafe { instance.enumerate_device_extension_properties(physical_device) }?;
 
-        println!("Available Vulkan extensions :");
+        debug!("Available Vulkan extensions :");
         for extension in available_extensions_properties.iter() {
             let extension_name = unsafe {
                 CStr::from_ptr(extension.extension_name.as_ptr())
                     .to_str()
                     .to_owned()
             }?;
-            println!("{}", extension_name);
+            debug!("    {}", extension_name);
         }
 
         for extension_name in extension_names.iter() {
@@ -2104,26 +4063,13 @@ impl Renderer {
         Ok(true)
     }
 
-    #[cfg(debug_assertions)]
-    fn new_debug_utils_messenger_create_info() -> vk::DebugUtilsMessengerCreateInfoEXT {
-        vk::DebugUtilsMessengerCreateInfoEXT {
-            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
-            message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-            pfn_user_callback: Some(vk_debug_utils_callback),
-            ..Default::default()
-        }
-    }
-
     pub fn new(
         display_handle: raw_window_handle::RawDisplayHandle,
         window_handle: raw_window_handle::RawWindowHandle,
         width: u32,
         height: u32,
-    ) -> Result<Self, Box<dyn Error>> {
+        config: &RendererConfig,
+    ) -> Result<Self, RendererError> {
         // Init Vulkan
         // Ash loads Vulkan dynamically, ash::Entry is the library loader and the entrypoint into the Vulkan API.
         let entry = Entry::linked();
@@ -2133,101 +4079,129 @@ impl Renderer {
             ..Default::default()
         };
 
-        let instance;
-        #[cfg(debug_assertions)]
-        let debug_utils;
-        #[cfg(debug_assertions)]
-        let enable_validation_layers =
-            Self::check_validation_layers_support(&entry, &VALIDATION_LAYERS)?;
-
-        let required_extensions = ash_window::enumerate_required_extensions(display_handle)?;
-
-        #[cfg(debug_assertions)]
-        {
-            if enable_validation_layers {
-                println!("Validation layers available.");
-
-                let mut enabled_extension_names = required_extensions.to_vec();
-                enabled_extension_names.push(DebugUtils::name().as_ptr());
-
-                let enabled_layer_names = VALIDATION_LAYERS
-                    .iter()
-                    .map(|l| CString::new(*l).unwrap())
-                    .collect::<Vec<CString>>();
-                let p_enabled_layer_names = enabled_layer_names
-                    .iter()
-                    .map(|l| l.as_ptr())
-                    .collect::<Vec<*const i8>>();
-
-                let instance_debug_utils_messenger_create_info =
-                    Self::new_debug_utils_messenger_create_info();
-
-                let create_info = vk::InstanceCreateInfo {
-                    p_next: &instance_debug_utils_messenger_create_info
-                        as *const vk::DebugUtilsMessengerCreateInfoEXT
-                        as *const c_void,
-                    p_application_info: &app_info,
-                    enabled_layer_count: p_enabled_layer_names.len() as u32,
-                    pp_enabled_layer_names: p_enabled_layer_names.as_ptr(),
-                    enabled_extension_count: enabled_extension_names.len() as u32,
-                    pp_enabled_extension_names: enabled_extension_names.as_ptr(),
-                    ..Default::default()
-                };
-
-                instance = unsafe { entry.create_instance(&create_info, None) }?;
-                println!("Vulkan instance created.");
-
-                let debug_utils_loader = DebugUtils::new(&entry, &instance);
-                let messenger_create_info = Self::new_debug_utils_messenger_create_info();
-                let debug_utils_messenger = unsafe {
-                    debug_utils_loader.create_debug_utils_messenger(&messenger_create_info, None)
-                }?;
-                println!("Debug messenger created.");
-
-                debug_utils = Some((debug_utils_loader, debug_utils_messenger));
-            } else {
-                println!("Validation layers not available.");
-
-                let create_info = vk::InstanceCreateInfo {
-                    p_application_info: &app_info,
-                    enabled_extension_count: required_extensions.len() as u32,
-                    pp_enabled_extension_names: required_extensions.as_ptr(),
-                    ..Default::default()
-                };
-                instance = unsafe { entry.create_instance(&create_info, None) }?;
-                println!("Vulkan instance created.");
+        let enable_validation_layers = config.validation
+            && match Self::check_validation_layers_support(&entry, &VALIDATION_LAYERS) {
+                Ok(()) => true,
+                // Only a help while developing, the renderer works without them.
+                Err(err @ RendererError::MissingValidationLayers(_)) => {
+                    warn!("{}", err);
+                    false
+                }
+                Err(err) => return Err(err),
+            };
+        let validation = Validation::new(config);
+
+        let mut enabled_extension_names = ash_window::enumerate_required_extensions(display_handle)
+            .map_err(RendererError::Instance)?
+            .to_vec();
+        // Needed for the surface to report anything other than sRGB color spaces.
+        if Self::check_instance_extension_support(
+            &entry,
+            None,
+            vk::ExtSwapchainColorspaceFn::name(),
+        )? {
+            enabled_extension_names.push(vk::ExtSwapchainColorspaceFn::name().as_ptr());
+        }
+        // Also used without validation, for the object names and labels capture tools show.
+        let enable_debug_utils = enable_validation_layers
+            || Self::check_instance_extension_support(&entry, None, DebugUtils::name())?;
+        if enable_debug_utils {
+            enabled_extension_names.push(DebugUtils::name().as_ptr());
+        }
 
-                debug_utils = None;
+        let enabled_layer_names = VALIDATION_LAYERS
+            .iter()
+            .map(|l| CString::new(*l).unwrap())
+            .collect::<Vec<CString>>();
+        let p_enabled_layer_names = enabled_layer_names
+            .iter()
+            .map(|l| l.as_ptr())
+            .collect::<Vec<*const i8>>();
+        // Also reports on the creation and destruction of the instance.
+        let instance_debug_utils_messenger_create_info = validation.messenger_create_info();
+        let validation_feature_enables = config
+            .validation_features
+            .iter()
+            .flat_map(|feature| feature.vk().iter().copied())
+            .collect::<Vec<vk::ValidationFeatureEnableEXT>>();
+        let validation_features = vk::ValidationFeaturesEXT {
+            p_next: &instance_debug_utils_messenger_create_info
+                as *const vk::DebugUtilsMessengerCreateInfoEXT as *const c_void,
+            enabled_validation_feature_count: validation_feature_enables.len() as u32,
+            p_enabled_validation_features: validation_feature_enables.as_ptr(),
+            ..Default::default()
+        };
+        let mut create_info = vk::InstanceCreateInfo {
+            p_application_info: &app_info,
+            ..Default::default()
+        };
+        if enable_validation_layers {
+            info!("Validation layers enabled.");
+
+            create_info.enabled_layer_count = p_enabled_layer_names.len() as u32;
+            create_info.pp_enabled_layer_names = p_enabled_layer_names.as_ptr();
+            create_info.p_next = &instance_debug_utils_messenger_create_info
+                as *const vk::DebugUtilsMessengerCreateInfoEXT
+                as *const c_void;
+
+            if !validation_feature_enables.is_empty() {
+                // Provided by the validation layer itself.
+                let layer_name = CString::new(VALIDATION_LAYERS[0]).unwrap();
+                if Self::check_instance_extension_support(
+                    &entry,
+                    Some(&layer_name),
+                    vk::ExtValidationFeaturesFn::name(),
+                )? {
+                    enabled_extension_names.push(vk::ExtValidationFeaturesFn::name().as_ptr());
+                    create_info.p_next =
+                        &validation_features as *const vk::ValidationFeaturesEXT as *const c_void;
+                    info!(
+                        "Validation features {:?} enabled.",
+                        config.validation_features
+                    );
+                } else {
+                    warn!("Validation features not supported by the validation layer.");
+                }
             }
+        } else {
+            info!("Validation layers disabled or not available.");
         }
+        create_info.enabled_extension_count = enabled_extension_names.len() as u32;
+        create_info.pp_enabled_extension_names = enabled_extension_names.as_ptr();
+
+        let instance = unsafe { entry.create_instance(&create_info, None) }
+            .map_err(RendererError::Instance)?;
+        debug!("Vulkan instance created.");
+
+        let debug_utils = enable_debug_utils.then(|| DebugUtils::new(&entry, &instance));
+        let debug_messenger = match &debug_utils {
+            Some(debug_utils) if enable_validation_layers => {
+                let messenger_create_info = validation.messenger_create_info();
+                let debug_messenger = unsafe {
+                    debug_utils.create_debug_utils_messenger(&messenger_create_info, None)
+                }?;
+                debug!("Debug messenger created.");
 
-        #[cfg(not(debug_assertions))]
-        {
-            let create_info = vk::InstanceCreateInfo {
-                p_application_info: &app_info,
-                enabled_extension_count: required_extensions.len() as u32,
-                pp_enabled_extension_names: required_extensions.as_ptr(),
-                ..Default::default()
-            };
-            instance = unsafe { entry.create_instance(&create_info, None) }?;
-        }
+                Some(debug_messenger)
+            }
+            _ => None,
+        };
 
         let surface_loader = Surface::new(&entry, &instance);
         let surface = unsafe {
             ash_window::create_surface(&entry, &instance, display_handle, window_handle, None)
         }?;
-        #[cfg(debug_assertions)]
-        println!("Window surface created.");
+        debug!("Window surface created.");
 
         let (physical_device, queue_family_indices, swapchain_support_details) =
-            Self::pick_physical_device(&instance, &surface_loader, surface)?;
+            Self::pick_physical_device(&instance, &surface_loader, surface, config.device)?;
         let device = Self::create_logical_device(
-            #[cfg(debug_assertions)]
             enable_validation_layers,
             &instance,
             physical_device,
             &queue_family_indices,
         )?;
+        let debug_names = DebugNames::new(debug_utils.clone(), device.handle());
 
         let graphics_queue = unsafe {
             device.get_device_queue(
@@ -2237,45 +4211,69 @@ impl Renderer {
                 0,
             )
         };
-        #[cfg(debug_assertions)]
-        println!("Graphics queue handle retrieved.");
+        debug!("Graphics queue handle retrieved.");
 
-        let present_queue = unsafe {
-            device.get_device_queue(
-                queue_family_indices
-                    .present_family
-                    .expect("Missing present queue family index !"),
-                0,
-            )
-        };
-        #[cfg(debug_assertions)]
-        println!("Present queue handle retrieved.");
+        let present_family = queue_family_indices
+            .present_family
+            .expect("Missing present queue family index !");
+        let present_queue = unsafe { device.get_device_queue(present_family, 0) };
+        debug!("Present queue handle retrieved.");
 
         let swapchain_loader = Swapchain::new(&instance, &device);
-        let (swapchain, swapchain_images, swapchain_image_format, swapchain_extent) =
-            Self::create_swapchain(
-                &swapchain_loader,
-                &surface,
-                &swapchain_support_details,
-                &queue_family_indices,
-                width,
-                height,
-            )?;
+        let active_present_mode = Self::choose_swap_present_mode(
+            &swapchain_support_details.present_modes,
+            config.present_mode,
+        );
+        let surface_format = Self::choose_swap_surface_format(
+            &swapchain_support_details.formats,
+            &config.surface_formats,
+        );
+        let (swapchain, swapchain_images, swapchain_extent) = Self::create_swapchain(
+            &swapchain_loader,
+            &surface,
+            &swapchain_support_details,
+            &queue_family_indices,
+            width,
+            height,
+            surface_format,
+            active_present_mode,
+        )?;
 
         let swapchain_image_views =
-            Self::create_image_views(&device, &swapchain_images, swapchain_image_format)?;
-
-        let render_pass =
-            Self::create_render_pass(&device, &instance, physical_device, swapchain_image_format)?;
+            Self::create_image_views(&device, &swapchain_images, surface_format.format)?;
+
+        let msaa_samples =
+            Self::choose_sample_count(&instance, physical_device, config.msaa_samples);
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let sample_shading =
+            config.sample_shading && supported_features.sample_rate_shading == vk::TRUE;
+        let fill_mode_non_solid = supported_features.fill_mode_non_solid == vk::TRUE;
+        let render_pass = Self::create_render_pass(
+            &device,
+            &instance,
+            physical_device,
+            HDR_FORMAT,
+            msaa_samples,
+        )?;
+        let tonemap_render_pass = Self::create_tonemap_render_pass(&device, surface_format.format)?;
 
-        let global_descriptor_set_layout = Self::create_global_descriptor_set_layout(&device)?;
-        let model_descriptor_set_layout = Self::create_model_descriptor_set_layout(&device)?;
+        let scene_layout = Self::reflect_scene_layout()?;
+        let global_descriptor_set_layout =
+            Self::create_scene_descriptor_set_layout(&device, &scene_layout, 0)?;
+        let model_descriptor_set_layout =
+            Self::create_scene_descriptor_set_layout(&device, &scene_layout, 1)?;
+        let post_descriptor_set_layout = Self::create_post_descriptor_set_layout(&device)?;
 
-        let (pipeline_layout, graphics_pipeline) = Self::create_graphics_pipeline(
+        let pipeline_layout = Self::create_pipeline_layout(
             &device,
-            swapchain_extent,
-            render_pass,
             &[global_descriptor_set_layout, model_descriptor_set_layout],
+            &scene_layout,
+        )?;
+        let pipeline_cache = Self::create_pipeline_cache(
+            &instance,
+            physical_device,
+            &device,
+            config.pipeline_cache.as_deref(),
         )?;
 
         let command_pool = Self::create_command_pool(&device, &queue_family_indices)?;
@@ -2285,16 +4283,49 @@ impl Renderer {
             physical_device,
             &device,
             swapchain_extent,
+            msaa_samples,
             graphics_queue,
             command_pool,
         )?;
+        let (msaa_image, msaa_image_memory, msaa_image_view) = Self::create_msaa_resources(
+            &instance,
+            physical_device,
+            &device,
+            swapchain_extent,
+            msaa_samples,
+        )?;
+
+        let (offscreen_image, offscreen_image_memory, offscreen_image_view) =
+            Self::create_offscreen_resources(
+                &instance,
+                physical_device,
+                &device,
+                swapchain_extent,
+            )?;
+        let post_sampler = Self::create_post_sampler(&device)?;
 
+        let offscreen_framebuffer = Self::create_offscreen_framebuffer(
+            &device,
+            offscreen_image_view,
+            msaa_image_view,
+            depth_image_view,
+            swapchain_extent,
+            render_pass,
+        )?;
         let swapchain_framebuffers = Self::create_framebuffers(
             &device,
             &swapchain_image_views,
             swapchain_extent,
-            render_pass,
-            &depth_image_view,
+            tonemap_render_pass,
+        )?;
+
+        let (tonemap_pipeline_layout, tonemap_pipeline) = Self::create_tonemap_pipeline(
+            &device,
+            &debug_names,
+            swapchain_extent,
+            tonemap_render_pass,
+            post_descriptor_set_layout,
+            pipeline_cache,
         )?;
 
         let texture_sampler = Self::create_texture_sampler(&instance, physical_device, &device)?;
@@ -2310,35 +4341,103 @@ impl Renderer {
             global_descriptor_set_layout,
             &global_uniform_buffers,
         )?;
+        let post_descriptor_pool = Self::create_post_descriptor_pool(&device)?;
+        let offscreen_descriptor_set = Self::allocate_post_descriptor_set(
+            &device,
+            post_descriptor_pool,
+            post_descriptor_set_layout,
+        )?;
+        Self::update_post_descriptor_set(
+            &device,
+            offscreen_descriptor_set,
+            offscreen_image_view,
+            post_sampler,
+        );
+        let post_render_pass = Self::create_post_render_pass(&device)?;
+        let post_pipeline_layout =
+            Self::create_post_pipeline_layout(&device, post_descriptor_set_layout)?;
+        let post_pipelines = Self::create_post_pipelines(
+            &device,
+            &debug_names,
+            post_render_pass,
+            post_pipeline_layout,
+            pipeline_cache,
+            &PostShader::ALL,
+        )?;
+        let post_targets = Self::create_post_targets(
+            &instance,
+            physical_device,
+            &device,
+            swapchain_extent,
+            post_render_pass,
+        )?;
+        let post_target_descriptor_sets = post_targets
+            .iter()
+            .map(|target| {
+                let descriptor_set = Self::allocate_post_descriptor_set(
+                    &device,
+                    post_descriptor_pool,
+                    post_descriptor_set_layout,
+                )?;
+                Self::update_post_descriptor_set(
+                    &device,
+                    descriptor_set,
+                    target.view,
+                    post_sampler,
+                );
+                Ok(descriptor_set)
+            })
+            .collect::<Result<Vec<vk::DescriptorSet>, RendererError>>()?;
 
         let command_buffers = Self::create_command_buffers(&device, command_pool)?;
 
         let (image_available_semaphores, render_finished_semaphores, in_flight_fences) =
             Self::create_sync_objects(&device)?;
-
-        Ok(Self {
-            // The entry has to live as long as the app, otherwise you get an access violation when destroying instance.
-            _entry: entry,
-            instance,
-            #[cfg(debug_assertions)]
-            debug_utils,
+        let profiler = Profiler::new(
+            &instance,
+            physical_device,
+            &device,
+            queue_family_indices
+                .graphics_family
+                .expect("Missing graphics queue family index !"),
+        )?;
+        let statistics = PipelineStatistics::new(
+            &device,
+            supported_features.pipeline_statistics_query == vk::TRUE,
+        )?;
+        validation.check_errors();
+
+        let renderer = Self {
+            // The entry has to live as long as the app, otherwise you get an access violation when destroying instance.
+            _entry: entry,
+            instance,
+            debug_utils,
+            debug_messenger,
+            validation,
+            debug_names,
             physical_device,
             surface_loader,
             surface,
             device,
             graphics_queue,
             present_queue,
+            present_family,
             swapchain_loader,
             swapchain,
             swapchain_images,
-            swapchain_image_format,
+            surface_format,
             swapchain_extent,
             swapchain_image_views,
             render_pass,
             global_descriptor_set_layout,
             model_descriptor_set_layout,
+            scene_layout,
             pipeline_layout,
-            graphics_pipeline,
+            pipelines: HashMap::new(),
+            pipeline_cache,
+            pipeline_cache_path: config.pipeline_cache.clone(),
+            render_mode: RenderMode::Solid,
+            fill_mode_non_solid,
             swapchain_framebuffers,
             command_pool,
             command_buffers,
@@ -2349,6 +4448,7 @@ impl Renderer {
             width,
             height,
             framebuffer_resized: false,
+            swapchain_invalid: false,
             models: vec![],
             global_uniform_buffers,
             global_uniform_buffers_memory,
@@ -2358,18 +4458,56 @@ impl Renderer {
             depth_image,
             depth_image_memory,
             depth_image_view,
+            msaa_image,
+            msaa_image_memory,
+            msaa_image_view,
+            msaa_samples,
+            sample_shading,
+            offscreen_image,
+            offscreen_image_memory,
+            offscreen_image_view,
+            offscreen_framebuffer,
+            tonemap_render_pass,
+            tonemap_pipeline_layout,
+            tonemap_pipeline,
+            post_sampler,
+            post_descriptor_set_layout,
+            post_descriptor_pool,
+            offscreen_descriptor_set,
+            post_render_pass,
+            post_pipeline_layout,
+            post_pipelines,
+            post_targets,
+            post_target_descriptor_sets,
+            post_passes: vec![],
+            post_luts: vec![],
+            frame_stats: FrameStats::default(),
+            recorded_stats: Cell::new(FrameStats::default()),
+            statistics,
+            profiler: RefCell::new(profiler),
+            reversed_z: false,
+            present_mode: config.present_mode,
+            active_present_mode,
+            surface_formats: config.surface_formats.clone(),
+            output_transform: OutputTransform::new(surface_format),
+            paper_white: config.paper_white,
+            peak_brightness: config.peak_brightness,
+            screenshot_requested: false,
+            screenshot: None,
             theta: 0.0,
-            camera: Point3 {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-            },
-            target: Point3 {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
+            camera: Camera {
+                aspect_ratio: swapchain_extent.width as f32 / swapchain_extent.height as f32,
+                ..Default::default()
             },
-        })
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            ambient_light: [1.0, 1.0, 1.0],
+            lights: vec![],
+            tonemap: Tonemap::Aces,
+            exposure: 1.0,
+        };
+        renderer.name_objects();
+
+        Ok(renderer)
     }
 
     pub fn load_model(
@@ -2377,131 +4515,274 @@ impl Renderer {
         obj: &str,
         texture: &str,
         triangulate: bool,
-    ) -> Result<usize, Box<dyn Error>> {
-        self.models
-            .push(Model::new(&self, obj, texture, triangulate)?);
+    ) -> Result<usize, RendererError> {
+        if self.models.len() >= MAX_MODELS {
+            Err("Too many models !")?
+        }
+        let model = Model::new(self, obj, texture, triangulate)?;
+        self.name_model(&model);
+        self.models.push(model);
 
         Ok(self.models.len() - 1)
     }
 
-    fn update_global_uniform_buffer(&self, current_image: usize) {
-        let mut ubo = UniformBufferObject {
-            model: Align16(Matrix4::identity()),
-            view: Align16(Matrix4::look_at_lh(
-                self.camera,
-                self.target,
-                Vector3::new(0.0, 1.0, 0.0),
-            )),
-            proj: Align16(cgmath::perspective(
-                Deg(90.0),
-                self.swapchain_extent.width as f32 / self.swapchain_extent.height as f32,
-                0.1,
-                1000.0,
-            )),
+    // Generates levels of detail by simplifying the full resolution mesh, each level being given as
+    // (fraction of the triangles to keep, switch distance).
+    pub fn generate_model_lods(
+        &mut self,
+        model: usize,
+        levels: &[(f32, f32)],
+    ) -> Result<(), RendererError> {
+        for &(ratio, distance) in levels.iter() {
+            let base = self.models[model].lods()[0].mesh();
+            let (vertices, indices) = simplify::simplify(base.vertices(), base.indices(), ratio);
+            debug!(
+                "Generated LOD with {} of {} triangles.",
+                indices.len() / 3,
+                base.indices().len() / 3
+            );
+            let mesh = Mesh::new(self, vertices, indices)?;
+            self.models[model].add_lod(mesh, ratio, distance);
+        }
+        self.name_model(&self.models[model]);
+
+        Ok(())
+    }
+
+    fn select_lods(&mut self) {
+        let camera = self.camera.position;
+        for model in self.models.iter_mut() {
+            let distance = camera.distance(model.world_bounding_sphere().center);
+            model.select_lod(distance);
+        }
+    }
+
+    // Returns the indices of the models that intersect the view frustum.
+    fn cull_models(&self) -> Vec<usize> {
+        let frustum = self.camera.frustum();
+        self.models
+            .iter()
+            .enumerate()
+            .filter(|(_, model)| {
+                // The sphere test is cheap and rejects most objects, the box test is tighter.
+                frustum.intersects_sphere(&model.world_bounding_sphere())
+                    && frustum.intersects_aabb(&model.world_aabb())
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Puts the opaque models first, front to back so that hidden fragments fail the depth test early,
+    // then the transparent ones back to front so that they blend over what is behind them.
+    fn sort_models(&self, visible_models: &mut [usize]) {
+        let camera = self.camera.position;
+        let key = |&i: &usize| {
+            let model = &self.models[i];
+            let distance = camera.distance2(model.world_bounding_sphere().center);
+            let transparent = model.material.transparent();
+            (transparent, if transparent { -distance } else { distance })
+        };
+        visible_models.sort_by(|a, b| {
+            let (a, b) = (key(a), key(b));
+            a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
+        });
+    }
+
+    fn update_global_uniform_buffer(&self, current_image: usize) -> Result<(), RendererError> {
+        // Lights past MAX_LIGHTS are ignored.
+        let mut lights = [LightData::default(); MAX_LIGHTS];
+        for (data, light) in lights.iter_mut().zip(self.lights.iter()) {
+            *data = light.data();
+        }
+        let ubo = GlobalUniformBufferObject {
+            view: Align16(self.camera.view_matrix()),
+            proj: Align16(self.camera.projection_matrix()),
+            ambient: Align16([
+                self.ambient_light[0],
+                self.ambient_light[1],
+                self.ambient_light[2],
+                0.0,
+            ]),
+            light_count: Align16(self.lights.len().min(MAX_LIGHTS) as u32),
+            lights,
         };
-        ubo.proj[1][1] *= -1.0;
 
         let data = unsafe {
             self.device.map_memory(
                 self.global_uniform_buffers_memory[current_image],
                 0,
-                std::mem::size_of::<UniformBufferObject>() as u64,
+                std::mem::size_of::<GlobalUniformBufferObject>() as u64,
                 vk::MemoryMapFlags::empty(),
             )
-        }
-        .expect("Error mapping memory to uniform buffer !")
-            as *mut UniformBufferObject;
-        unsafe { data.copy_from_nonoverlapping(&ubo as *const UniformBufferObject, 1) };
+        }? as *mut GlobalUniformBufferObject;
+        unsafe { data.copy_from_nonoverlapping(&ubo as *const GlobalUniformBufferObject, 1) };
         unsafe {
             self.device
                 .unmap_memory(self.global_uniform_buffers_memory[current_image])
         };
-        #[cfg(debug_assertions)]
-        println!("Uniform buffer memory copied.");
+        trace!("Uniform buffer memory copied.");
+
+        Ok(())
     }
 
-    fn update_model_uniform_buffer(&self, current_image: usize, model: &Model) {
-        let ubo = UniformBufferObject {
-            model: Align16(
-                Matrix4::from_translation(Vector3::new(
-                    model.position.x,
-                    model.position.y,
-                    model.position.z,
-                )) * Matrix4::from_angle_y(Deg(model.theta))
-                    * Matrix4::from_angle_x(Deg(model.theta)),
-            ),
-            view: Align16(Matrix4::identity()),
-            proj: Align16(Matrix4::identity()),
+    fn update_model_uniform_buffer(
+        &self,
+        current_image: usize,
+        model: &Model,
+    ) -> Result<(), RendererError> {
+        let ubo = ModelUniformBufferObject {
+            model: Align16(model.model_matrix()),
+            base_color: Align16(model.material.base_color),
+            alpha_cutoff: model.material.alpha_cutoff(),
         };
 
         let data = unsafe {
             self.device.map_memory(
                 model.uniform_buffers_memory()[current_image],
                 0,
-                std::mem::size_of::<UniformBufferObject>() as u64,
+                std::mem::size_of::<ModelUniformBufferObject>() as u64,
                 vk::MemoryMapFlags::empty(),
             )
-        }
-        .expect("Error mapping memory to uniform buffer !")
-            as *mut UniformBufferObject;
-        unsafe { data.copy_from_nonoverlapping(&ubo as *const UniformBufferObject, 1) };
+        }? as *mut ModelUniformBufferObject;
+        unsafe { data.copy_from_nonoverlapping(&ubo as *const ModelUniformBufferObject, 1) };
         unsafe {
             self.device
                 .unmap_memory(model.uniform_buffers_memory()[current_image])
         };
-        #[cfg(debug_assertions)]
-        println!("Uniform buffer memory copied.");
+        trace!("Uniform buffer memory copied.");
+
+        Ok(())
     }
 
-    pub fn draw_frame(&mut self) {
+    pub fn draw_frame(&mut self) -> Result<(), RendererError> {
+        if self.swapchain_invalid {
+            // The last recreation failed, nothing can be drawn until one succeeds.
+            self.recreate_swapchain()?;
+        }
+        if self.camera.reversed_z != self.reversed_z {
+            // The depth compare op is baked into the pipeline, which is rebuilt with the swapchain.
+            self.reversed_z = self.camera.reversed_z;
+            self.recreate_swapchain()?;
+        }
+
+        let profiler = self.profiler.get_mut();
+        profiler.begin_frame(self.current_frame);
+        profiler.begin(&self.device, None, "Wait");
         unsafe {
             self.device.wait_for_fences(
                 &[self.in_flight_fences[self.current_frame]],
                 true,
                 u64::MAX,
             )
+        }?;
+        let profiler = self.profiler.get_mut();
+        profiler.end(&self.device);
+        profiler.collect(&self.device);
+        if let Some(stats) = self.statistics.collect(&self.device, self.current_frame) {
+            self.frame_stats = stats;
+        }
+
+        // Everything that can fail is done before the acquire, which signals the semaphore the submit
+        // waits on.
+        self.profiler.get_mut().begin(&self.device, None, "Update");
+        self.update_global_uniform_buffer(self.current_frame)?;
+        for model in self.models.iter() {
+            self.update_model_uniform_buffer(self.current_frame, model)?;
+        }
+        self.profiler.get_mut().end(&self.device);
+
+        self.profiler.get_mut().begin(&self.device, None, "Culling");
+        self.select_lods();
+        let mut visible_models = self.cull_models();
+        self.sort_models(&mut visible_models);
+        self.profiler.get_mut().end(&self.device);
+        for desc in self.frame_pipeline_descs(&visible_models) {
+            self.pipeline(&desc)?;
         }
-        .expect("Error waiting for fence !");
+        self.recorded_stats.set(FrameStats {
+            culled_objects: (self.models.len() - visible_models.len()) as u32,
+            ..Default::default()
+        });
+        let screenshot = if self.screenshot_requested {
+            self.screenshot_requested = false;
+            Some(self.create_screenshot()?)
+        } else {
+            None
+        };
 
-        let (image_index, _) = match unsafe {
+        self.profiler.get_mut().begin(&self.device, None, "Acquire");
+        let result = unsafe {
             self.swapchain_loader.acquire_next_image(
                 self.swapchain,
                 u64::MAX,
                 self.image_available_semaphores[self.current_frame],
                 vk::Fence::null(),
             )
-        } {
-            Ok(result) => result,
-            Err(err) => match err {
-                vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                    self.recreate_swapchain()
-                        .expect("Error recreating swapchain !");
-                    return;
+        };
+        self.profiler.get_mut().end(&self.device);
+        let image_index = match result {
+            Ok((image_index, suboptimal)) => {
+                // Still presentable, the swapchain is recreated once the frame is done.
+                if suboptimal {
+                    self.framebuffer_resized = true;
                 }
-                _ => panic!("Error acquiring next image !"),
-            },
+                image_index
+            }
+            Err(err) => {
+                // The screenshot is taken of the next frame, with the new extent.
+                if let Some(screenshot) = screenshot {
+                    self.cleanup_screenshot(&screenshot);
+                    self.screenshot_requested = true;
+                }
+                match err {
+                    vk::Result::ERROR_OUT_OF_DATE_KHR => {
+                        self.recreate_swapchain()?;
+                        return Ok(());
+                    }
+                    _ => return Err(RendererError::swapchain(err)),
+                }
+            }
         };
 
-        self.update_global_uniform_buffer(self.current_frame);
-        for model in self.models.iter() {
-            self.update_model_uniform_buffer(self.current_frame, model);
-        }
-
-        unsafe {
-            self.device
-                .reset_fences(&[self.in_flight_fences[self.current_frame]])
-        }
-        .expect("Error resetting fence !");
-
-        unsafe {
+        let recorded = unsafe {
             self.device.reset_command_buffer(
                 self.command_buffers[self.current_frame],
                 vk::CommandBufferResetFlags::default(),
             )
         }
-        .expect("Error resetting command buffer !");
-        self.record_command_buffer(self.command_buffers[self.current_frame], image_index)
-            .expect("Error recording command buffer !");
+        .map_err(RendererError::from)
+        .and_then(|()| {
+            self.record_command_buffer(
+                self.command_buffers[self.current_frame],
+                image_index,
+                &visible_models,
+                screenshot.as_ref(),
+            )
+        });
+        if let Err(err) = recorded {
+            if let Some(screenshot) = screenshot {
+                self.cleanup_screenshot(&screenshot);
+            }
+            // Waits on the acquire semaphore anyway, so that it is unsignaled for the next acquire.
+            let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
+            let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+            let submit_infos = [vk::SubmitInfo {
+                wait_semaphore_count: 1,
+                p_wait_semaphores: wait_semaphores.as_ptr(),
+                p_wait_dst_stage_mask: wait_stages.as_ptr(),
+                ..Default::default()
+            }];
+            unsafe {
+                self.device
+                    .queue_submit(self.graphics_queue, &submit_infos, vk::Fence::null())
+            }?;
+            return Err(err);
+        }
+        if let Some(screenshot) = screenshot {
+            if let Some(previous) = self.screenshot.replace(screenshot) {
+                self.cleanup_screenshot(&previous);
+            }
+        }
 
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
@@ -2516,14 +4797,22 @@ impl Renderer {
             p_signal_semaphores: signal_semaphores.as_ptr(),
             ..Default::default()
         }];
+        self.profiler.get_mut().begin(&self.device, None, "Submit");
+        // Only reset now that nothing can fail before the submit, which signals it again.
+        unsafe {
+            self.device
+                .reset_fences(&[self.in_flight_fences[self.current_frame]])
+        }?;
         unsafe {
             self.device.queue_submit(
                 self.graphics_queue,
                 &submit_infos,
                 self.in_flight_fences[self.current_frame],
             )
-        }
-        .expect("Error submitting command buffer !");
+        }?;
+        self.profiler.get_mut().end(&self.device);
+        self.statistics
+            .submitted(self.current_frame, self.recorded_stats.get());
 
         let swapchains = [self.swapchain];
         let present_info = vk::PresentInfoKHR {
@@ -2534,24 +4823,389 @@ impl Renderer {
             p_image_indices: &image_index,
             ..Default::default()
         };
+        self.profiler.get_mut().begin(&self.device, None, "Present");
         let result = unsafe {
             self.swapchain_loader
                 .queue_present(self.present_queue, &present_info)
         };
-        let framebuffer_resized = match result {
-            Ok(_) => self.framebuffer_resized,
-            Err(err) => match err {
-                vk::Result::ERROR_OUT_OF_DATE_KHR => true,
-                _ => panic!("Error presenting to swapchain !"),
-            },
+        let profiler = self.profiler.get_mut();
+        profiler.end(&self.device);
+        profiler.end_frame();
+        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        self.validation.check_errors();
+        let recreate = match result {
+            Ok(suboptimal) => suboptimal || self.framebuffer_resized,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
+            Err(result) => Err(RendererError::swapchain(result))?,
         };
-        if framebuffer_resized {
+        if recreate {
             self.framebuffer_resized = false;
-            self.recreate_swapchain()
-                .expect("Error recreating swapchain !");
+            self.recreate_swapchain()?;
         }
 
-        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        Ok(())
+    }
+
+    // Replaces a lost surface with one for the same window, keeping everything else.
+    pub fn recreate_surface(
+        &mut self,
+        display_handle: raw_window_handle::RawDisplayHandle,
+        window_handle: raw_window_handle::RawWindowHandle,
+    ) -> Result<(), RendererError> {
+        let surface = unsafe {
+            ash_window::create_surface(
+                &self._entry,
+                &self.instance,
+                display_handle,
+                window_handle,
+                None,
+            )
+        }?;
+        let supported = Self::find_queue_families(
+            &self.instance,
+            self.physical_device,
+            &self.surface_loader,
+            surface,
+        )
+        .map(|indices| indices.present_family == Some(self.present_family));
+        if !matches!(supported, Ok(true)) {
+            unsafe { self.surface_loader.destroy_surface(surface, None) };
+            Err("The new surface can't be presented to from the same queue !")?
+        }
+        debug!("Surface recreated.");
+
+        // The old swapchain has to go before its surface.
+        let old_surface = std::mem::replace(&mut self.surface, surface);
+        let result = self.recreate_swapchain();
+        unsafe { self.surface_loader.destroy_surface(old_surface, None) };
+
+        result
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    pub fn active_present_mode(&self) -> PresentMode {
+        self.active_present_mode
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<(), RendererError> {
+        if present_mode != self.present_mode {
+            self.present_mode = present_mode;
+            self.recreate_swapchain()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
+    pub fn supports_render_mode(&self, render_mode: RenderMode) -> bool {
+        !render_mode.wireframe() || self.fill_mode_non_solid
+    }
+
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) -> Result<(), RendererError> {
+        if !self.supports_render_mode(render_mode) {
+            Err("Wireframes need the fillModeNonSolid device feature !")?
+        }
+        self.render_mode = render_mode;
+
+        Ok(())
+    }
+
+    // Samples per pixel actually used, 1 when multisampling is off.
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa_samples.as_raw()
+    }
+
+    // Lowered to what the device supports, like RendererConfig::msaa_samples.
+    pub fn set_msaa_samples(&mut self, samples: u32) -> Result<(), RendererError> {
+        let msaa_samples = Self::choose_sample_count(&self.instance, self.physical_device, samples);
+        if msaa_samples != self.msaa_samples {
+            self.msaa_samples = msaa_samples;
+            self.recreate_swapchain()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn post_passes(&self) -> &[PostPass] {
+        &self.post_passes
+    }
+
+    // Replaces the post-process chain, loading the lookup tables it needs. The ones already loaded are
+    // kept, so changing the parameters or toggling passes this way is cheap.
+    pub fn set_post_passes(&mut self, post_passes: Vec<PostPass>) -> Result<(), RendererError> {
+        let mut paths = vec![];
+        for pass in post_passes.iter() {
+            if let PostEffect::ColorGrading { lut, .. } = &pass.effect {
+                if !paths.contains(&lut.as_str()) {
+                    paths.push(lut.as_str());
+                }
+            }
+        }
+        if paths.len() > MAX_LUTS {
+            Err("Too many lookup tables !")?
+        }
+
+        // Load before dropping anything, so that the chain is left untouched on error.
+        let mut new_luts = vec![];
+        for path in paths.iter() {
+            if !self.post_luts.iter().any(|lut| lut.path() == *path) {
+                match Lut::load(self, path) {
+                    Ok(lut) => {
+                        self.name_lut(&lut);
+                        new_luts.push(lut);
+                    }
+                    Err(err) => {
+                        for lut in new_luts.iter() {
+                            self.cleanup_lut(lut);
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+        }
+
+        let (kept, unused): (Vec<Lut>, Vec<Lut>) = std::mem::take(&mut self.post_luts)
+            .into_iter()
+            .partition(|lut| paths.contains(&lut.path()));
+        if !unused.is_empty() {
+            self.wait_idle();
+            for lut in unused.iter() {
+                self.cleanup_lut(lut);
+            }
+        }
+        self.post_luts = kept;
+        self.post_luts.extend(new_luts);
+        self.post_passes = post_passes;
+
+        Ok(())
+    }
+
+    // Recompiles the given (source, output) shaders and rebuilds the pipelines using them. A shader that
+    // doesn't compile keeps its last SPIR-V, and so its pipelines, the diagnostics being printed.
+    pub fn reload_shaders(&mut self, shaders: &[(&str, &str)]) -> Result<(), RendererError> {
+        let mut outputs = vec![];
+        for &(source, output) in shaders.iter() {
+            match shader_compiler::compile_to_file(source, output) {
+                Ok(()) => outputs.push(output),
+                Err(err) => error!("Error compiling {} :\n{}", source, err),
+            }
+        }
+        if outputs.is_empty() {
+            return Ok(());
+        }
+
+        // The frames in flight may still be using the old pipelines.
+        unsafe {
+            self.device
+                .wait_for_fences(&self.in_flight_fences, true, u64::MAX)
+        }?;
+
+        // The tonemap pipeline is made with the swapchain, which drops the scene pipelines too.
+        let fullscreen_vert = outputs.contains(&"shaders/fullscreen_vert.spv");
+        if fullscreen_vert || outputs.contains(&"shaders/tonemap_frag.spv") {
+            self.recreate_swapchain()?;
+        }
+        let post_shaders = PostShader::ALL
+            .iter()
+            .copied()
+            .filter(|shader| fullscreen_vert || outputs.contains(&shader.path()))
+            .collect::<Vec<PostShader>>();
+        if !post_shaders.is_empty() {
+            self.rebuild_post_pipelines(&post_shaders);
+        }
+
+        let descs = self
+            .pipelines
+            .keys()
+            .filter(|desc| {
+                outputs.contains(&desc.vert_shader.as_str())
+                    || outputs.contains(&desc.frag_shader.as_str())
+            })
+            .cloned()
+            .collect::<Vec<PipelineDesc>>();
+        for desc in descs {
+            match self.create_graphics_pipeline(&desc) {
+                Ok(pipeline) => {
+                    if let Some(old) = self.pipelines.insert(desc, pipeline) {
+                        unsafe { self.device.destroy_pipeline(old, None) };
+                    }
+                }
+                Err(err) => error!("Error rebuilding pipeline : {}", err),
+            }
+        }
+        debug!("Shaders reloaded.");
+
+        Ok(())
+    }
+
+    // Keeps the old pipelines when the new ones can't be made.
+    fn rebuild_post_pipelines(&mut self, shaders: &[PostShader]) {
+        let pipelines = match Self::create_post_pipelines(
+            &self.device,
+            &self.debug_names,
+            self.post_render_pass,
+            self.post_pipeline_layout,
+            self.pipeline_cache,
+            shaders,
+        ) {
+            Ok(pipelines) => pipelines,
+            Err(err) => {
+                error!("Error rebuilding post-process pipelines : {}", err);
+                return;
+            }
+        };
+        for (shader, pipeline) in shaders.iter().zip(pipelines) {
+            let index = PostShader::ALL
+                .iter()
+                .position(|s| s == shader)
+                .expect("Missing post-process pipeline !");
+            let old = std::mem::replace(&mut self.post_pipelines[index], pipeline);
+            unsafe { self.device.destroy_pipeline(old, None) };
+            self.debug_names
+                .set(pipeline, &format!("{:?} pipeline", shader));
+        }
+    }
+
+    // The format and color space the swapchain actually uses.
+    pub fn surface_format(&self) -> vk::SurfaceFormatKHR {
+        self.surface_format
+    }
+
+    pub fn output_transform(&self) -> OutputTransform {
+        self.output_transform
+    }
+
+    // The next frame drawn is copied, use save_screenshot once it has been drawn.
+    pub fn request_screenshot(&mut self) -> Result<(), RendererError> {
+        let capabilities = Self::query_swapchain_support(
+            self.physical_device,
+            &self.surface_loader,
+            self.surface,
+        )?
+        .capabilities;
+        if !capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            Err("Swapchain images can't be copied on this surface !")?
+        }
+        if self.surface_format.color_space != vk::ColorSpaceKHR::SRGB_NONLINEAR {
+            Err("Screenshots of HDR surfaces are not supported !")?
+        }
+        self.screenshot_requested = true;
+
+        Ok(())
+    }
+
+    pub fn save_screenshot(&mut self, path: &Path) -> Result<(), RendererError> {
+        let screenshot = match self.screenshot.take() {
+            Some(screenshot) => screenshot,
+            None => Err("No frame has been drawn since the screenshot was requested !")?,
+        };
+        self.wait_idle();
+
+        let size = (screenshot.extent.width * screenshot.extent.height * 4) as usize;
+        let data = unsafe {
+            self.device.map_memory(
+                screenshot.memory,
+                0,
+                size as u64,
+                vk::MemoryMapFlags::empty(),
+            )
+        }? as *const u8;
+        let mut pixels = unsafe { std::slice::from_raw_parts(data, size) }.to_vec();
+        unsafe { self.device.unmap_memory(screenshot.memory) };
+        self.cleanup_screenshot(&screenshot);
+
+        match screenshot.format {
+            vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => (),
+            // 2 bits of alpha then 10 bits per channel, from the most significant bit.
+            vk::Format::A2B10G10R10_UNORM_PACK32 | vk::Format::A2R10G10B10_UNORM_PACK32 => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    let packed = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                    let channel = |shift: u32| (((packed >> shift) & 0x3ff) >> 2) as u8;
+                    let (r, b) = if screenshot.format == vk::Format::A2B10G10R10_UNORM_PACK32 {
+                        (channel(0), channel(20))
+                    } else {
+                        (channel(20), channel(0))
+                    };
+                    pixel.copy_from_slice(&[r, channel(10), b, ((packed >> 30) * 85) as u8]);
+                }
+            }
+            _ => Err("Unsupported swapchain format for screenshots !")?,
+        }
+        image::save_buffer(
+            path,
+            &pixels,
+            screenshot.extent.width,
+            screenshot.extent.height,
+            image::ColorType::Rgba8,
+        )
+        .map_err(|source| RendererError::Image {
+            path: path.to_string_lossy().into_owned(),
+            source,
+        })?;
+
+        Ok(())
+    }
+
+    // Lists every device with the result of each suitability check, without creating a renderer.
+    pub fn list_devices(
+        display_handle: raw_window_handle::RawDisplayHandle,
+        window_handle: raw_window_handle::RawWindowHandle,
+    ) -> Result<Vec<DeviceInfo>, RendererError> {
+        let entry = Entry::linked();
+        let app_info = vk::ApplicationInfo {
+            api_version: vk::make_api_version(0, 1, 0, 0),
+            ..Default::default()
+        };
+        let required_extensions = ash_window::enumerate_required_extensions(display_handle)
+            .map_err(RendererError::Instance)?;
+        let create_info = vk::InstanceCreateInfo {
+            p_application_info: &app_info,
+            enabled_extension_count: required_extensions.len() as u32,
+            pp_enabled_extension_names: required_extensions.as_ptr(),
+            ..Default::default()
+        };
+        let instance = unsafe { entry.create_instance(&create_info, None) }
+            .map_err(RendererError::Instance)?;
+        let surface_loader = Surface::new(&entry, &instance);
+        let surface = unsafe {
+            ash_window::create_surface(&entry, &instance, display_handle, window_handle, None)
+        }?;
+
+        // The surface and instance have to be destroyed whether the checks fail or not.
+        let devices = (|| -> Result<Vec<DeviceInfo>, RendererError> {
+            let mut devices = vec![];
+            let physical_devices = unsafe { instance.enumerate_physical_devices() }?;
+            for (index, &physical_device) in physical_devices.iter().enumerate() {
+                let (device_info, _, _) = Self::check_physical_device(
+                    &instance,
+                    physical_device,
+                    index,
+                    &surface_loader,
+                    surface,
+                )?;
+                devices.push(device_info);
+            }
+            Ok(devices)
+        })();
+
+        unsafe { surface_loader.destroy_surface(surface, None) };
+        unsafe { instance.destroy_instance(None) };
+
+        devices
     }
 
     pub fn window_resized(&mut self, width: u32, height: u32) {
@@ -2561,16 +5215,19 @@ impl Renderer {
     }
 
     pub fn wait_idle(&self) {
-        unsafe {
-            self.device
-                .device_wait_idle()
-                .expect("Error waiting for operations to finish !")
-        };
+        // Nothing left to wait for when the device is lost.
+        if let Err(err) = unsafe { self.device.device_wait_idle() } {
+            error!("Error waiting for operations to finish : {}", err);
+        }
     }
 }
 
 impl Drop for Renderer {
     fn drop(&mut self) {
+        if let Some(screenshot) = self.screenshot.take() {
+            self.cleanup_screenshot(&screenshot);
+        }
+
         for model in self.models.iter() {
             self.cleanup_model(model);
         }
@@ -2578,8 +5235,46 @@ impl Drop for Renderer {
         self.cleanup_swapchain();
 
         unsafe { self.device.destroy_sampler(self.texture_sampler, None) };
-        #[cfg(debug_assertions)]
-        println!("Texture sampler dropped.");
+        debug!("Texture sampler dropped.");
+
+        for lut in self.post_luts.iter() {
+            self.cleanup_lut(lut);
+        }
+
+        for &pipeline in self.post_pipelines.iter() {
+            unsafe { self.device.destroy_pipeline(pipeline, None) };
+        }
+        unsafe {
+            self.device
+                .destroy_pipeline_layout(self.post_pipeline_layout, None)
+        };
+        unsafe { self.device.destroy_render_pass(self.post_render_pass, None) };
+        debug!("Post-process pipelines and render pass dropped.");
+
+        unsafe {
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None)
+        };
+        debug!("Pipeline layout dropped.");
+
+        // Not worth failing over, the pipelines are only compiled again on the next run.
+        if let Err(err) = self.save_pipeline_cache() {
+            error!("Error saving pipeline cache : {}", err);
+        }
+        unsafe {
+            self.device
+                .destroy_pipeline_cache(self.pipeline_cache, None)
+        };
+        debug!("Pipeline cache dropped.");
+
+        unsafe {
+            self.device
+                .destroy_descriptor_pool(self.post_descriptor_pool, None)
+        };
+        debug!("Post-process descriptor pool dropped.");
+
+        unsafe { self.device.destroy_sampler(self.post_sampler, None) };
+        debug!("Post-process sampler dropped.");
 
         for i in 0..MAX_FRAMES_IN_FLIGHT {
             unsafe {
@@ -2591,29 +5286,34 @@ impl Drop for Renderer {
                     .free_memory(self.global_uniform_buffers_memory[i], None)
             };
         }
-        #[cfg(debug_assertions)]
-        println!("Uniform buffers dropped and uniform buffers memory freed.");
+        debug!("Uniform buffers dropped and uniform buffers memory freed.");
 
         unsafe {
             self.device
                 .destroy_descriptor_pool(self.descriptor_pool, None)
         };
-        #[cfg(debug_assertions)]
-        println!("Descriptor pool dropped.");
+        debug!("Descriptor pool dropped.");
 
         unsafe {
             self.device
                 .destroy_descriptor_set_layout(self.model_descriptor_set_layout, None)
         };
-        #[cfg(debug_assertions)]
-        println!("Model descriptor set layout dropped.");
+        debug!("Model descriptor set layout dropped.");
+
+        unsafe {
+            self.device
+                .destroy_descriptor_set_layout(self.post_descriptor_set_layout, None)
+        };
+        debug!("Post-process descriptor set layout dropped.");
 
         unsafe {
             self.device
                 .destroy_descriptor_set_layout(self.global_descriptor_set_layout, None)
         };
-        #[cfg(debug_assertions)]
-        println!("Global descriptor set layout dropped.");
+        debug!("Global descriptor set layout dropped.");
+
+        self.profiler.borrow().destroy(&self.device);
+        self.statistics.destroy(&self.device);
 
         for i in 0..MAX_FRAMES_IN_FLIGHT {
             unsafe {
@@ -2626,32 +5326,26 @@ impl Drop for Renderer {
             };
             unsafe { self.device.destroy_fence(self.in_flight_fences[i], None) }
         }
-        #[cfg(debug_assertions)]
-        println!("Sync objects dropped.");
+        debug!("Sync objects dropped.");
 
         unsafe { self.device.destroy_command_pool(self.command_pool, None) };
-        #[cfg(debug_assertions)]
-        println!("Command pool dropped.");
+        debug!("Command pool dropped.");
 
         unsafe { self.device.destroy_device(None) };
-        #[cfg(debug_assertions)]
-        println!("Logical device dropped.");
+        debug!("Logical device dropped.");
 
         unsafe { self.surface_loader.destroy_surface(self.surface, None) };
-        #[cfg(debug_assertions)]
-        println!("Window surface dropped.");
+        debug!("Window surface dropped.");
 
-        #[cfg(debug_assertions)]
-        if let Some((debug_utils_loader, debug_utils_messenger)) = &self.debug_utils {
-            unsafe {
-                debug_utils_loader.destroy_debug_utils_messenger(*debug_utils_messenger, None)
-            };
-            println!("Debug messenger dropped.");
+        if let (Some(debug_utils), Some(debug_messenger)) =
+            (&self.debug_utils, self.debug_messenger)
+        {
+            unsafe { debug_utils.destroy_debug_utils_messenger(debug_messenger, None) };
+            debug!("Debug messenger dropped.");
         }
 
         // The ash::Entry used to create the instance has to be alive when calling ash::Instance::destroy_instance.
         unsafe { self.instance.destroy_instance(None) };
-        #[cfg(debug_assertions)]
-        println!("Vulkan instance dropped.");
+        debug!("Vulkan instance dropped.");
     }
 }
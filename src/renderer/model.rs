@@ -1,10 +1,81 @@
-use std::{collections::HashMap, error::Error, path::Path};
+use std::{collections::HashMap, path::Path};
 
 use ash::vk;
-use cgmath::Point3;
+use cgmath::{Deg, InnerSpace, Matrix4, Point3, Transform, Vector3, Zero};
+use serde::{Deserialize, Serialize};
 use tobj::LoadOptions;
 
-use super::{types::Vertex, Renderer};
+use super::{
+    bounds::{Aabb, BoundingSphere},
+    pipeline::{BlendMode, PipelineDesc},
+    types::Vertex,
+    Renderer, RendererError,
+};
+
+// Fraction of the switch distance the camera has to move past before the level of detail changes.
+const LOD_HYSTERESIS: f32 = 0.1;
+
+// How the alpha of the texture and base color is used.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    // Fragments with an alpha under the cutoff are discarded, the others are opaque.
+    Mask {
+        cutoff: f32,
+    },
+    // Mixed with what is behind, drawn after the opaque models from back to front.
+    Blend,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Material {
+    pub name: String,
+    // Multiplied with the texture color.
+    pub base_color: [f32; 4],
+    pub alpha_mode: AlphaMode,
+    pub pipeline: PipelineDesc,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: "default".to_owned(),
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            alpha_mode: AlphaMode::Opaque,
+            pipeline: PipelineDesc::default(),
+        }
+    }
+}
+
+impl Material {
+    // The pipeline the material is drawn with, blended materials not writing depth so that what is
+    // behind them still shows through the ones drawn later.
+    pub fn pipeline_desc(&self) -> PipelineDesc {
+        let mut desc = self.pipeline.clone();
+        if self.alpha_mode == AlphaMode::Blend {
+            if desc.blend == BlendMode::Opaque {
+                desc.blend = BlendMode::Alpha;
+            }
+            desc.depth_write = false;
+        }
+
+        desc
+    }
+
+    // Drawn in the transparent queue, after the opaque models.
+    pub fn transparent(&self) -> bool {
+        self.alpha_mode == AlphaMode::Blend || self.pipeline.blend != BlendMode::Opaque
+    }
+
+    // Under which fragments are discarded, 0 keeping them all.
+    pub fn alpha_cutoff(&self) -> f32 {
+        match self.alpha_mode {
+            AlphaMode::Mask { cutoff } => cutoff,
+            _ => 0.0,
+        }
+    }
+}
 
 pub struct Texture {
     width: u32,
@@ -26,27 +97,18 @@ impl Texture {
     }
 }
 
-pub struct Model {
-    _vertices: Vec<Vertex>,
+pub struct Mesh {
+    vertices: Vec<Vertex>,
     indices: Vec<u32>,
     vertex_buffer: vk::Buffer,
     vertex_buffer_memory: vk::DeviceMemory,
     index_buffer: vk::Buffer,
     index_buffer_memory: vk::DeviceMemory,
-    _texture: Texture,
-    texture_image: vk::Image,
-    texture_image_memory: vk::DeviceMemory,
-    texture_image_view: vk::ImageView,
-    uniform_buffers: Vec<vk::Buffer>,
-    uniform_buffers_memory: Vec<vk::DeviceMemory>,
-    descriptor_sets: Vec<vk::DescriptorSet>,
-    pub position: Point3<f32>,
-    pub theta: f32,
 }
 
-impl Model {
-    pub fn _vertices(&self) -> &[Vertex] {
-        &self._vertices
+impl Mesh {
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
     }
 
     pub fn indices(&self) -> &[u32] {
@@ -69,40 +131,25 @@ impl Model {
         self.index_buffer_memory
     }
 
-    pub fn _texture(&self) -> &Texture {
-        &self._texture
-    }
-
-    pub fn texture_image(&self) -> vk::Image {
-        self.texture_image
-    }
-
-    pub fn texture_image_memory(&self) -> vk::DeviceMemory {
-        self.texture_image_memory
-    }
-
-    pub fn texture_image_view(&self) -> vk::ImageView {
-        self.texture_image_view
-    }
-
-    pub fn descriptor_sets(&self) -> &[vk::DescriptorSet] {
-        &self.descriptor_sets
-    }
-
-    pub fn uniform_buffers(&self) -> &[vk::Buffer] {
-        &self.uniform_buffers
-    }
+    pub fn new(
+        renderer: &Renderer,
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+    ) -> Result<Self, RendererError> {
+        let (vertex_buffer, vertex_buffer_memory) = renderer.create_vertex_buffer(&vertices)?;
+        let (index_buffer, index_buffer_memory) = renderer.create_index_buffer(&indices)?;
 
-    pub fn uniform_buffers_memory(&self) -> &[vk::DeviceMemory] {
-        &self.uniform_buffers_memory
+        Ok(Mesh {
+            vertices,
+            indices,
+            vertex_buffer,
+            vertex_buffer_memory,
+            index_buffer,
+            index_buffer_memory,
+        })
     }
 
-    pub fn new(
-        renderer: &Renderer,
-        obj: &str,
-        texture: &str,
-        triangulate: bool,
-    ) -> Result<Self, Box<dyn Error>> {
+    pub fn load(renderer: &Renderer, obj: &str, triangulate: bool) -> Result<Self, RendererError> {
         let mut vertices = vec![];
         let mut indices = vec![];
         let mut unique_vertices = HashMap::new();
@@ -111,10 +158,28 @@ impl Model {
             triangulate, // enable if model is not composed of triangles only
             ..Default::default()
         };
-        let (models, _) = tobj::load_obj(obj, &load_options)?;
+        let (models, _) =
+            tobj::load_obj(obj, &load_options).map_err(|source| RendererError::Obj {
+                path: obj.to_owned(),
+                source,
+            })?;
+        let mut has_normals = true;
         for model in models.iter() {
             let mesh = &model.mesh;
-            for (&index, &tex_index) in mesh.indices.iter().zip(mesh.texcoord_indices.iter()) {
+            has_normals &= !mesh.normal_indices.is_empty();
+            for (i, (&index, &tex_index)) in mesh
+                .indices
+                .iter()
+                .zip(mesh.texcoord_indices.iter())
+                .enumerate()
+            {
+                let normal = match mesh.normal_indices.get(i) {
+                    Some(&n) => {
+                        let n = 3 * n as usize;
+                        [mesh.normals[n], mesh.normals[n + 1], mesh.normals[n + 2]]
+                    }
+                    None => [0.0, 0.0, 0.0],
+                };
                 let vertex = Vertex {
                     pos: [
                         mesh.positions[3 * index as usize + 0],
@@ -126,6 +191,7 @@ impl Model {
                         mesh.texcoords[2 * tex_index as usize + 0],
                         1.0 - mesh.texcoords[2 * tex_index as usize + 1],
                     ],
+                    normal,
                 };
                 if let Some(i) = unique_vertices.get(&vertex) {
                     indices.push(*i as u32);
@@ -138,10 +204,196 @@ impl Model {
             }
         }
 
-        let (vertex_buffer, vertex_buffer_memory) = renderer.create_vertex_buffer(&vertices)?;
-        let (index_buffer, index_buffer_memory) = renderer.create_index_buffer(&indices)?;
+        if !has_normals {
+            Self::compute_normals(&mut vertices, &indices);
+        }
+
+        Self::new(renderer, vertices, indices)
+    }
+
+    // Smooth normals averaged from the faces around each vertex, weighted by their area.
+    fn compute_normals(vertices: &mut [Vertex], indices: &[u32]) {
+        let mut normals = vec![Vector3::zero(); vertices.len()];
+        for t in indices.chunks_exact(3) {
+            let p = [t[0], t[1], t[2]].map(|i| Vector3::from(vertices[i as usize].pos));
+            let normal = (p[1] - p[0]).cross(p[2] - p[0]);
+            for &i in t.iter() {
+                normals[i as usize] += normal;
+            }
+        }
+        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+            if normal.magnitude2() > 0.0 {
+                vertex.normal = normal.normalize().into();
+            }
+        }
+    }
+}
+
+pub struct Lod {
+    mesh: Mesh,
+    // Fraction of the full resolution triangles the mesh was simplified to.
+    ratio: f32,
+    distance: f32,
+}
+
+impl Lod {
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+}
 
-        let image = image::open(Path::new(texture))?;
+pub struct Model {
+    obj_path: String,
+    texture_path: String,
+    triangulated: bool,
+    lods: Vec<Lod>,
+    lod: usize,
+    _texture: Texture,
+    texture_image: vk::Image,
+    texture_image_memory: vk::DeviceMemory,
+    texture_image_view: vk::ImageView,
+    uniform_buffers: Vec<vk::Buffer>,
+    uniform_buffers_memory: Vec<vk::DeviceMemory>,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    aabb: Aabb,
+    bounding_sphere: BoundingSphere,
+    pub name: String,
+    pub material: Material,
+    pub position: Point3<f32>,
+    pub theta: f32,
+    pub scale: f32,
+}
+
+impl Model {
+    pub fn obj_path(&self) -> &str {
+        &self.obj_path
+    }
+
+    pub fn texture_path(&self) -> &str {
+        &self.texture_path
+    }
+
+    pub fn triangulated(&self) -> bool {
+        self.triangulated
+    }
+
+    pub fn lods(&self) -> &[Lod] {
+        &self.lods
+    }
+
+    pub fn lod(&self) -> usize {
+        self.lod
+    }
+
+    // The mesh of the currently selected level of detail.
+    pub fn mesh(&self) -> &Mesh {
+        &self.lods[self.lod].mesh
+    }
+
+    pub fn _texture(&self) -> &Texture {
+        &self._texture
+    }
+
+    pub fn texture_image(&self) -> vk::Image {
+        self.texture_image
+    }
+
+    pub fn texture_image_memory(&self) -> vk::DeviceMemory {
+        self.texture_image_memory
+    }
+
+    pub fn texture_image_view(&self) -> vk::ImageView {
+        self.texture_image_view
+    }
+
+    pub fn descriptor_sets(&self) -> &[vk::DescriptorSet] {
+        &self.descriptor_sets
+    }
+
+    pub fn uniform_buffers(&self) -> &[vk::Buffer] {
+        &self.uniform_buffers
+    }
+
+    pub fn uniform_buffers_memory(&self) -> &[vk::DeviceMemory] {
+        &self.uniform_buffers_memory
+    }
+
+    pub fn model_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(Vector3::new(
+            self.position.x,
+            self.position.y,
+            self.position.z,
+        )) * Matrix4::from_angle_y(Deg(self.theta))
+            * Matrix4::from_angle_x(Deg(self.theta))
+            * Matrix4::from_scale(self.scale)
+    }
+
+    pub fn world_aabb(&self) -> Aabb {
+        self.aabb.transform(&self.model_matrix())
+    }
+
+    // The model matrix scales uniformly, so the sphere stays a sphere.
+    pub fn world_bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere {
+            center: self
+                .model_matrix()
+                .transform_point(self.bounding_sphere.center),
+            radius: self.bounding_sphere.radius * self.scale.abs(),
+        }
+    }
+
+    pub fn add_lod(&mut self, mesh: Mesh, ratio: f32, distance: f32) {
+        let i = self.lods.partition_point(|lod| lod.distance <= distance);
+        self.lods.insert(
+            i,
+            Lod {
+                mesh,
+                ratio,
+                distance,
+            },
+        );
+        self.lod = 0;
+    }
+
+    // Picks the level for the given camera distance. Switching only happens once the distance is
+    // LOD_HYSTERESIS past the switch distance, so a camera hovering around it doesn't make the model pop.
+    pub fn select_lod(&mut self, distance: f32) {
+        let mut lod = self.lod;
+        while lod + 1 < self.lods.len()
+            && distance > self.lods[lod + 1].distance * (1.0 + LOD_HYSTERESIS)
+        {
+            lod += 1;
+        }
+        while lod > 0 && distance < self.lods[lod].distance * (1.0 - LOD_HYSTERESIS) {
+            lod -= 1;
+        }
+        self.lod = lod;
+    }
+
+    pub fn new(
+        renderer: &Renderer,
+        obj: &str,
+        texture: &str,
+        triangulate: bool,
+    ) -> Result<Self, RendererError> {
+        let texture_path = texture.to_owned();
+        let mesh = Mesh::load(renderer, obj, triangulate)?;
+
+        let aabb = Aabb::from_vertices(mesh.vertices());
+        let bounding_sphere = BoundingSphere::from_vertices(mesh.vertices(), &aabb);
+
+        let image = image::open(Path::new(texture)).map_err(|source| RendererError::Image {
+            path: texture.to_owned(),
+            source,
+        })?;
         let pixels = image.to_rgba8().into_raw();
 
         let texture = Texture {
@@ -157,12 +409,15 @@ impl Model {
             renderer.create_model_descriptor_sets(&uniform_buffers, texture_image_view)?;
 
         Ok(Model {
-            _vertices: vertices,
-            indices,
-            vertex_buffer,
-            vertex_buffer_memory,
-            index_buffer,
-            index_buffer_memory,
+            obj_path: obj.to_owned(),
+            texture_path,
+            triangulated: triangulate,
+            lods: vec![Lod {
+                mesh,
+                ratio: 1.0,
+                distance: 0.0,
+            }],
+            lod: 0,
             _texture: texture,
             texture_image,
             texture_image_memory,
@@ -170,12 +425,111 @@ impl Model {
             uniform_buffers,
             uniform_buffers_memory,
             descriptor_sets,
+            aabb,
+            bounding_sphere,
+            name: Path::new(obj)
+                .file_stem()
+                .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned()),
+            material: Material::default(),
             position: Point3 {
                 x: 0.0,
                 y: 0.0,
                 z: 0.0,
             },
             theta: 0.0,
+            scale: 1.0,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_mesh() -> Mesh {
+        Mesh {
+            vertices: vec![],
+            indices: vec![],
+            vertex_buffer: vk::Buffer::null(),
+            vertex_buffer_memory: vk::DeviceMemory::null(),
+            index_buffer: vk::Buffer::null(),
+            index_buffer_memory: vk::DeviceMemory::null(),
+        }
+    }
+
+    // A model without any GPU object, its LODs switching at the given distances.
+    fn model(distances: &[f32]) -> Model {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut model = Model {
+            obj_path: String::new(),
+            texture_path: String::new(),
+            triangulated: true,
+            lods: vec![Lod {
+                mesh: empty_mesh(),
+                ratio: 1.0,
+                distance: 0.0,
+            }],
+            lod: 0,
+            _texture: Texture {
+                width: 0,
+                height: 0,
+                pixels: vec![],
+            },
+            texture_image: vk::Image::null(),
+            texture_image_memory: vk::DeviceMemory::null(),
+            texture_image_view: vk::ImageView::null(),
+            uniform_buffers: vec![],
+            uniform_buffers_memory: vec![],
+            descriptor_sets: vec![],
+            aabb: Aabb {
+                min: origin,
+                max: origin,
+            },
+            bounding_sphere: BoundingSphere {
+                center: origin,
+                radius: 0.0,
+            },
+            name: String::new(),
+            material: Material::default(),
+            position: origin,
+            theta: 0.0,
+            scale: 1.0,
+        };
+        for &distance in distances.iter() {
+            model.add_lod(empty_mesh(), 1.0 / distance, distance);
+        }
+
+        model
+    }
+
+    #[test]
+    fn lods_are_sorted_by_distance() {
+        let model = model(&[20.0, 10.0, 40.0]);
+        let distances = model.lods().iter().map(Lod::distance).collect::<Vec<f32>>();
+        assert_eq!(distances, [0.0, 10.0, 20.0, 40.0]);
+    }
+
+    #[test]
+    fn select_lod_switches_past_the_hysteresis() {
+        let mut model = model(&[10.0, 20.0]);
+
+        model.select_lod(10.5);
+        assert_eq!(model.lod(), 0);
+        model.select_lod(11.5);
+        assert_eq!(model.lod(), 1);
+
+        // Back inside the band, the current level is kept.
+        model.select_lod(9.5);
+        assert_eq!(model.lod(), 1);
+        model.select_lod(10.5);
+        assert_eq!(model.lod(), 1);
+        model.select_lod(8.5);
+        assert_eq!(model.lod(), 0);
+
+        // Several levels at once.
+        model.select_lod(100.0);
+        assert_eq!(model.lod(), 2);
+        model.select_lod(1.0);
+        assert_eq!(model.lod(), 0);
+    }
+}
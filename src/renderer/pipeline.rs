@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use ash::vk;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum VertexLayout {
+    // Vertex buffers of the model meshes, see Vertex.
+    Mesh,
+    // No vertex input, the shader makes up the vertices from their index.
+    Empty,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Topology {
+    Triangles,
+    Lines,
+    Points,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PolygonMode {
+    Fill,
+    // Needs the fillModeNonSolid device feature.
+    Line,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BlendMode {
+    Opaque,
+    // Mixed with what is behind by the alpha of the fragment.
+    Alpha,
+    // Added to what is behind.
+    Additive,
+}
+
+// Everything a graphics pipeline of the scene pass is built from, the render pass, sample count and
+// depth direction coming from the renderer. The shaders use the global and model descriptor sets of
+// shader.vert, and may use the push constants of debug.frag.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(default)]
+pub struct PipelineDesc {
+    // Compiled SPIR-V files.
+    pub vert_shader: String,
+    pub frag_shader: String,
+    pub vertex_layout: VertexLayout,
+    pub topology: Topology,
+    pub polygon_mode: PolygonMode,
+    pub cull_mode: CullMode,
+    pub blend: BlendMode,
+    pub depth_test: bool,
+    pub depth_write: bool,
+    // Pulls the geometry towards the camera, for lines drawn over the faces they belong to.
+    pub depth_bias: bool,
+}
+
+// Lit and textured meshes.
+impl Default for PipelineDesc {
+    fn default() -> Self {
+        PipelineDesc {
+            vert_shader: "shaders/vert.spv".to_owned(),
+            frag_shader: "shaders/frag.spv".to_owned(),
+            vertex_layout: VertexLayout::Mesh,
+            topology: Topology::Triangles,
+            polygon_mode: PolygonMode::Fill,
+            cull_mode: CullMode::Back,
+            blend: BlendMode::Opaque,
+            depth_test: true,
+            depth_write: true,
+            depth_bias: false,
+        }
+    }
+}
+
+impl PipelineDesc {
+    // Short enough for object names and command buffer labels, like `vert frag Fill Back Opaque`.
+    pub(super) fn label(&self) -> String {
+        let stem = |path: &str| {
+            Path::new(path)
+                .file_stem()
+                .map_or(path.to_owned(), |stem| stem.to_string_lossy().into_owned())
+        };
+        format!(
+            "{} {} {:?} {:?} {:?}",
+            stem(&self.vert_shader),
+            stem(&self.frag_shader),
+            self.polygon_mode,
+            self.cull_mode,
+            self.blend
+        )
+    }
+}
+
+impl Topology {
+    pub(super) fn vk(self) -> vk::PrimitiveTopology {
+        match self {
+            Topology::Triangles => vk::PrimitiveTopology::TRIANGLE_LIST,
+            Topology::Lines => vk::PrimitiveTopology::LINE_LIST,
+            Topology::Points => vk::PrimitiveTopology::POINT_LIST,
+        }
+    }
+
+    // Primitives assembled from a list of `vertices`.
+    pub(super) fn primitives(self, vertices: u32) -> u32 {
+        match self {
+            Topology::Triangles => vertices / 3,
+            Topology::Lines => vertices / 2,
+            Topology::Points => vertices,
+        }
+    }
+}
+
+impl PolygonMode {
+    pub(super) fn vk(self) -> vk::PolygonMode {
+        match self {
+            PolygonMode::Fill => vk::PolygonMode::FILL,
+            PolygonMode::Line => vk::PolygonMode::LINE,
+        }
+    }
+}
+
+impl CullMode {
+    pub(super) fn vk(self) -> vk::CullModeFlags {
+        match self {
+            CullMode::None => vk::CullModeFlags::NONE,
+            CullMode::Back => vk::CullModeFlags::BACK,
+            CullMode::Front => vk::CullModeFlags::FRONT,
+        }
+    }
+}
+
+impl BlendMode {
+    pub(super) fn vk(self) -> vk::PipelineColorBlendAttachmentState {
+        let (blend_enable, src_color_blend_factor, dst_color_blend_factor) = match self {
+            BlendMode::Opaque => (vk::FALSE, vk::BlendFactor::ONE, vk::BlendFactor::ZERO),
+            BlendMode::Alpha => (
+                vk::TRUE,
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::Additive => (vk::TRUE, vk::BlendFactor::ONE, vk::BlendFactor::ONE),
+        };
+        vk::PipelineColorBlendAttachmentState {
+            blend_enable,
+            src_color_blend_factor,
+            dst_color_blend_factor,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: if self == BlendMode::Alpha {
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA
+            } else {
+                vk::BlendFactor::ZERO
+            },
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::{error::Error, fs};
+
+use ash::vk;
+use serde::{Deserialize, Serialize};
+
+use super::{Renderer, RendererError};
+
+// Post-process effects, applied in order to the HDR image before it is tonemapped.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum PostEffect {
+    // Parts brighter than the threshold bleed into their surroundings.
+    Bloom { threshold: f32, intensity: f32 },
+    // Fast approximate anti-aliasing, smooths the edges.
+    Fxaa,
+    // Darkens the image past `radius` from the center, 1 being the corners.
+    Vignette { intensity: f32, radius: f32 },
+    // 3D lookup table read from a .cube file, mixed with the original colors by `strength`.
+    ColorGrading { lut: String, strength: f32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PostPass {
+    pub effect: PostEffect,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+// The two full resolution targets the passes alternate between, then the half resolution ones used by
+// bloom.
+pub(super) const POST_TARGETS: usize = 4;
+pub(super) const BLOOM_TARGETS: [usize; 2] = [2, 3];
+
+// Fragment shaders of the post-process steps, drawn with fullscreen.vert.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum PostShader {
+    // Keeps what is above the bloom threshold, at half resolution.
+    Threshold,
+    // Separable gaussian blur.
+    Blur,
+    // Adds the blurred bright parts back to the image.
+    Bloom,
+    Fxaa,
+    Vignette,
+    ColorGrading,
+}
+
+impl PostShader {
+    pub(super) const ALL: [PostShader; 6] = [
+        PostShader::Threshold,
+        PostShader::Blur,
+        PostShader::Bloom,
+        PostShader::Fxaa,
+        PostShader::Vignette,
+        PostShader::ColorGrading,
+    ];
+
+    pub(super) fn path(self) -> &'static str {
+        match self {
+            PostShader::Threshold => "shaders/post_threshold_frag.spv",
+            PostShader::Blur => "shaders/post_blur_frag.spv",
+            PostShader::Bloom => "shaders/post_bloom_frag.spv",
+            PostShader::Fxaa => "shaders/post_fxaa_frag.spv",
+            PostShader::Vignette => "shaders/post_vignette_frag.spv",
+            PostShader::ColorGrading => "shaders/post_color_grading_frag.spv",
+        }
+    }
+}
+
+// An image the post-process steps draw into, sampled by the next one.
+pub struct PostTarget {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    pub framebuffer: vk::Framebuffer,
+    pub extent: vk::Extent2D,
+}
+
+// Lookup table as read from a .cube file, red changing the fastest.
+pub struct CubeLut {
+    size: u32,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    table: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let mut size = None;
+        let mut domain_min = [0.0, 0.0, 0.0];
+        let mut domain_max = [1.0, 1.0, 1.0];
+        let mut table = vec![];
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let keyword = line.split_whitespace().next().unwrap_or_default();
+            let triplet = |values: &str| -> Result<[f32; 3], Box<dyn Error>> {
+                let mut words = values.split_whitespace();
+                let mut triplet = [0.0; 3];
+                for value in triplet.iter_mut() {
+                    *value = match words.next() {
+                        Some(word) => word.parse()?,
+                        None => Err(format!("Missing value in {} !", path))?,
+                    };
+                }
+                Ok(triplet)
+            };
+            let values = &line[keyword.len()..];
+            match keyword {
+                "TITLE" => (),
+                "LUT_3D_SIZE" => size = Some(values.trim().parse::<u32>()?),
+                "LUT_1D_SIZE" => Err(format!("1D lookup tables are not supported in {} !", path))?,
+                "DOMAIN_MIN" => domain_min = triplet(values)?,
+                "DOMAIN_MAX" => domain_max = triplet(values)?,
+                _ => table.push(triplet(line)?),
+            }
+        }
+
+        let size = match size {
+            Some(size) if size >= 2 => size,
+            _ => Err(format!("Missing or invalid LUT_3D_SIZE in {} !", path))?,
+        };
+        if table.len() != (size * size * size) as usize {
+            Err(format!(
+                "{} has {} entries instead of {} !",
+                path,
+                table.len(),
+                size * size * size
+            ))?
+        }
+
+        Ok(CubeLut {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    // Entries packed as A2B10G10R10_UNORM_PACK32, which is always filterable.
+    pub fn packed(&self) -> Vec<u32> {
+        self.table
+            .iter()
+            .map(|rgb| {
+                let [r, g, b] = rgb.map(|c| (c.clamp(0.0, 1.0) * 1023.0).round() as u32);
+                (3 << 30) | (b << 20) | (g << 10) | r
+            })
+            .collect()
+    }
+}
+
+// A lookup table uploaded for the color grading passes using it.
+pub struct Lut {
+    path: String,
+    size: u32,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    descriptor_set: vk::DescriptorSet,
+}
+
+impl Lut {
+    pub fn load(renderer: &Renderer, path: &str) -> Result<Self, RendererError> {
+        let cube = CubeLut::load(path).map_err(|err| RendererError::Lut {
+            path: path.to_owned(),
+            message: err.to_string(),
+        })?;
+        let (image, memory, view) = renderer.create_lut_image(&cube)?;
+        let descriptor_set = renderer.create_post_descriptor_set(view)?;
+
+        Ok(Lut {
+            path: path.to_owned(),
+            size: cube.size(),
+            domain_min: cube.domain_min,
+            domain_max: cube.domain_max,
+            image,
+            memory,
+            view,
+            descriptor_set,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn domain_min(&self) -> [f32; 3] {
+        self.domain_min
+    }
+
+    pub fn domain_max(&self) -> [f32; 3] {
+        self.domain_max
+    }
+
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn descriptor_set(&self) -> vk::DescriptorSet {
+        self.descriptor_set
+    }
+}
@@ -0,0 +1,414 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    fs, mem,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use ash::{vk, Device, Instance};
+use log::{debug, trace, warn};
+
+use super::{RendererError, MAX_FRAMES_IN_FLIGHT};
+
+// Timestamps each frame in flight can write, two per scope.
+const MAX_QUERIES: u32 = 128;
+// Frames the report is made from.
+const HISTORY_FRAMES: usize = 300;
+
+// Time spent in a pass or draw group. Nested scopes are named after their parents, like
+// `Scene/vert frag Fill Back Opaque`.
+#[derive(Clone, Debug)]
+pub struct PassTiming {
+    pub name: String,
+    // Recording the commands, or running the code for the scopes only timed on the CPU.
+    pub cpu: Duration,
+    // None without timestamp support, for CPU only scopes, or past MAX_QUERIES.
+    pub gpu: Option<Duration>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FrameTimings {
+    // Time spent in draw_frame.
+    pub cpu: Duration,
+    // From the first timestamp of the frame to the last.
+    pub gpu: Option<Duration>,
+    pub passes: Vec<PassTiming>,
+}
+
+// Milliseconds over the frames a pass was in.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimingStats {
+    pub average: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl TimingStats {
+    fn new(mut samples: Vec<f64>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_by(|a, b| a.total_cmp(b));
+        // Nearest rank.
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+
+        Some(TimingStats {
+            average: samples.iter().sum::<f64>() / samples.len() as f64,
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReportRow {
+    pub name: String,
+    pub frames: usize,
+    pub cpu: TimingStats,
+    pub gpu: Option<TimingStats>,
+}
+
+// Rolling statistics over the last HISTORY_FRAMES frames, the first row being the whole frame. A pass
+// drawn more than once in a frame, like the bloom blurs, counts as the sum of its times.
+#[derive(Clone, Debug, Default)]
+pub struct ProfileReport {
+    pub frames: usize,
+    pub rows: Vec<ReportRow>,
+}
+
+impl ProfileReport {
+    pub fn to_csv(&self) -> String {
+        let mut csv = "pass,frames,cpu_avg_ms,cpu_p50_ms,cpu_p95_ms,cpu_p99_ms,cpu_max_ms,\
+                       gpu_avg_ms,gpu_p50_ms,gpu_p95_ms,gpu_p99_ms,gpu_max_ms\n"
+            .to_owned();
+        for row in self.rows.iter() {
+            let stats = |stats: Option<TimingStats>| match stats {
+                Some(s) => format!(
+                    "{:.4},{:.4},{:.4},{:.4},{:.4}",
+                    s.average, s.p50, s.p95, s.p99, s.max
+                ),
+                None => ",,,,".to_owned(),
+            };
+            let _ = writeln!(
+                csv,
+                "\"{}\",{},{},{}",
+                row.name.replace('"', "\"\""),
+                row.frames,
+                stats(Some(row.cpu)),
+                stats(row.gpu)
+            );
+        }
+
+        csv
+    }
+
+    pub fn save_csv(&self, path: &Path) -> Result<(), RendererError> {
+        fs::write(path, self.to_csv())?;
+
+        Ok(())
+    }
+}
+
+struct Scope {
+    name: String,
+    depth: usize,
+    start: Instant,
+    cpu: Duration,
+    // Command buffer and index of the begin timestamp in the frame queries, the end one following it.
+    query: Option<(vk::CommandBuffer, u32)>,
+}
+
+#[derive(Default)]
+struct FrameRecord {
+    scopes: Vec<Scope>,
+    // Indices of the scopes not ended yet, innermost last.
+    open: Vec<usize>,
+    queries: u32,
+    start: Option<Instant>,
+    cpu: Duration,
+}
+
+// CPU and GPU times of the passes. The timestamps of a frame are read back once the fence of its frame
+// in flight is waited on, so the GPU is never waited for and the times are MAX_FRAMES_IN_FLIGHT frames
+// late.
+pub struct Profiler {
+    // Null when the graphics queue can't write timestamps, only the CPU times are measured then.
+    query_pool: vk::QueryPool,
+    // Nanoseconds per timestamp tick.
+    timestamp_period: f32,
+    timestamp_mask: u64,
+    frame: usize,
+    recording: FrameRecord,
+    // Submitted frames not read back yet, by frame in flight.
+    pending: Vec<Option<FrameRecord>>,
+    history: VecDeque<FrameTimings>,
+}
+
+impl Profiler {
+    pub fn new(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &Device,
+        queue_family: u32,
+    ) -> Result<Self, RendererError> {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let valid_bits = queue_families[queue_family as usize].timestamp_valid_bits;
+        let query_pool = if valid_bits == 0 {
+            warn!("Graphics queue without timestamps, only CPU times are measured.");
+            vk::QueryPool::null()
+        } else {
+            let create_info = vk::QueryPoolCreateInfo {
+                query_type: vk::QueryType::TIMESTAMP,
+                query_count: MAX_QUERIES * MAX_FRAMES_IN_FLIGHT as u32,
+                ..Default::default()
+            };
+            let query_pool = unsafe { device.create_query_pool(&create_info, None) }?;
+            debug!("Timestamp query pool created.");
+            query_pool
+        };
+
+        Ok(Profiler {
+            query_pool,
+            timestamp_period: properties.limits.timestamp_period,
+            timestamp_mask: if valid_bits >= 64 {
+                u64::MAX
+            } else {
+                (1 << valid_bits) - 1
+            },
+            frame: 0,
+            recording: FrameRecord::default(),
+            pending: (0..MAX_FRAMES_IN_FLIGHT).map(|_| None).collect(),
+            history: VecDeque::new(),
+        })
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        if self.query_pool != vk::QueryPool::null() {
+            unsafe { device.destroy_query_pool(self.query_pool, None) };
+            debug!("Timestamp query pool dropped.");
+        }
+    }
+
+    pub fn query_pool(&self) -> vk::QueryPool {
+        self.query_pool
+    }
+
+    // Latest frame read back.
+    pub fn last_frame(&self) -> Option<&FrameTimings> {
+        self.history.back()
+    }
+
+    // Starts timing a frame drawn with the given frame in flight.
+    pub fn begin_frame(&mut self, frame: usize) {
+        self.frame = frame;
+        self.recording = FrameRecord {
+            start: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+
+    // The frame is submitted, its timestamps are read back by the next collect of its frame in flight.
+    pub fn end_frame(&mut self) {
+        let mut record = mem::take(&mut self.recording);
+        record.cpu = record.start.map_or(Duration::ZERO, |start| start.elapsed());
+        self.pending[self.frame] = Some(record);
+    }
+
+    // Reads back the last frame submitted with the current frame in flight, whose fence has to be
+    // signaled.
+    pub fn collect(&mut self, device: &Device) {
+        let record = match self.pending[self.frame].take() {
+            Some(record) => record,
+            None => return,
+        };
+        let mut timestamps = vec![0u64; record.queries as usize];
+        let gpu_done = record.queries > 0
+            && match unsafe {
+                device.get_query_pool_results(
+                    self.query_pool,
+                    self.first_query(),
+                    record.queries,
+                    &mut timestamps,
+                    vk::QueryResultFlags::TYPE_64,
+                )
+            } {
+                Ok(()) => true,
+                // Not waited for, the GPU times of the frame are left out.
+                Err(err) => {
+                    trace!("Timestamps not available : {}", err);
+                    false
+                }
+            };
+        let ticks_to_duration = |begin: u32, end: u32| {
+            let ticks = timestamps[end as usize].wrapping_sub(timestamps[begin as usize])
+                & self.timestamp_mask;
+            Duration::from_nanos((ticks as f64 * self.timestamp_period as f64) as u64)
+        };
+
+        let mut first_query = None;
+        let mut last_query = None;
+        let passes = record
+            .scopes
+            .iter()
+            .map(|scope| {
+                let query = scope.query.filter(|_| gpu_done).map(|(_, query)| query);
+                if let (Some(query), 0) = (query, scope.depth) {
+                    first_query = first_query.or(Some(query));
+                    last_query = Some(query + 1);
+                }
+                PassTiming {
+                    name: scope.name.clone(),
+                    cpu: scope.cpu,
+                    gpu: query.map(|query| ticks_to_duration(query, query + 1)),
+                }
+            })
+            .collect();
+        let gpu = match (first_query, last_query) {
+            (Some(first), Some(last)) => Some(ticks_to_duration(first, last)),
+            _ => None,
+        };
+
+        self.history.push_back(FrameTimings {
+            cpu: record.cpu,
+            gpu,
+            passes,
+        });
+        if self.history.len() > HISTORY_FRAMES {
+            self.history.pop_front();
+        }
+    }
+
+    // Has to be recorded before the first scope of the command buffer.
+    pub fn reset_queries(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        if self.query_pool != vk::QueryPool::null() {
+            unsafe {
+                device.cmd_reset_query_pool(
+                    command_buffer,
+                    self.query_pool,
+                    self.first_query(),
+                    MAX_QUERIES,
+                )
+            };
+        }
+    }
+
+    // Without a command buffer the scope is only timed on the CPU.
+    pub fn begin(
+        &mut self,
+        device: &Device,
+        command_buffer: Option<vk::CommandBuffer>,
+        name: &str,
+    ) {
+        let first_query = self.first_query();
+        let record = &mut self.recording;
+        let name = match record.open.last() {
+            Some(&parent) => format!("{}/{}", record.scopes[parent].name, name),
+            None => name.to_owned(),
+        };
+        let query = match command_buffer {
+            Some(command_buffer)
+                if self.query_pool != vk::QueryPool::null()
+                    && record.queries + 2 <= MAX_QUERIES =>
+            {
+                let query = record.queries;
+                record.queries += 2;
+                unsafe {
+                    device.cmd_write_timestamp(
+                        command_buffer,
+                        vk::PipelineStageFlags::TOP_OF_PIPE,
+                        self.query_pool,
+                        first_query + query,
+                    )
+                };
+                Some((command_buffer, query))
+            }
+            _ => None,
+        };
+        record.open.push(record.scopes.len());
+        record.scopes.push(Scope {
+            name,
+            depth: record.open.len() - 1,
+            start: Instant::now(),
+            cpu: Duration::ZERO,
+            query,
+        });
+    }
+
+    pub fn end(&mut self, device: &Device) {
+        let first_query = self.first_query();
+        let scope = match self.recording.open.pop() {
+            Some(index) => &mut self.recording.scopes[index],
+            None => return,
+        };
+        scope.cpu = scope.start.elapsed();
+        if let Some((command_buffer, query)) = scope.query {
+            unsafe {
+                device.cmd_write_timestamp(
+                    command_buffer,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    self.query_pool,
+                    first_query + query + 1,
+                )
+            };
+        }
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        let frame = |timings: &FrameTimings| (timings.cpu, timings.gpu);
+        let mut rows = vec![Self::row("Frame", self.history.iter().map(frame).collect())];
+
+        let mut names: Vec<&str> = vec![];
+        for timings in self.history.iter() {
+            for pass in timings.passes.iter() {
+                if !names.contains(&pass.name.as_str()) {
+                    names.push(&pass.name);
+                }
+            }
+        }
+        for name in names {
+            let samples = self
+                .history
+                .iter()
+                .filter_map(|timings| {
+                    let mut passes = timings.passes.iter().filter(|pass| pass.name == name);
+                    let first = passes.next()?;
+                    Some(passes.fold((first.cpu, first.gpu), |(cpu, gpu), pass| {
+                        (cpu + pass.cpu, gpu.zip(pass.gpu).map(|(a, b)| a + b))
+                    }))
+                })
+                .collect();
+            rows.push(Self::row(name, samples));
+        }
+
+        ProfileReport {
+            frames: self.history.len(),
+            rows,
+        }
+    }
+
+    fn row(name: &str, samples: Vec<(Duration, Option<Duration>)>) -> ReportRow {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        ReportRow {
+            name: name.to_owned(),
+            frames: samples.len(),
+            cpu: TimingStats::new(samples.iter().map(|&(cpu, _)| ms(cpu)).collect())
+                .unwrap_or_default(),
+            gpu: TimingStats::new(samples.iter().filter_map(|&(_, gpu)| gpu.map(ms)).collect()),
+        }
+    }
+
+    fn first_query(&self) -> u32 {
+        self.frame as u32 * MAX_QUERIES
+    }
+}
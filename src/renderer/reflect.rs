@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+
+use ash::vk;
+use naga::{
+    front::spv, AddressSpace, Binding, ImageClass, Module, ScalarKind, ShaderStage, TypeInner,
+};
+
+use super::RendererError;
+
+// The resources and inputs of a shader, read from its SPIR-V.
+pub struct ShaderReflection {
+    pub stage: vk::ShaderStageFlags,
+    // Descriptor type by (set, binding).
+    pub bindings: BTreeMap<(u32, u32), vk::DescriptorType>,
+    // Size of the push constant block, 0 without one.
+    pub push_constant_size: u32,
+    // Vertex shader inputs by location.
+    pub inputs: BTreeMap<u32, vk::Format>,
+}
+
+impl ShaderReflection {
+    pub fn new(path: &str, words: &[u32]) -> Result<Self, RendererError> {
+        let options = spv::Options {
+            adjust_coordinate_space: false,
+            ..Default::default()
+        };
+        let module = spv::Frontend::new(words.iter().copied(), &options)
+            .parse()
+            .map_err(|err| RendererError::shader(path, err))?;
+        let entry_point = match module.entry_points.as_slice() {
+            [entry_point] => entry_point,
+            _ => Err(RendererError::shader(
+                path,
+                "expected a single entry point !",
+            ))?,
+        };
+        let stage = match entry_point.stage {
+            ShaderStage::Vertex => vk::ShaderStageFlags::VERTEX,
+            ShaderStage::Fragment => vk::ShaderStageFlags::FRAGMENT,
+            ShaderStage::Compute => vk::ShaderStageFlags::COMPUTE,
+            _ => Err(RendererError::shader(path, "unsupported shader stage !"))?,
+        };
+
+        let mut bindings = BTreeMap::new();
+        let mut push_constant_size = 0;
+        for (_, variable) in module.global_variables.iter() {
+            let inner = &module.types[variable.ty].inner;
+            if variable.space == AddressSpace::PushConstant {
+                push_constant_size = inner.size(module.to_ctx());
+                continue;
+            }
+            let binding = match &variable.binding {
+                Some(binding) => binding,
+                None => continue,
+            };
+            let descriptor_type = match (variable.space, inner) {
+                (AddressSpace::Uniform, _) => vk::DescriptorType::UNIFORM_BUFFER,
+                (AddressSpace::Storage { .. }, _) => vk::DescriptorType::STORAGE_BUFFER,
+                (_, TypeInner::Sampler { .. }) => vk::DescriptorType::SAMPLER,
+                (_, TypeInner::Image { class, .. }) => match class {
+                    ImageClass::Storage { .. } => vk::DescriptorType::STORAGE_IMAGE,
+                    _ => vk::DescriptorType::SAMPLED_IMAGE,
+                },
+                _ => Err(RendererError::shader(
+                    path,
+                    format!(
+                        "unsupported resource at set {} binding {} !",
+                        binding.group, binding.binding
+                    ),
+                ))?,
+            };
+            bindings.insert((binding.group, binding.binding), descriptor_type);
+        }
+
+        let mut inputs = BTreeMap::new();
+        if entry_point.stage == ShaderStage::Vertex {
+            for argument in entry_point.function.arguments.iter() {
+                // WGSL inputs may be gathered in a struct.
+                let members = match (&argument.binding, &module.types[argument.ty].inner) {
+                    (None, TypeInner::Struct { members, .. }) => members
+                        .iter()
+                        .map(|member| (member.binding.clone(), member.ty))
+                        .collect(),
+                    _ => vec![(argument.binding.clone(), argument.ty)],
+                };
+                for (binding, ty) in members {
+                    if let Some(Binding::Location { location, .. }) = binding {
+                        let format = match vertex_format(&module, &module.types[ty].inner) {
+                            Some(format) => format,
+                            None => Err(RendererError::shader(
+                                path,
+                                format!("unsupported type for input location {} !", location),
+                            ))?,
+                        };
+                        inputs.insert(location, format);
+                    }
+                }
+            }
+        }
+
+        Ok(ShaderReflection {
+            stage,
+            bindings,
+            push_constant_size,
+            inputs,
+        })
+    }
+
+    // Every resource has to be in the layout the pipeline is made with.
+    pub fn check_layout(&self, path: &str, layout: &LayoutReflection) -> Result<(), RendererError> {
+        for (&(set, binding), &descriptor_type) in self.bindings.iter() {
+            match layout.bindings.get(&(set, binding)) {
+                Some(&(expected, stages)) if expected == descriptor_type => {
+                    if !stages.contains(self.stage) {
+                        Err(RendererError::shader(
+                            path,
+                            format!(
+                                "set {} binding {} isn't visible to {:?} shaders !",
+                                set, binding, self.stage
+                            ),
+                        ))?
+                    }
+                }
+                Some(&(expected, _)) => Err(RendererError::shader(
+                    path,
+                    format!(
+                        "set {} binding {} is a {:?} instead of a {:?} !",
+                        set, binding, descriptor_type, expected
+                    ),
+                ))?,
+                None => Err(RendererError::shader(
+                    path,
+                    format!(
+                        "set {} binding {} isn't in the pipeline layout !",
+                        set, binding
+                    ),
+                ))?,
+            }
+        }
+        if self.push_constant_size > 0 {
+            match layout.push_constants {
+                Some(range)
+                    if range.stage_flags.contains(self.stage)
+                        && range.size >= self.push_constant_size => {}
+                _ => Err(RendererError::shader(
+                    path,
+                    format!(
+                        "{} bytes of push constants aren't in the pipeline layout !",
+                        self.push_constant_size
+                    ),
+                ))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // The vertex inputs have to match the attributes of the vertex buffers, location by location.
+    pub fn check_inputs(
+        &self,
+        path: &str,
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Result<(), RendererError> {
+        for (&location, &format) in self.inputs.iter() {
+            match attributes.iter().find(|a| a.location == location) {
+                Some(attribute) if attribute.format == format => (),
+                Some(attribute) => Err(RendererError::shader(
+                    path,
+                    format!(
+                        "input location {} is {:?} but the vertex attribute is {:?} !",
+                        location, format, attribute.format
+                    ),
+                ))?,
+                None => Err(RendererError::shader(
+                    path,
+                    format!("no vertex attribute for input location {} !", location),
+                ))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Descriptor bindings and push constants of a set of shaders sharing a pipeline layout.
+pub struct LayoutReflection {
+    pub bindings: BTreeMap<(u32, u32), (vk::DescriptorType, vk::ShaderStageFlags)>,
+    pub push_constants: Option<vk::PushConstantRange>,
+}
+
+impl LayoutReflection {
+    pub fn merge(shaders: &[(&str, ShaderReflection)]) -> Result<Self, RendererError> {
+        let mut bindings: BTreeMap<(u32, u32), (vk::DescriptorType, vk::ShaderStageFlags)> =
+            BTreeMap::new();
+        let mut push_constants: Option<vk::PushConstantRange> = None;
+        for (path, shader) in shaders.iter() {
+            for (&key, &descriptor_type) in shader.bindings.iter() {
+                match bindings.get_mut(&key) {
+                    Some((expected, stages)) if *expected == descriptor_type => {
+                        *stages |= shader.stage
+                    }
+                    Some((expected, _)) => Err(RendererError::shader(
+                        path,
+                        format!(
+                            "set {} binding {} is a {:?}, but a {:?} in another shader !",
+                            key.0, key.1, descriptor_type, expected
+                        ),
+                    ))?,
+                    None => {
+                        bindings.insert(key, (descriptor_type, shader.stage));
+                    }
+                }
+            }
+            if shader.push_constant_size > 0 {
+                let range = push_constants.get_or_insert(vk::PushConstantRange::default());
+                range.stage_flags |= shader.stage;
+                range.size = range.size.max(shader.push_constant_size);
+            }
+        }
+
+        Ok(LayoutReflection {
+            bindings,
+            push_constants,
+        })
+    }
+
+    // The renderer fills the descriptor sets itself, the shaders can't ask for more or other resources.
+    pub fn check_bindings(
+        &self,
+        expected: &[((u32, u32), vk::DescriptorType)],
+    ) -> Result<(), RendererError> {
+        for (&(set, binding), &(descriptor_type, _)) in self.bindings.iter() {
+            match expected.iter().find(|(key, _)| *key == (set, binding)) {
+                Some(&(_, expected)) if expected == descriptor_type => (),
+                Some(&(_, expected)) => Err(format!(
+                    "Set {} binding {} is a {:?} in the shaders but a {:?} in the renderer !",
+                    set, binding, descriptor_type, expected
+                ))?,
+                None => Err(format!(
+                    "Set {} binding {} of the shaders isn't filled by the renderer !",
+                    set, binding
+                ))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn set_bindings(&self, set: u32) -> Vec<vk::DescriptorSetLayoutBinding> {
+        self.bindings
+            .iter()
+            .filter(|((s, _), _)| *s == set)
+            .map(|(&(_, binding), &(descriptor_type, stage_flags))| {
+                vk::DescriptorSetLayoutBinding {
+                    binding,
+                    descriptor_type,
+                    descriptor_count: 1,
+                    stage_flags,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+fn vertex_format(module: &Module, inner: &TypeInner) -> Option<vk::Format> {
+    let (count, scalar) = match *inner {
+        TypeInner::Scalar(scalar) => (1, scalar),
+        TypeInner::Vector { size, scalar } => (size as u32, scalar),
+        _ => return None,
+    };
+    if inner.size(module.to_ctx()) != count * 4 {
+        return None;
+    }
+    let formats = match scalar.kind {
+        ScalarKind::Float => [
+            vk::Format::R32_SFLOAT,
+            vk::Format::R32G32_SFLOAT,
+            vk::Format::R32G32B32_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        ScalarKind::Sint => [
+            vk::Format::R32_SINT,
+            vk::Format::R32G32_SINT,
+            vk::Format::R32G32B32_SINT,
+            vk::Format::R32G32B32A32_SINT,
+        ],
+        ScalarKind::Uint => [
+            vk::Format::R32_UINT,
+            vk::Format::R32G32_UINT,
+            vk::Format::R32G32B32_UINT,
+            vk::Format::R32G32B32A32_UINT,
+        ],
+        _ => return None,
+    };
+
+    Some(formats[count as usize - 1])
+}
@@ -0,0 +1,445 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use super::types::Vertex;
+
+// Symmetric 4x4 error quadric (Garland & Heckbert), only the upper triangle is stored.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(a: f64, b: f64, c: f64, d: f64, weight: f64) -> Self {
+        Quadric([
+            a * a * weight,
+            a * b * weight,
+            a * c * weight,
+            a * d * weight,
+            b * b * weight,
+            b * c * weight,
+            b * d * weight,
+            c * c * weight,
+            c * d * weight,
+            d * d * weight,
+        ])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, p: [f32; 3]) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p[0] as f64, p[1] as f64, p[2] as f64);
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed so that the BinaryHeap pops the cheapest collapse first.
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// Simplification works on positions, so that texture seams, where Model::new splits vertices, can be
+// collapsed like any other edge. Each triangle corner keeps the index of the vertex it came from (its
+// wedge), which carries the texture coordinates.
+struct Simplifier<'a> {
+    vertices: &'a [Vertex],
+    positions: Vec<[f32; 3]>,
+    position_wedges: Vec<Vec<u32>>,
+    triangles: Vec<[u32; 3]>,
+    wedges: Vec<[u32; 3]>,
+    triangle_removed: Vec<bool>,
+    position_triangles: Vec<Vec<usize>>,
+    position_removed: Vec<bool>,
+    position_locked: Vec<bool>,
+    versions: Vec<u32>,
+    quadrics: Vec<Quadric>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(vertices: &'a [Vertex], indices: &[u32]) -> Self {
+        let mut positions = vec![];
+        let mut position_wedges: Vec<Vec<u32>> = vec![];
+        let mut position_of = Vec::with_capacity(vertices.len());
+        let mut unique_positions = HashMap::new();
+        for (i, vertex) in vertices.iter().enumerate() {
+            let key = vertex.pos.map(f32::to_bits);
+            let p = *unique_positions.entry(key).or_insert_with(|| {
+                positions.push(vertex.pos);
+                position_wedges.push(vec![]);
+                positions.len() - 1
+            });
+            position_wedges[p].push(i as u32);
+            position_of.push(p as u32);
+        }
+
+        let wedges = indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect::<Vec<[u32; 3]>>();
+        let triangles = wedges
+            .iter()
+            .map(|t| t.map(|v| position_of[v as usize]))
+            .collect::<Vec<[u32; 3]>>();
+
+        let mut position_triangles = vec![vec![]; positions.len()];
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut edge_count = HashMap::new();
+        for (i, t) in triangles.iter().enumerate() {
+            let p0 = positions[t[0] as usize];
+            let p1 = positions[t[1] as usize];
+            let p2 = positions[t[2] as usize];
+            let n = cross(sub(p1, p0), sub(p2, p0));
+            let length = dot(n, n).sqrt();
+            if length > 0.0 {
+                let (a, b, c) = (
+                    (n[0] / length) as f64,
+                    (n[1] / length) as f64,
+                    (n[2] / length) as f64,
+                );
+                let d = -(a * p0[0] as f64 + b * p0[1] as f64 + c * p0[2] as f64);
+                // Weighted by the triangle area so that small slivers don't dominate the error.
+                let quadric = Quadric::from_plane(a, b, c, d, length as f64 * 0.5);
+                for &p in t.iter() {
+                    quadrics[p as usize].add(&quadric);
+                }
+            }
+            for k in 0..3 {
+                position_triangles[t[k] as usize].push(i);
+                let (a, b) = (t[k], t[(k + 1) % 3]);
+                *edge_count.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+
+        // Edges used by a single triangle are on the mesh border, moving their ends would shrink the outline.
+        let mut position_locked = vec![false; positions.len()];
+        for (&(a, b), &count) in edge_count.iter() {
+            if count == 1 {
+                position_locked[a as usize] = true;
+                position_locked[b as usize] = true;
+            }
+        }
+
+        let mut simplifier = Simplifier {
+            vertices,
+            position_removed: vec![false; positions.len()],
+            versions: vec![0; positions.len()],
+            positions,
+            position_wedges,
+            triangle_removed: vec![false; triangles.len()],
+            triangles,
+            wedges,
+            position_triangles,
+            position_locked,
+            quadrics,
+            heap: BinaryHeap::new(),
+        };
+        for &(a, b) in edge_count.keys() {
+            simplifier.push_edge(a, b);
+        }
+
+        simplifier
+    }
+
+    // Positions are only ever moved onto one of the edge ends, so no new vertices have to be made up.
+    fn push_edge(&mut self, a: u32, b: u32) {
+        let mut quadric = self.quadrics[a as usize];
+        quadric.add(&self.quadrics[b as usize]);
+
+        let mut candidates = vec![];
+        if !self.position_locked[a as usize] {
+            candidates.push((quadric.error(self.positions[b as usize]), a, b));
+        }
+        if !self.position_locked[b as usize] {
+            candidates.push((quadric.error(self.positions[a as usize]), b, a));
+        }
+
+        if let Some(&(cost, from, to)) = candidates.iter().min_by(|x, y| x.0.total_cmp(&y.0)) {
+            self.heap.push(Collapse {
+                cost,
+                from,
+                to,
+                from_version: self.versions[from as usize],
+                to_version: self.versions[to as usize],
+            });
+        }
+    }
+
+    // Rejects collapses that would flip a triangle around.
+    fn is_valid(&self, from: u32, to: u32) -> bool {
+        let target = self.positions[to as usize];
+        self.position_triangles[from as usize]
+            .iter()
+            .map(|&i| self.triangles[i])
+            .filter(|t| !t.contains(&to))
+            .all(|t| {
+                let p = t.map(|v| self.positions[v as usize]);
+                let moved = t.map(|v| {
+                    if v == from {
+                        target
+                    } else {
+                        self.positions[v as usize]
+                    }
+                });
+                let before = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+                let after = cross(sub(moved[1], moved[0]), sub(moved[2], moved[0]));
+                dot(before, after) > 0.0
+            })
+    }
+
+    // The wedge at `to` replacing `wedge` once `from` is collapsed. Triangles along the collapsed edge tell
+    // which wedges belong to the same side of a seam, otherwise the closest texture coordinates win.
+    fn remap_wedge(&self, wedge: u32, edge_triangles: &[usize], from: u32, to: u32) -> u32 {
+        for &i in edge_triangles.iter() {
+            let t = self.triangles[i];
+            let w = self.wedges[i];
+            let from_corner = t.iter().position(|&p| p == from);
+            let to_corner = t.iter().position(|&p| p == to);
+            if let (Some(f), Some(k)) = (from_corner, to_corner) {
+                if w[f] == wedge {
+                    return w[k];
+                }
+            }
+        }
+
+        let tex_coord = self.vertices[wedge as usize].tex_coord;
+        *self.position_wedges[to as usize]
+            .iter()
+            .min_by(|&&a, &&b| {
+                let da = self.vertices[a as usize].tex_coord;
+                let db = self.vertices[b as usize].tex_coord;
+                let distance =
+                    |t: [f32; 2]| (t[0] - tex_coord[0]).powi(2) + (t[1] - tex_coord[1]).powi(2);
+                distance(da).total_cmp(&distance(db))
+            })
+            .expect("Position without vertices !")
+    }
+
+    fn collapse(&mut self, from: u32, to: u32) -> usize {
+        let triangles = std::mem::take(&mut self.position_triangles[from as usize]);
+        let (edge_triangles, moved_triangles): (Vec<usize>, Vec<usize>) = triangles
+            .into_iter()
+            .partition(|&i| self.triangles[i].contains(&to));
+
+        for &i in moved_triangles.iter() {
+            for k in 0..3 {
+                if self.triangles[i][k] == from {
+                    self.wedges[i][k] =
+                        self.remap_wedge(self.wedges[i][k], &edge_triangles, from, to);
+                    self.triangles[i][k] = to;
+                }
+            }
+            self.position_triangles[to as usize].push(i);
+        }
+
+        for &i in edge_triangles.iter() {
+            self.triangle_removed[i] = true;
+            for p in self.triangles[i] {
+                self.position_triangles[p as usize].retain(|&t| t != i);
+            }
+        }
+
+        let quadric = self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&quadric);
+        self.position_removed[from as usize] = true;
+        self.versions[to as usize] += 1;
+
+        let mut neighbours = self.position_triangles[to as usize]
+            .iter()
+            .flat_map(|&i| self.triangles[i])
+            .filter(|&p| p != to)
+            .collect::<Vec<u32>>();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        for p in neighbours {
+            self.push_edge(to, p);
+        }
+
+        edge_triangles.len()
+    }
+
+    fn run(mut self, target_triangle_count: usize) -> (Vec<Vertex>, Vec<u32>) {
+        let mut triangle_count = self.triangles.len();
+        while triangle_count > target_triangle_count {
+            let collapse = match self.heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            let (from, to) = (collapse.from, collapse.to);
+            if self.position_removed[from as usize]
+                || self.position_removed[to as usize]
+                || self.versions[from as usize] != collapse.from_version
+                || self.versions[to as usize] != collapse.to_version
+                || !self.is_valid(from, to)
+            {
+                continue;
+            }
+            triangle_count -= self.collapse(from, to);
+        }
+
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for (w, _) in self
+            .wedges
+            .iter()
+            .zip(self.triangle_removed.iter())
+            .filter(|(_, &removed)| !removed)
+        {
+            for &v in w.iter() {
+                if remap[v as usize] == u32::MAX {
+                    remap[v as usize] = vertices.len() as u32;
+                    vertices.push(self.vertices[v as usize]);
+                }
+                indices.push(remap[v as usize]);
+            }
+        }
+
+        (vertices, indices)
+    }
+}
+
+// Quadric edge collapse simplification, reducing the mesh to roughly `ratio` of its triangles.
+// Border vertices are kept in place, so meshes with a lot of open edges may not reach the target.
+pub fn simplify(vertices: &[Vertex], indices: &[u32], ratio: f32) -> (Vec<Vertex>, Vec<u32>) {
+    let target_triangle_count = ((indices.len() / 3) as f32 * ratio.clamp(0.0, 1.0)) as usize;
+    Simplifier::new(vertices, indices).run(target_triangle_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A grid of n by n quads over [0, n]², facing +z, with the given height.
+    fn grid(n: u32, height: impl Fn(f32, f32) -> f32) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = vec![];
+        for j in 0..=n {
+            for i in 0..=n {
+                let (x, y) = (i as f32, j as f32);
+                vertices.push(Vertex {
+                    pos: [x, y, height(x, y)],
+                    color: [1.0, 1.0, 1.0],
+                    tex_coord: [x / n as f32, y / n as f32],
+                    normal: [0.0, 0.0, 1.0],
+                });
+            }
+        }
+        let index = |i: u32, j: u32| j * (n + 1) + i;
+        let mut indices = vec![];
+        for j in 0..n {
+            for i in 0..n {
+                let (a, b, c, d) = (
+                    index(i, j),
+                    index(i + 1, j),
+                    index(i + 1, j + 1),
+                    index(i, j + 1),
+                );
+                indices.extend_from_slice(&[a, b, c, a, c, d]);
+            }
+        }
+
+        (vertices, indices)
+    }
+
+    fn flat(_: f32, _: f32) -> f32 {
+        0.0
+    }
+
+    fn bumpy(x: f32, y: f32) -> f32 {
+        (x * 0.5).sin() * (y * 0.5).cos()
+    }
+
+    #[test]
+    fn reaches_target_triangle_count() {
+        let (vertices, indices) = grid(16, bumpy);
+        let (_, simplified) = simplify(&vertices, &indices, 0.25);
+        let triangle_count = simplified.len() / 3;
+        // A collapse removes up to two triangles.
+        assert!((127..=128).contains(&triangle_count), "{}", triangle_count);
+    }
+
+    #[test]
+    fn keeps_border_vertices() {
+        let n = 16;
+        let (vertices, indices) = grid(n, bumpy);
+        // Too low to be reached with the border kept.
+        let (simplified, _) = simplify(&vertices, &indices, 0.05);
+
+        for vertex in vertices.iter() {
+            let [x, y, _] = vertex.pos;
+            if x == 0.0 || y == 0.0 || x == n as f32 || y == n as f32 {
+                assert!(simplified.iter().any(|v| v.pos == vertex.pos));
+            }
+        }
+        for vertex in simplified.iter() {
+            assert!(vertices.iter().any(|v| v.pos == vertex.pos));
+        }
+    }
+
+    #[test]
+    fn doesnt_flip_triangles() {
+        let (vertices, indices) = grid(16, flat);
+        let (simplified, simplified_indices) = simplify(&vertices, &indices, 0.05);
+
+        assert!(simplified_indices.len() < indices.len());
+        for t in simplified_indices.chunks_exact(3) {
+            let p = [0, 1, 2].map(|k| simplified[t[k] as usize].pos);
+            let normal = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+            assert!(normal[2] > 0.0, "{:?}", p);
+        }
+    }
+}
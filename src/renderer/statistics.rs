@@ -0,0 +1,115 @@
+use ash::{vk, Device};
+use log::{debug, trace, warn};
+
+use super::{FrameStats, RendererError, MAX_FRAMES_IN_FLIGHT};
+
+// Written in this order, by increasing bit.
+const STATISTICS: [vk::QueryPipelineStatisticFlags; 4] = [
+    vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES,
+    vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES,
+    vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS,
+];
+
+// A pipeline statistics query around each command buffer, one per frame in flight. Like the profiler,
+// a frame is read back once its fence is waited on, the stats counted while recording it being kept
+// until then.
+pub struct PipelineStatistics {
+    // Null without the pipelineStatisticsQuery feature, the counts then being those of the draw calls.
+    query_pool: vk::QueryPool,
+    pending: Vec<Option<FrameStats>>,
+}
+
+impl PipelineStatistics {
+    pub fn new(device: &Device, supported: bool) -> Result<Self, RendererError> {
+        let query_pool = if supported {
+            let create_info = vk::QueryPoolCreateInfo {
+                query_type: vk::QueryType::PIPELINE_STATISTICS,
+                query_count: MAX_FRAMES_IN_FLIGHT as u32,
+                pipeline_statistics: STATISTICS
+                    .iter()
+                    .fold(vk::QueryPipelineStatisticFlags::empty(), |all, &s| all | s),
+                ..Default::default()
+            };
+            let query_pool = unsafe { device.create_query_pool(&create_info, None) }?;
+            debug!("Pipeline statistics query pool created.");
+            query_pool
+        } else {
+            warn!("No pipeline statistics, the frame stats are counted from the draw calls.");
+            vk::QueryPool::null()
+        };
+
+        Ok(PipelineStatistics {
+            query_pool,
+            pending: vec![None; MAX_FRAMES_IN_FLIGHT],
+        })
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        if self.query_pool != vk::QueryPool::null() {
+            unsafe { device.destroy_query_pool(self.query_pool, None) };
+            debug!("Pipeline statistics query pool dropped.");
+        }
+    }
+
+    pub fn query_pool(&self) -> vk::QueryPool {
+        self.query_pool
+    }
+
+    // Both have to be recorded outside of a render pass.
+    pub fn begin(&self, device: &Device, command_buffer: vk::CommandBuffer, frame: usize) {
+        if self.query_pool != vk::QueryPool::null() {
+            unsafe {
+                device.cmd_reset_query_pool(command_buffer, self.query_pool, frame as u32, 1);
+                device.cmd_begin_query(
+                    command_buffer,
+                    self.query_pool,
+                    frame as u32,
+                    vk::QueryControlFlags::empty(),
+                )
+            };
+        }
+    }
+
+    pub fn end(&self, device: &Device, command_buffer: vk::CommandBuffer, frame: usize) {
+        if self.query_pool != vk::QueryPool::null() {
+            unsafe { device.cmd_end_query(command_buffer, self.query_pool, frame as u32) };
+        }
+    }
+
+    // The frame, with the stats counted while recording it, was submitted.
+    pub fn submitted(&mut self, frame: usize, stats: FrameStats) {
+        self.pending[frame] = Some(stats);
+    }
+
+    // The stats of the last frame submitted with this frame in flight, whose fence has to be signaled.
+    pub fn collect(&mut self, device: &Device, frame: usize) -> Option<FrameStats> {
+        let mut stats = self.pending[frame].take()?;
+        if self.query_pool == vk::QueryPool::null() {
+            return Some(stats);
+        }
+
+        let mut results = [[0u64; STATISTICS.len()]];
+        match unsafe {
+            device.get_query_pool_results(
+                self.query_pool,
+                frame as u32,
+                1,
+                &mut results,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        } {
+            Ok(()) => {
+                let [vertices, primitives, vertex_invocations, fragment_invocations] = results[0];
+                stats.vertices = vertices;
+                stats.triangles = primitives;
+                stats.vertex_shader_invocations = Some(vertex_invocations);
+                stats.fragment_shader_invocations = Some(fragment_invocations);
+            }
+            // Not waited for, the counts of the draw calls are kept.
+            Err(err) => trace!("Pipeline statistics not available : {}", err),
+        }
+
+        Some(stats)
+    }
+}
@@ -1,6 +1,11 @@
-use std::{error::Error, fs::File, io::Read, path::Path};
+use std::{fs, path::Path};
 
-pub fn read_shader(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
-    let spv = File::open(path)?;
-    Ok(spv.bytes().filter_map(|b| b.ok()).collect::<Vec<u8>>())
+use super::RendererError;
+use crate::shader_compiler;
+
+// Checked to be SPIR-V, and aligned as Vulkan needs it.
+pub fn read_shader(path: &Path) -> Result<Vec<u32>, RendererError> {
+    let path = path.to_string_lossy();
+    let bytes = fs::read(path.as_ref()).map_err(|err| RendererError::shader(&path, err))?;
+    shader_compiler::spirv_words(&bytes).map_err(|err| RendererError::shader(&path, err))
 }
@@ -31,11 +31,15 @@ pub struct Vertex {
     pub pos: [f32; 3],
     pub color: [f32; 3],
     pub tex_coord: [f32; 2],
+    pub normal: [f32; 3],
 }
 
 impl PartialEq for Vertex {
     fn eq(&self, other: &Self) -> bool {
-        self.pos == other.pos && self.color == other.color && self.tex_coord == other.tex_coord
+        self.pos == other.pos
+            && self.color == other.color
+            && self.tex_coord == other.tex_coord
+            && self.normal == other.normal
     }
 }
 
@@ -51,6 +55,9 @@ impl Hash for Vertex {
         self.color[2].to_bits().hash(state);
         self.tex_coord[0].to_bits().hash(state);
         self.tex_coord[1].to_bits().hash(state);
+        self.normal[0].to_bits().hash(state);
+        self.normal[1].to_bits().hash(state);
+        self.normal[2].to_bits().hash(state);
     }
 }
 
@@ -63,7 +70,7 @@ impl Vertex {
         }
     }
 
-    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
+    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
         [
             vk::VertexInputAttributeDescription {
                 binding: 0,
@@ -83,6 +90,12 @@ impl Vertex {
                 format: vk::Format::R32G32_SFLOAT,
                 offset: offset_of!(Self, tex_coord) as u32,
             },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Self, normal) as u32,
+            },
         ]
     }
 }
@@ -104,9 +117,100 @@ impl<T> DerefMut for Align16<T> {
     }
 }
 
+pub const MAX_LIGHTS: usize = 8;
+
+// std140 layout of a light, see shader.frag.
 #[repr(C)]
-pub struct UniformBufferObject {
-    pub model: Align16<Matrix4<f32>>,
+#[derive(Clone, Copy, Default)]
+pub struct LightData {
+    // w is 0 for a directional light, xyz being the direction the light travels in, and 1 for a point light.
+    pub position: [f32; 4],
+    // Color premultiplied by the intensity, w is the range of point lights.
+    pub color: [f32; 4],
+}
+
+#[repr(C)]
+pub struct GlobalUniformBufferObject {
     pub view: Align16<Matrix4<f32>>,
     pub proj: Align16<Matrix4<f32>>,
+    pub ambient: Align16<[f32; 4]>,
+    pub light_count: Align16<u32>,
+    pub lights: [LightData; MAX_LIGHTS],
+}
+
+#[repr(C)]
+pub struct ModelUniformBufferObject {
+    pub model: Align16<Matrix4<f32>>,
+    pub base_color: Align16<[f32; 4]>,
+    pub alpha_cutoff: f32,
+}
+
+// See tonemap.frag.
+#[repr(C)]
+pub struct TonemapPushConstants {
+    pub tonemap: u32,
+    pub exposure: f32,
+    pub transform: u32,
+    pub paper_white: f32,
+    pub peak_brightness: f32,
+}
+
+// See debug.frag.
+#[repr(C)]
+pub struct DebugPushConstants {
+    pub mode: u32,
+    pub near: f32,
+    pub far: f32,
+}
+
+// See the post_*.frag shaders.
+#[repr(C)]
+#[derive(Default)]
+pub struct PostPushConstants {
+    // Size of a texel of the input image in texture coordinates.
+    pub texel_size: [f32; 2],
+    // Axis of the blur.
+    pub direction: [f32; 2],
+    // Meaning depends on the shader.
+    pub params: [[f32; 4]; 3],
+}
+
+// What the last frame read back drew, see Renderer::frame_stats.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    // Primitives assembled, triangles unless a pipeline draws lines or points. Counted by the device
+    // when it supports pipeline statistics, from the draw calls otherwise.
+    pub triangles: u64,
+    pub vertices: u64,
+    pub culled_objects: u32,
+    // vkCmdBindDescriptorSets and vkCmdBindPipeline calls.
+    pub descriptor_set_binds: u32,
+    pub pipeline_binds: u32,
+    // Only with pipeline statistics.
+    pub vertex_shader_invocations: Option<u64>,
+    pub fragment_shader_invocations: Option<u64>,
+}
+
+// What --list-devices prints, with the result of each check pick_physical_device makes.
+#[derive(Debug)]
+pub struct DeviceInfo {
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub checks: Vec<(&'static str, bool)>,
+}
+
+impl DeviceInfo {
+    pub fn is_suitable(&self) -> bool {
+        self.checks.iter().all(|&(_, passed)| passed)
+    }
+}
+
+// Host visible copy of a swapchain image, filled by the command buffer of the frame it was requested in.
+pub struct Screenshot {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub extent: vk::Extent2D,
+    pub format: vk::Format,
 }
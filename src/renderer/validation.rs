@@ -0,0 +1,105 @@
+use std::{
+    ffi::{c_void, CStr},
+    sync::Mutex,
+};
+
+use ash::vk;
+use log::{log, Level};
+
+use super::RendererConfig;
+
+// What the debug messenger callback needs. Boxed so that its address, given to Vulkan as the user
+// data, doesn't change when the renderer moves.
+pub struct Validation {
+    suppressed_messages: Vec<String>,
+    panic_on_error: bool,
+    errors: Mutex<Vec<String>>,
+}
+
+impl Validation {
+    pub fn new(config: &RendererConfig) -> Box<Self> {
+        Box::new(Validation {
+            suppressed_messages: config.suppressed_messages.clone(),
+            panic_on_error: config.panic_on_validation_error,
+            errors: Mutex::new(vec![]),
+        })
+    }
+
+    // Every severity is asked for, the log level decides what is shown.
+    pub fn messenger_create_info(&self) -> vk::DebugUtilsMessengerCreateInfoEXT {
+        vk::DebugUtilsMessengerCreateInfoEXT {
+            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+                | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            pfn_user_callback: Some(debug_utils_callback),
+            p_user_data: self as *const Validation as *mut c_void,
+            ..Default::default()
+        }
+    }
+
+    // Panics with the errors reported since the last call, see RendererConfig::panic_on_validation_error.
+    // The callback can't panic itself, unwinding out of an extern "system" function aborts.
+    pub fn check_errors(&self) {
+        let errors = std::mem::take(&mut *self.errors.lock().unwrap());
+        if !errors.is_empty() {
+            panic!("Validation errors :\n{}", errors.join("\n"));
+        }
+    }
+
+    fn is_suppressed(&self, id_name: &str, id_number: i32) -> bool {
+        self.suppressed_messages.iter().any(|message| {
+            message == id_name
+                || message.parse::<i32>() == Ok(id_number)
+                || message
+                    .strip_prefix("0x")
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    == Some(id_number as u32)
+        })
+    }
+}
+
+unsafe extern "system" fn debug_utils_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    p_user_data: *mut c_void,
+) -> vk::Bool32 {
+    let validation = &*(p_user_data as *const Validation);
+    let callback_data = &*p_callback_data;
+    let id_name = if callback_data.p_message_id_name.is_null() {
+        "".into()
+    } else {
+        CStr::from_ptr(callback_data.p_message_id_name).to_string_lossy()
+    };
+    if validation.is_suppressed(&id_name, callback_data.message_id_number) {
+        return vk::FALSE;
+    }
+
+    let level = match message_severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => Level::Trace,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => Level::Debug,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => Level::Warn,
+        _ => Level::Error,
+    };
+    // Filtered with RUST_LOG, like vulkan::validation=warn.
+    let target = match message_type {
+        vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE => "vulkan::performance",
+        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION => "vulkan::validation",
+        _ => "vulkan::general",
+    };
+
+    let msg = CStr::from_ptr(callback_data.p_message).to_string_lossy();
+
+    log!(target: target, level, "{}", msg);
+    if level == Level::Error && validation.panic_on_error {
+        if let Ok(mut errors) = validation.errors.lock() {
+            errors.push(msg.into_owned());
+        }
+    }
+
+    vk::FALSE
+}
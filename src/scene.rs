@@ -0,0 +1,391 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use cgmath::{Deg, Point3};
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::{
+    AlphaMode, Camera, Light, Material, PipelineDesc, PostPass, Projection, Renderer, Tonemap,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum SceneProjection {
+    // Vertical field of view in degrees.
+    Perspective { fov_y: f32 },
+    Orthographic { height: f32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SceneCamera {
+    pub position: [f32; 3],
+    // Degrees, a yaw of 0 looks down -Z.
+    pub yaw: f32,
+    pub pitch: f32,
+    pub projection: SceneProjection,
+    pub near: f32,
+    pub far: f32,
+    pub reversed_z: bool,
+}
+
+impl Default for SceneCamera {
+    fn default() -> Self {
+        SceneCamera::from_camera(&Camera::default())
+    }
+}
+
+impl SceneCamera {
+    fn from_camera(camera: &Camera) -> Self {
+        SceneCamera {
+            position: camera.position.into(),
+            yaw: camera.yaw.0,
+            pitch: camera.pitch.0,
+            projection: match camera.projection {
+                Projection::Perspective { fov_y } => {
+                    SceneProjection::Perspective { fov_y: fov_y.0 }
+                }
+                Projection::Orthographic { height } => SceneProjection::Orthographic { height },
+            },
+            near: camera.near,
+            far: camera.far,
+            reversed_z: camera.reversed_z,
+        }
+    }
+
+    // The aspect ratio follows the window, so it is kept from the current camera.
+    fn apply(&self, camera: &mut Camera) {
+        camera.position = Point3::from(self.position);
+        camera.yaw = Deg(self.yaw);
+        camera.pitch = Deg(self.pitch);
+        camera.projection = match self.projection {
+            SceneProjection::Perspective { fov_y } => Projection::Perspective { fov_y: Deg(fov_y) },
+            SceneProjection::Orthographic { height } => Projection::Orthographic { height },
+        };
+        camera.near = self.near;
+        camera.far = self.far;
+        camera.reversed_z = self.reversed_z;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SceneMaterial {
+    pub texture: String,
+    #[serde(default = "white")]
+    pub base_color: [f32; 4],
+    #[serde(default, skip_serializing_if = "is_opaque")]
+    pub alpha_mode: AlphaMode,
+    // Name of one of the scene pipelines, the lit and textured one when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<String>,
+}
+
+fn white() -> [f32; 4] {
+    [1.0, 1.0, 1.0, 1.0]
+}
+
+fn is_opaque(alpha_mode: &AlphaMode) -> bool {
+    *alpha_mode == AlphaMode::Opaque
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct SceneTransform {
+    pub position: [f32; 3],
+    // Degrees, see Model::model_matrix.
+    pub theta: f32,
+    pub scale: f32,
+}
+
+impl Default for SceneTransform {
+    fn default() -> Self {
+        SceneTransform {
+            position: [0.0, 0.0, 0.0],
+            theta: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+// A level of detail generated by simplifying the mesh, see Renderer::generate_model_lods.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SceneLod {
+    pub ratio: f32,
+    pub distance: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SceneModel {
+    pub name: String,
+    pub mesh: String,
+    pub material: String,
+    #[serde(default)]
+    pub triangulate: bool,
+    #[serde(default)]
+    pub transform: SceneTransform,
+    #[serde(default)]
+    pub lods: Vec<SceneLod>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Scene {
+    pub clear_color: [f32; 4],
+    pub tonemap: Tonemap,
+    pub exposure: f32,
+    // Passes are applied in the order they are listed.
+    pub post: Vec<PostPass>,
+    pub camera: SceneCamera,
+    pub ambient_light: [f32; 3],
+    pub lights: Vec<Light>,
+    // See PipelineDesc, the unset fields being those of the lit and textured pipeline.
+    pub pipelines: BTreeMap<String, PipelineDesc>,
+    pub materials: BTreeMap<String, SceneMaterial>,
+    pub models: Vec<SceneModel>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            tonemap: Tonemap::Aces,
+            exposure: 1.0,
+            post: vec![],
+            camera: SceneCamera::default(),
+            ambient_light: [1.0, 1.0, 1.0],
+            lights: vec![],
+            pipelines: BTreeMap::new(),
+            materials: BTreeMap::new(),
+            models: vec![],
+        }
+    }
+}
+
+impl Scene {
+    // A scene with a single model at the origin, textured with `texture`.
+    pub fn from_model(mesh: &str, texture: &str) -> Self {
+        let name = Path::new(mesh)
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        let mut materials = BTreeMap::new();
+        materials.insert(
+            name.clone(),
+            SceneMaterial {
+                texture: texture.to_owned(),
+                base_color: white(),
+                alpha_mode: AlphaMode::Opaque,
+                pipeline: None,
+            },
+        );
+
+        Scene {
+            materials,
+            models: vec![SceneModel {
+                name: name.clone(),
+                mesh: mesh.to_owned(),
+                material: name,
+                triangulate: true,
+                transform: SceneTransform::default(),
+                lods: vec![],
+            }],
+            ..Default::default()
+        }
+    }
+
+    // The format is picked from the extension, either .ron or .json.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ron") => Ok(ron::from_str(&text)?),
+            Some("json") => Ok(serde_json::from_str(&text)?),
+            _ => Err("Unknown scene file format !")?,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let text = match path.extension().and_then(|e| e.to_str()) {
+            Some("ron") => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?,
+            Some("json") => serde_json::to_string_pretty(self)?,
+            _ => Err("Unknown scene file format !")?,
+        };
+        fs::write(path, text)?;
+
+        Ok(())
+    }
+
+    // Loads the models and sets up the camera and lighting. Returns the renderer index of each model by name.
+    pub fn build(&self, renderer: &mut Renderer) -> Result<HashMap<String, usize>, Box<dyn Error>> {
+        let mut models = HashMap::new();
+        for model in self.models.iter() {
+            let material = match self.materials.get(&model.material) {
+                Some(material) => material,
+                None => Err(format!("Unknown material {} !", model.material))?,
+            };
+            let pipeline = match &material.pipeline {
+                Some(name) => match self.pipelines.get(name) {
+                    Some(pipeline) => pipeline.clone(),
+                    None => Err(format!("Unknown pipeline {} !", name))?,
+                },
+                None => PipelineDesc::default(),
+            };
+            let i = renderer.load_model(&model.mesh, &material.texture, model.triangulate)?;
+            let lods = model
+                .lods
+                .iter()
+                .map(|lod| (lod.ratio, lod.distance))
+                .collect::<Vec<(f32, f32)>>();
+            renderer.generate_model_lods(i, &lods)?;
+
+            let m = renderer.model(i);
+            m.name = model.name.clone();
+            m.material = Material {
+                name: model.material.clone(),
+                base_color: material.base_color,
+                alpha_mode: material.alpha_mode,
+                pipeline,
+            };
+            m.position = Point3::from(model.transform.position);
+            m.theta = model.transform.theta;
+            m.scale = model.transform.scale;
+            models.insert(model.name.clone(), i);
+        }
+
+        self.camera.apply(&mut renderer.camera);
+        renderer.clear_color = self.clear_color;
+        renderer.tonemap = self.tonemap;
+        renderer.exposure = self.exposure;
+        renderer.set_post_passes(self.post.clone())?;
+        renderer.ambient_light = self.ambient_light;
+        renderer.lights = self.lights.clone();
+
+        Ok(models)
+    }
+
+    // Describes what the renderer currently shows, so that it can be saved and loaded back.
+    pub fn capture(renderer: &Renderer) -> Self {
+        let mut pipelines: BTreeMap<String, PipelineDesc> = BTreeMap::new();
+        let mut materials = BTreeMap::new();
+        let mut models = vec![];
+        for model in renderer.models().iter() {
+            // Named after the first material using them.
+            let pipeline = if model.material.pipeline == PipelineDesc::default() {
+                None
+            } else {
+                let pipeline = &model.material.pipeline;
+                match pipelines.iter().find(|(_, p)| *p == pipeline) {
+                    Some((name, _)) => Some(name.clone()),
+                    None => {
+                        let name = free_name(&pipelines, &model.material.name, &model.name);
+                        pipelines.insert(name.clone(), pipeline.clone());
+                        Some(name)
+                    }
+                }
+            };
+            let material = SceneMaterial {
+                texture: model.texture_path().to_owned(),
+                base_color: model.material.base_color,
+                alpha_mode: model.material.alpha_mode,
+                pipeline,
+            };
+            // Models sharing a material name may still have been given different textures or colors.
+            let name = if materials
+                .get(&model.material.name)
+                .is_some_and(|m: &SceneMaterial| *m != material)
+            {
+                free_name(&materials, &model.material.name, &model.name)
+            } else {
+                model.material.name.clone()
+            };
+            materials.insert(name.clone(), material);
+
+            models.push(SceneModel {
+                name: model.name.clone(),
+                mesh: model.obj_path().to_owned(),
+                material: name,
+                triangulate: model.triangulated(),
+                transform: SceneTransform {
+                    position: model.position.into(),
+                    theta: model.theta,
+                    scale: model.scale,
+                },
+                lods: model
+                    .lods()
+                    .iter()
+                    .skip(1)
+                    .map(|lod| SceneLod {
+                        ratio: lod.ratio(),
+                        distance: lod.distance(),
+                    })
+                    .collect(),
+            });
+        }
+
+        Scene {
+            clear_color: renderer.clear_color,
+            tonemap: renderer.tonemap,
+            exposure: renderer.exposure,
+            post: renderer.post_passes().to_vec(),
+            camera: SceneCamera::from_camera(&renderer.camera),
+            ambient_light: renderer.ambient_light,
+            lights: renderer.lights.clone(),
+            pipelines,
+            materials,
+            models,
+        }
+    }
+}
+
+// `name` when it isn't taken yet, otherwise `<name>_<model>`, numbered if that is taken too.
+fn free_name<T>(taken: &BTreeMap<String, T>, name: &str, model: &str) -> String {
+    if !taken.contains_key(name) {
+        return name.to_owned();
+    }
+    let renamed = format!("{}_{}", name, model);
+    let mut free = renamed.clone();
+    let mut i = 2;
+    while taken.contains_key(&free) {
+        free = format!("{}_{}", renamed, i);
+        i += 1;
+    }
+
+    free
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_scene_round_trips() {
+        let scene = Scene::load(Path::new("scenes/default.ron")).unwrap();
+        assert_eq!(scene.post.len(), 4);
+        assert!(scene.post.iter().all(|pass| pass.enabled));
+        assert_eq!(
+            scene.lights[0],
+            Light::Directional {
+                direction: cgmath::Vector3::new(-0.4, -1.0, -0.6),
+                color: [1.0, 0.95, 0.85],
+                intensity: 0.9,
+            }
+        );
+
+        let text = ron::ser::to_string(&scene).unwrap();
+        let reloaded: Scene = ron::from_str(&text).unwrap();
+        assert_eq!(reloaded.post, scene.post);
+        assert_eq!(reloaded.lights, scene.lights);
+        assert_eq!(reloaded.materials, scene.materials);
+    }
+
+    #[test]
+    fn free_name_skips_taken_names() {
+        let mut taken = BTreeMap::new();
+        assert_eq!(free_name(&taken, "wood", "table"), "wood");
+        taken.insert("wood".to_owned(), ());
+        assert_eq!(free_name(&taken, "wood", "table"), "wood_table");
+        taken.insert("wood_table".to_owned(), ());
+        assert_eq!(free_name(&taken, "wood", "table"), "wood_table_2");
+    }
+}
@@ -0,0 +1,137 @@
+use std::{error::Error, fs, path::Path};
+
+use naga::{
+    back::spv,
+    front::{glsl, wgsl},
+    valid::{Capabilities, ValidationFlags, Validator},
+    Module, ShaderStage, SourceLocation,
+};
+
+// First word of every SPIR-V module, in the byte order of the machine that wrote it.
+pub const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+// Compiles a GLSL (.vert, .frag or .comp) or WGSL (.wgsl) source to SPIR-V. Errors are reported as
+// `path:line:column: message`, one per line.
+pub fn compile(path: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let extension = Path::new(path).extension().and_then(|e| e.to_str());
+    let module = match extension {
+        Some("wgsl") => wgsl::parse_str(&source)
+            .map_err(|err| located(path, err.location(&source), &err.to_string()))?,
+        _ => {
+            let stage = match extension {
+                Some("vert") => ShaderStage::Vertex,
+                Some("frag") => ShaderStage::Fragment,
+                Some("comp") => ShaderStage::Compute,
+                _ => Err(format!("{}: unknown shader stage !", path))?,
+            };
+            parse_glsl(path, &source, stage)?
+        }
+    };
+
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|err| located(path, err.location(&source), &err.as_inner().to_string()))?;
+    let options = spv::Options {
+        // The shaders are written for Vulkan's clip space already.
+        flags: spv::WriterFlags::empty(),
+        ..Default::default()
+    };
+    let words = spv::write_vec(&module, &info, &options, None)
+        .map_err(|err| format!("{}: {}", path, err))?;
+
+    Ok(words)
+}
+
+// Compiles `source` and writes the SPIR-V to `output`, which is left as is on error.
+pub fn compile_to_file(source: &str, output: &str) -> Result<(), Box<dyn Error>> {
+    let words = compile(source)?;
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_ne_bytes())
+        .collect::<Vec<u8>>();
+    fs::write(output, bytes)?;
+
+    Ok(())
+}
+
+// Checks that `bytes` look like a SPIR-V module, and returns its words in the byte order of this
+// machine.
+pub fn spirv_words(bytes: &[u8]) -> Result<Vec<u32>, Box<dyn Error>> {
+    if !bytes.len().is_multiple_of(4) {
+        Err(format!(
+            "SPIR-V size {} isn't a multiple of 4 !",
+            bytes.len()
+        ))?
+    }
+    let mut words = bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect::<Vec<u32>>();
+    // The header is the magic number, version, generator, bound and a reserved zero.
+    if words.len() < 5 {
+        Err("SPIR-V header is truncated !")?
+    }
+    // Written on a machine with the other byte order.
+    if words[0] == SPIRV_MAGIC.swap_bytes() {
+        for word in words.iter_mut() {
+            *word = word.swap_bytes();
+        }
+    }
+    if words[0] != SPIRV_MAGIC {
+        Err(format!("Bad SPIR-V magic number {:#010x} !", words[0]))?
+    }
+
+    Ok(words)
+}
+
+fn parse_glsl(path: &str, source: &str, stage: ShaderStage) -> Result<Module, String> {
+    glsl::Frontend::default()
+        .parse(&glsl::Options::from(stage), source)
+        .map_err(|errors| {
+            errors
+                .errors
+                .iter()
+                .map(|err| {
+                    let location = err.meta.to_range().map(|_| err.meta.location(source));
+                    located(path, location, &err.kind.to_string())
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        })
+}
+
+fn located(path: &str, location: Option<SourceLocation>, message: &str) -> String {
+    match location {
+        Some(location) => format!(
+            "{}:{}:{}: {}",
+            path, location.line_number, location.line_position, message
+        ),
+        None => format!("{}: {}", path, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(words: &[u32], to_bytes: fn(u32) -> [u8; 4]) -> Vec<u8> {
+        words.iter().flat_map(|&word| to_bytes(word)).collect()
+    }
+
+    #[test]
+    fn spirv_words_reads_both_byte_orders() {
+        let words = [SPIRV_MAGIC, 0x0001_0000, 0, 1, 0];
+        for to_bytes in [u32::to_le_bytes, u32::to_be_bytes] {
+            assert_eq!(spirv_words(&module(&words, to_bytes)).unwrap(), words);
+        }
+    }
+
+    #[test]
+    fn spirv_words_rejects_bad_headers() {
+        let words = [SPIRV_MAGIC, 0x0001_0000, 0, 1, 0];
+        assert!(spirv_words(&module(&words, u32::to_ne_bytes)[..18]).is_err());
+        assert!(spirv_words(&module(&words[..4], u32::to_ne_bytes)).is_err());
+        assert!(spirv_words(&module(&[0xdead_beef, 0, 0, 0, 0], u32::to_ne_bytes)).is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use std::{fs, path::Path};
+
+// Shared with build.rs, which compiles everything found here.
+pub const SHADER_DIR: &str = "shaders";
+const EXTENSIONS: [&str; 4] = ["vert", "frag", "comp", "wgsl"];
+
+// Each GLSL or WGSL source in the shaders directory and the SPIR-V file it is compiled to, sorted by
+// source.
+pub fn shaders() -> Vec<(String, String)> {
+    let entries = match fs::read_dir(SHADER_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut shaders = vec![];
+    for entry in entries.flatten() {
+        let source = Path::new(SHADER_DIR).join(entry.file_name());
+        if let (Some(source_str), Some(output)) = (source.to_str(), output_path(&source)) {
+            shaders.push((source_str.to_owned(), output));
+        }
+    }
+    shaders.sort();
+
+    shaders
+}
+
+// name.stage is compiled to name_stage.spv, name.wgsl holding all its stages to name.spv. The main
+// shader.vert and shader.frag keep their short vert.spv and frag.spv.
+fn output_path(source: &Path) -> Option<String> {
+    let stem = source.file_stem()?.to_str()?;
+    let extension = source.extension()?.to_str()?;
+    if !EXTENSIONS.contains(&extension) {
+        return None;
+    }
+    let name = match (stem, extension) {
+        (_, "wgsl") => stem.to_owned(),
+        ("shader", _) => extension.to_owned(),
+        _ => format!("{}_{}", stem, extension),
+    };
+
+    Some(format!("{}/{}.spv", SHADER_DIR, name))
+}
@@ -0,0 +1,53 @@
+use std::{
+    collections::HashMap,
+    fs,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::shader_list;
+
+// Checking more often than this only costs file system calls, saving a file isn't that fast.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Notices the shader sources being saved or added, by polling the shaders directory.
+pub struct ShaderWatcher {
+    modified: HashMap<String, SystemTime>,
+    next_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Self {
+        ShaderWatcher {
+            modified: shader_list::shaders()
+                .into_iter()
+                .filter_map(|(source, _)| Some((source.clone(), modified(&source)?)))
+                .collect(),
+            next_poll: Instant::now() + POLL_INTERVAL,
+        }
+    }
+
+    // The shaders whose source changed since the last call, as (source, output) pairs.
+    pub fn changed(&mut self) -> Vec<(String, String)> {
+        let now = Instant::now();
+        if now < self.next_poll {
+            return vec![];
+        }
+        self.next_poll = now + POLL_INTERVAL;
+
+        let mut changed = vec![];
+        for (source, output) in shader_list::shaders() {
+            // A missing file is being rewritten by the editor, it will show up again.
+            if let Some(time) = modified(&source) {
+                if self.modified.insert(source.clone(), time) != Some(time) {
+                    changed.push((source, output));
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}